@@ -1,49 +1,77 @@
 "use client";
 
 import React, { useEffect, useState } from "react";
+import { Horizon, Contract, nativeToScVal, scValToNative, TransactionBuilder, Networks, rpc } from "@stellar/stellar-sdk";
 
 interface OrderBookEdge {
     price: string;
     amount: string;
 }
 
+// Order book prices are quote units per 10^7 base units; amounts are in stroops
+const PRICE_SCALE = 10_000_000;
+const STROOPS = 10_000_000;
+const LEVELS = 8;
+
 export function Orderbook() {
     const [bids, setBids] = useState<OrderBookEdge[]>([]);
     const [asks, setAsks] = useState<OrderBookEdge[]>([]);
     const [loading, setLoading] = useState(true);
 
     useEffect(() => {
-        // Generate aesthetic mock orderbook data
-        const basePrice = 0.1250;
-        const _bids = Array.from({ length: 8 }).map((_, i) => ({
-            price: (basePrice - (i * 0.0005)).toFixed(4),
-            amount: (Math.random() * 5000 + 1000).toFixed(2)
-        }));
-        const _asks = Array.from({ length: 8 }).map((_, i) => ({
-            price: (basePrice + (i * 0.0005) + 0.0002).toFixed(4),
-            amount: (Math.random() * 5000 + 1000).toFixed(2)
-        })).reverse();
-        setBids(_bids);
-        setAsks(_asks);
-        setLoading(false);
-
-        // Simulate live updates every 3s
-        const interval = setInterval(() => {
-            const newBids = Array.from({ length: 8 }).map((_, i) => ({
-                price: (basePrice - (i * 0.0005) + (Math.random() * 0.0002 - 0.0001)).toFixed(4),
-                amount: (Math.random() * 5000 + 1000).toFixed(2)
-            }));
-            const newAsks = Array.from({ length: 8 }).map((_, i) => ({
-                price: (basePrice + (i * 0.0005) + 0.0002 + (Math.random() * 0.0002 - 0.0001)).toFixed(4),
-                amount: (Math.random() * 5000 + 1000).toFixed(2)
-            })).reverse();
-            setBids(newBids);
-            setAsks(newAsks);
-        }, 3000);
+        const fetchDepth = async () => {
+            try {
+                const contractId = process.env.NEXT_PUBLIC_ORDERBOOK_ADDRESS;
+                if (!contractId) return;
+
+                const server = new Horizon.Server("https://horizon-testnet.stellar.org");
+                const defaultAddress = "GBV2VSXKD6CY3XNZOVKIWAEXBHYU3XDQWOGOZSFI27SDCA6SGST73ZBQ";
+                const account = await server.loadAccount(defaultAddress).catch(() => null);
+                if (!account) return;
+
+                const rpcServer = new rpc.Server("https://soroban-testnet.stellar.org");
+                const contract = new Contract(contractId);
 
+                const operation = contract.call("depth", nativeToScVal(LEVELS, { type: "u32" }));
+                const tx = new TransactionBuilder(account, { fee: "1000", networkPassphrase: Networks.TESTNET })
+                    .addOperation(operation)
+                    .setTimeout(30)
+                    .build();
+
+                // eslint-disable-next-line @typescript-eslint/no-explicit-any
+                const sim = await rpcServer.simulateTransaction(tx as any);
+                if (rpc.Api.isSimulationSuccess(sim)) {
+                    // eslint-disable-next-line @typescript-eslint/no-explicit-any
+                    const [rawBids, rawAsks] = scValToNative((sim.result as any).retval);
+                    // eslint-disable-next-line @typescript-eslint/no-explicit-any
+                    const toEdge = (l: any): OrderBookEdge => ({
+                        price: (Number(l.price) / PRICE_SCALE).toFixed(4),
+                        amount: (Number(l.amount) / STROOPS).toFixed(2),
+                    });
+                    // Bids come best (highest) first; asks best (lowest) first,
+                    // so reverse asks to stack them above the spread
+                    setBids(rawBids.map(toEdge));
+                    setAsks(rawAsks.map(toEdge).reverse());
+                }
+            } catch (e) {
+                console.error("Failed to fetch order book depth", e);
+            } finally {
+                setLoading(false);
+            }
+        };
+
+        fetchDepth();
+        const interval = setInterval(fetchDepth, 5000);
         return () => clearInterval(interval);
     }, []);
 
+    const bestBid = bids.length > 0 ? Number(bids[0].price) : null;
+    const bestAsk = asks.length > 0 ? Number(asks[asks.length - 1].price) : null;
+    const mid = bestBid !== null && bestAsk !== null
+        ? ((bestBid + bestAsk) / 2).toFixed(4)
+        : (bestBid ?? bestAsk)?.toFixed(4) ?? "—";
+    const maxAmount = Math.max(1, ...bids.map(b => Number(b.amount)), ...asks.map(a => Number(a.amount)));
+
     if (loading) {
         return (
             <div className="bg-zinc-950 border border-zinc-900 rounded-3xl p-6 w-full h-[400px] flex items-center justify-center">
@@ -69,7 +97,7 @@ export function Orderbook() {
             {/* Asks (Sell Orders - Red) */}
             <div className="flex flex-col gap-1 mb-4">
                 {asks.map((ask, i) => {
-                    const depth = (parseInt(ask.amount) / 8000) * 100;
+                    const depth = (Number(ask.amount) / maxAmount) * 100;
                     return (
                         <div key={`ask-${i}`} className="flex relative items-center px-2 py-0.5 group hover:bg-zinc-900 transition rounded cursor-pointer">
                             <div className="absolute right-0 top-0 bottom-0 bg-red-500/10 z-0" style={{ width: `${Math.min(depth, 100)}%` }} />
@@ -81,13 +109,13 @@ export function Orderbook() {
             </div>
 
             <div className="flex items-center justify-center py-2 text-lg text-primary font-bold bg-primary/5 rounded-lg mb-4 cursor-pointer hover:bg-primary/10 transition">
-                0.1250 <span className="text-xs text-zinc-500 ml-2 font-sans font-medium">$0.12</span>
+                {mid} <span className="text-xs text-zinc-500 ml-2 font-sans font-medium">USDC</span>
             </div>
 
             {/* Bids (Buy Orders - Green) */}
             <div className="flex flex-col gap-1">
                 {bids.map((bid, i) => {
-                    const depth = (parseInt(bid.amount) / 8000) * 100;
+                    const depth = (Number(bid.amount) / maxAmount) * 100;
                     return (
                         <div key={`bid-${i}`} className="flex relative items-center px-2 py-0.5 group hover:bg-zinc-900 transition rounded cursor-pointer">
                             <div className="absolute right-0 top-0 bottom-0 bg-green-500/10 z-0" style={{ width: `${Math.min(depth, 100)}%` }} />
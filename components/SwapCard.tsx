@@ -187,13 +187,16 @@ export function SwapCard() {
             const amountIn = BigInt(Math.floor(payAmtNum * 1e7));
             const minOut = BigInt(1);
 
+            // Partner deployments set this to earn a share of swap fees
+            const referrer = process.env.NEXT_PUBLIC_REFERRER_ADDRESS;
             const methodName = `swap_${payToken.symbol.toLowerCase()}_for_${receiveToken.symbol.toLowerCase()}`;
 
             const operation = contract.call(
                 methodName,
                 nativeToScVal(address, { type: "address" }),
                 nativeToScVal(amountIn, { type: "i128" }),
-                nativeToScVal(minOut, { type: "i128" })
+                nativeToScVal(minOut, { type: "i128" }),
+                referrer ? nativeToScVal(referrer, { type: "address" }) : nativeToScVal(null)
             );
 
             const tx = new TransactionBuilder(account, {
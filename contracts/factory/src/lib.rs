@@ -0,0 +1,309 @@
+#![no_std]
+
+use soroban_sdk::{
+    contract, contractclient, contracterror, contractevent, contractimpl, contracttype,
+    panic_with_error, xdr::ToXdr, Address, Bytes, BytesN, Env, IntoVal, Val, Vec,
+};
+
+// ─────────────────────────────────────────────
+//  StellarDex pool factory
+// ─────────────────────────────────────────────
+//  Deploys StellarDex pools from a stored WASM hash and initialises them
+//  in the same call, so nobody can slip in between deploy and initialize.
+//  The deploy salt is derived from the (xlm, usdc, eth) asset set, so
+//  each set has exactly one pool address, known before it is created.
+//
+//  The registry keeps one persistent entry per pool, both in the global
+//  list and in each of its pairs' lists, so no single entry grows with
+//  the number of pools. Entries are bumped whenever they are
+//  written, and anyone can bump them later through `bump_pool`.
+
+// ─────────────────────────────────────────────
+//  Storage Keys
+// ─────────────────────────────────────────────
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    Admin,
+    PoolWasm,
+    PoolCount,
+    PoolAt(u32),
+    Pool(BytesN<32>),
+    PairCount(Address, Address),
+    PairPool(Address, Address, u32),
+}
+
+/// Registry entry for a deployed pool, stored under `PoolAt(index)`.
+/// `pair_slots` is the pool's index in the xlm/usdc, xlm/eth and
+/// usdc/eth pair lists.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolInfo {
+    pub pool:       Address,
+    pub xlm:        Address,
+    pub usdc:       Address,
+    pub eth:        Address,
+    pub pair_slots: (u32, u32, u32),
+}
+
+/// Most pools returned by one `all_pools` or `pools_for_pair` call.
+pub const MAX_PAGE: u32 = 50;
+
+// ─────────────────────────────────────────────
+//  Storage TTL  ← ledgers; ~17_280 per day at 5s close time
+// ─────────────────────────────────────────────
+const DAY_IN_LEDGERS: u32 = 17_280;
+const TTL_THRESHOLD: u32 = 7 * DAY_IN_LEDGERS;
+const TTL_EXTEND_TO: u32 = 30 * DAY_IN_LEDGERS;
+
+// ─────────────────────────────────────────────
+//  Pool interface
+// ─────────────────────────────────────────────
+#[contractclient(name = "PoolClient")]
+pub trait Pool {
+    fn initialize(
+        env: Env,
+        admin: Address,
+        xlm_token: Address,
+        usdc_token: Address,
+        eth_token: Address,
+        xlm_per_usdc: i128,
+        xlm_per_eth: i128,
+    );
+}
+
+// ─────────────────────────────────────────────
+//  Errors
+// ─────────────────────────────────────────────
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum FactoryError {
+    NotInitialized = 1,
+    AlreadyInit    = 2,
+    PoolExists     = 3,
+    SameToken      = 4,
+    NoPool         = 5,
+}
+
+// ─────────────────────────────────────────────
+//  Events
+// ─────────────────────────────────────────────
+#[contractevent]
+pub struct PoolCreatedEvent {
+    #[topic]
+    pub pool: Address,
+    pub xlm:  Address,
+    pub usdc: Address,
+    pub eth:  Address,
+}
+
+// ─────────────────────────────────────────────
+//  Contract
+// ─────────────────────────────────────────────
+#[contract]
+pub struct PoolFactory;
+
+#[contractimpl]
+impl PoolFactory {
+
+    // ═══════════════════════════════════════
+    //  INITIALISATION
+    // ═══════════════════════════════════════
+    pub fn initialize(env: Env, admin: Address, pool_wasm_hash: BytesN<32>) {
+        if env.storage().instance().has(&DataKey::Admin) {
+            panic_with_error!(&env, FactoryError::AlreadyInit);
+        }
+        admin.require_auth();
+
+        env.storage().instance().set(&DataKey::Admin,     &admin);
+        env.storage().instance().set(&DataKey::PoolWasm,  &pool_wasm_hash);
+        env.storage().instance().set(&DataKey::PoolCount, &0_u32);
+        env.storage().instance().extend_ttl(TTL_THRESHOLD, TTL_EXTEND_TO);
+    }
+
+    // ═══════════════════════════════════════
+    //  ADMIN
+    // ═══════════════════════════════════════
+
+    /// WASM used for pools created from now on; existing pools are untouched.
+    pub fn set_pool_wasm(env: Env, pool_wasm_hash: BytesN<32>) {
+        Self::require_admin(&env);
+        env.storage().instance().set(&DataKey::PoolWasm, &pool_wasm_hash);
+    }
+
+    /// Deploy and initialise the pool for an asset set, with the factory
+    /// admin as pool admin. Each asset set can only be deployed once.
+    pub fn create_pool(
+        env: Env,
+        xlm_token: Address,
+        usdc_token: Address,
+        eth_token: Address,
+        xlm_per_usdc: i128,
+        xlm_per_eth: i128,
+    ) -> Address {
+        let admin = Self::require_admin(&env);
+        env.storage().instance().extend_ttl(TTL_THRESHOLD, TTL_EXTEND_TO);
+        if xlm_token == usdc_token || xlm_token == eth_token || usdc_token == eth_token {
+            panic_with_error!(&env, FactoryError::SameToken);
+        }
+        let salt = Self::salt(&env, &xlm_token, &usdc_token, &eth_token);
+        if env.storage().persistent().has(&DataKey::Pool(salt.clone())) {
+            panic_with_error!(&env, FactoryError::PoolExists);
+        }
+
+        let wasm: BytesN<32> = env.storage().instance().get(&DataKey::PoolWasm).unwrap();
+        let pool = env.deployer().with_current_contract(salt.clone()).deploy_v2(wasm, ());
+        PoolClient::new(&env, &pool).initialize(
+            &admin, &xlm_token, &usdc_token, &eth_token, &xlm_per_usdc, &xlm_per_eth,
+        );
+
+        Self::save(&env, &DataKey::Pool(salt), &pool);
+        let count: u32 = env.storage().instance().get(&DataKey::PoolCount).unwrap_or(0);
+        let pair_slots = (
+            Self::register_pair(&env, &xlm_token,  &usdc_token, &pool),
+            Self::register_pair(&env, &xlm_token,  &eth_token,  &pool),
+            Self::register_pair(&env, &usdc_token, &eth_token,  &pool),
+        );
+        let info = PoolInfo {
+            pool: pool.clone(), xlm: xlm_token.clone(), usdc: usdc_token.clone(), eth: eth_token.clone(), pair_slots,
+        };
+        Self::save(&env, &DataKey::PoolAt(count), &info);
+        env.storage().instance().set(&DataKey::PoolCount, &(count + 1));
+
+        PoolCreatedEvent { pool: pool.clone(), xlm: xlm_token, usdc: usdc_token, eth: eth_token }.publish(&env);
+        pool
+    }
+
+    /// Extend the TTL of every registry entry for the pool at `index`.
+    /// Anyone may call this to keep a quiet pool discoverable.
+    pub fn bump_pool(env: Env, index: u32) {
+        let info = Self::pool_at(env.clone(), index)
+            .unwrap_or_else(|| panic_with_error!(&env, FactoryError::NoPool));
+        let salt = Self::salt(&env, &info.xlm, &info.usdc, &info.eth);
+        let (xlm_usdc, xlm_eth, usdc_eth) = info.pair_slots;
+        for key in [
+            DataKey::PoolAt(index),
+            DataKey::Pool(salt),
+            Self::pair_count_key(&info.xlm,  &info.usdc),
+            Self::pair_count_key(&info.xlm,  &info.eth),
+            Self::pair_count_key(&info.usdc, &info.eth),
+            Self::pair_pool_key(&info.xlm,  &info.usdc, xlm_usdc),
+            Self::pair_pool_key(&info.xlm,  &info.eth,  xlm_eth),
+            Self::pair_pool_key(&info.usdc, &info.eth,  usdc_eth),
+        ] {
+            env.storage().persistent().extend_ttl(&key, TTL_THRESHOLD, TTL_EXTEND_TO);
+        }
+        env.storage().instance().extend_ttl(TTL_THRESHOLD, TTL_EXTEND_TO);
+    }
+
+    // ═══════════════════════════════════════
+    //  VIEW
+    // ═══════════════════════════════════════
+
+    /// Address the pool for this asset set has, or will have once created.
+    pub fn pool_address(env: Env, xlm_token: Address, usdc_token: Address, eth_token: Address) -> Address {
+        let salt = Self::salt(&env, &xlm_token, &usdc_token, &eth_token);
+        env.deployer().with_current_contract(salt).deployed_address()
+    }
+
+    pub fn get_pool(env: Env, xlm_token: Address, usdc_token: Address, eth_token: Address) -> Option<Address> {
+        let salt = Self::salt(&env, &xlm_token, &usdc_token, &eth_token);
+        env.storage().persistent().get(&DataKey::Pool(salt))
+    }
+
+    /// Up to `limit` pools (at most `MAX_PAGE`) trading `token_a` against
+    /// `token_b`, in either order, starting at index `start` of the pair.
+    pub fn pools_for_pair(env: Env, token_a: Address, token_b: Address, start: u32, limit: u32) -> Vec<Address> {
+        let count = Self::pair_pool_count(env.clone(), token_a.clone(), token_b.clone());
+        let end = count.min(start.saturating_add(limit.min(MAX_PAGE)));
+        let mut out = Vec::new(&env);
+        for index in start..end {
+            out.push_back(env.storage().persistent().get(&Self::pair_pool_key(&token_a, &token_b, index)).unwrap());
+        }
+        out
+    }
+
+    pub fn pair_pool_count(env: Env, token_a: Address, token_b: Address) -> u32 {
+        env.storage().persistent().get(&Self::pair_count_key(&token_a, &token_b)).unwrap_or(0)
+    }
+
+    /// Up to `limit` pools (at most `MAX_PAGE`) in creation order,
+    /// starting at index `start`.
+    pub fn all_pools(env: Env, start: u32, limit: u32) -> Vec<Address> {
+        let end = Self::pool_count(env.clone()).min(start.saturating_add(limit.min(MAX_PAGE)));
+        let mut out = Vec::new(&env);
+        for index in start..end {
+            let info: PoolInfo = env.storage().persistent().get(&DataKey::PoolAt(index)).unwrap();
+            out.push_back(info.pool);
+        }
+        out
+    }
+
+    pub fn pool_count(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::PoolCount).unwrap_or(0)
+    }
+
+    pub fn pool_at(env: Env, index: u32) -> Option<PoolInfo> {
+        env.storage().persistent().get(&DataKey::PoolAt(index))
+    }
+
+    pub fn pool_wasm(env: Env) -> BytesN<32> {
+        env.storage().instance().get(&DataKey::PoolWasm)
+            .unwrap_or_else(|| panic_with_error!(&env, FactoryError::NotInitialized))
+    }
+
+    pub fn admin(env: Env) -> Address {
+        env.storage().instance().get(&DataKey::Admin)
+            .unwrap_or_else(|| panic_with_error!(&env, FactoryError::NotInitialized))
+    }
+
+    // ═══════════════════════════════════════
+    //  INTERNAL HELPERS
+    // ═══════════════════════════════════════
+    fn require_admin(env: &Env) -> Address {
+        let admin: Address = env.storage().instance().get(&DataKey::Admin)
+            .unwrap_or_else(|| panic_with_error!(env, FactoryError::NotInitialized));
+        admin.require_auth();
+        admin
+    }
+
+    fn salt(env: &Env, xlm: &Address, usdc: &Address, eth: &Address) -> BytesN<32> {
+        let mut preimage = Bytes::new(env);
+        preimage.append(&xlm.clone().to_xdr(env));
+        preimage.append(&usdc.clone().to_xdr(env));
+        preimage.append(&eth.clone().to_xdr(env));
+        env.crypto().sha256(&preimage).into()
+    }
+
+    fn ordered(a: &Address, b: &Address) -> (Address, Address) {
+        if a < b { (a.clone(), b.clone()) } else { (b.clone(), a.clone()) }
+    }
+
+    fn pair_count_key(a: &Address, b: &Address) -> DataKey {
+        let (lo, hi) = Self::ordered(a, b);
+        DataKey::PairCount(lo, hi)
+    }
+
+    fn pair_pool_key(a: &Address, b: &Address, index: u32) -> DataKey {
+        let (lo, hi) = Self::ordered(a, b);
+        DataKey::PairPool(lo, hi, index)
+    }
+
+    /// Append `pool` to the pair's list and return its index there.
+    fn register_pair(env: &Env, a: &Address, b: &Address, pool: &Address) -> u32 {
+        let count_key = Self::pair_count_key(a, b);
+        let index: u32 = env.storage().persistent().get(&count_key).unwrap_or(0);
+        Self::save(env, &Self::pair_pool_key(a, b, index), pool);
+        Self::save(env, &count_key, &(index + 1));
+        index
+    }
+
+    /// Persistent write that also bumps the entry's TTL.
+    fn save<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+        env.storage().persistent().set(key, value);
+        env.storage().persistent().extend_ttl(key, TTL_THRESHOLD, TTL_EXTEND_TO);
+    }
+}
+
+mod test;
@@ -0,0 +1,235 @@
+#![cfg(test)]
+
+extern crate std;
+
+use soroban_sdk::{testutils::Address as _, Address, BytesN, Env};
+
+use crate::{FactoryError, PoolFactory, PoolFactoryClient};
+
+// ─────────────────────────────────────────────
+//  Helpers
+// ─────────────────────────────────────────────
+
+fn create_factory<'a>(env: &'a Env, admin: &Address, pool_wasm: &BytesN<32>) -> PoolFactoryClient<'a> {
+    let factory = PoolFactoryClient::new(env, &env.register(PoolFactory, ()));
+    factory.initialize(admin, pool_wasm);
+    factory
+}
+
+/// Placeholder hash for tests that never reach the deploy.
+fn unused_wasm(env: &Env) -> BytesN<32> {
+    BytesN::from_array(env, &[0; 32])
+}
+
+const USDC_RATE: i128 = 61_900_000;
+const ETH_RATE:  i128 = 121_200_000_000;
+
+// ─────────────────────────────────────────────
+//  1. GUARDS
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_create_pool_guards() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let factory = create_factory(&env, &admin, &unused_wasm(&env));
+    let (xlm, usdc) = (Address::generate(&env), Address::generate(&env));
+
+    assert_eq!(
+        factory.try_create_pool(&xlm, &usdc, &usdc, &USDC_RATE, &ETH_RATE),
+        Err(Ok(FactoryError::SameToken.into()))
+    );
+    assert_eq!(
+        factory.try_initialize(&admin, &unused_wasm(&env)),
+        Err(Ok(FactoryError::AlreadyInit.into()))
+    );
+    assert_eq!(factory.try_bump_pool(&0), Err(Ok(FactoryError::NoPool.into())));
+    assert_eq!(factory.pool_count(), 0);
+    assert_eq!(factory.all_pools(&0, &10).len(), 0);
+    assert_eq!(factory.pools_for_pair(&xlm, &usdc, &0, &10).len(), 0);
+}
+
+#[test]
+#[should_panic]
+fn test_create_pool_requires_admin() {
+    let env = Env::default();
+    let admin = Address::generate(&env);
+    let factory = PoolFactoryClient::new(&env, &env.register(PoolFactory, ()));
+    env.mock_all_auths();
+    factory.initialize(&admin, &unused_wasm(&env));
+    env.set_auths(&[]);
+
+    let (xlm, usdc, eth) = (Address::generate(&env), Address::generate(&env), Address::generate(&env));
+    factory.create_pool(&xlm, &usdc, &eth, &USDC_RATE, &ETH_RATE);
+}
+
+// ─────────────────────────────────────────────
+//  2. DEPLOYMENT  ← needs the built contracts
+// ─────────────────────────────────────────────
+//  These deploy the real StellarDex WASM, so they only run with the
+//  `pool-wasm` feature after `stellar contract build` (see the Makefile).
+
+#[cfg(feature = "pool-wasm")]
+mod deploy {
+    use soroban_sdk::{
+        testutils::{storage::Persistent as _, Address as _, Ledger},
+        vec, Address, Bytes, BytesN, Env, Vec,
+    };
+
+    use super::{create_factory, ETH_RATE, USDC_RATE};
+    use crate::{DataKey, FactoryError, PoolFactoryClient, PoolInfo, TTL_EXTEND_TO};
+
+    #[allow(clippy::too_many_arguments)]
+    mod pool {
+        soroban_sdk::contractimport!(file = "../../target/wasm32v1-none/release/hello_world.wasm");
+    }
+
+    /// A contract with no `initialize`, so pool setup fails.
+    mod no_init {
+        soroban_sdk::contractimport!(file = "../../target/wasm32v1-none/release/router.wasm");
+    }
+
+    fn upload(env: &Env, wasm: &[u8]) -> BytesN<32> {
+        env.deployer().upload_contract_wasm(Bytes::from_slice(env, wasm))
+    }
+
+    fn setup(env: &Env) -> (Address, PoolFactoryClient<'_>) {
+        env.mock_all_auths();
+        let admin = Address::generate(env);
+        let factory = create_factory(env, &admin, &upload(env, pool::WASM));
+        (admin, factory)
+    }
+
+    fn three(env: &Env) -> (Address, Address, Address) {
+        (Address::generate(env), Address::generate(env), Address::generate(env))
+    }
+
+    #[test]
+    fn test_create_pool_at_predicted_address() {
+        let env = Env::default();
+        let (_, factory) = setup(&env);
+        let (xlm, usdc, eth) = three(&env);
+
+        let predicted = factory.pool_address(&xlm, &usdc, &eth);
+        assert_eq!(factory.get_pool(&xlm, &usdc, &eth), None);
+
+        let pool = factory.create_pool(&xlm, &usdc, &eth, &USDC_RATE, &ETH_RATE);
+        assert_eq!(pool, predicted);
+        assert_eq!(factory.get_pool(&xlm, &usdc, &eth), Some(pool.clone()));
+        assert_eq!(factory.pool_count(), 1);
+        assert_eq!(
+            factory.pool_at(&0),
+            Some(PoolInfo { pool, xlm, usdc, eth, pair_slots: (0, 0, 0) })
+        );
+    }
+
+    #[test]
+    fn test_created_pool_is_initialised() {
+        let env = Env::default();
+        let (admin, factory) = setup(&env);
+        let (xlm, usdc, eth) = three(&env);
+
+        let pool = pool::Client::new(&env, &factory.create_pool(&xlm, &usdc, &eth, &USDC_RATE, &ETH_RATE));
+        assert_eq!(pool.get_rates(), (USDC_RATE, ETH_RATE));
+        assert_eq!(pool.get_admin(), Some(admin));
+        assert_eq!(pool.get_reserves(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_asset_set_deploys_once() {
+        let env = Env::default();
+        let (_, factory) = setup(&env);
+        let (xlm, usdc, eth) = three(&env);
+
+        factory.create_pool(&xlm, &usdc, &eth, &USDC_RATE, &ETH_RATE);
+        assert_eq!(
+            factory.try_create_pool(&xlm, &usdc, &eth, &USDC_RATE, &ETH_RATE),
+            Err(Ok(FactoryError::PoolExists.into()))
+        );
+
+        // A different asset set gets its own address
+        let eth2 = Address::generate(&env);
+        let other = factory.create_pool(&xlm, &usdc, &eth2, &USDC_RATE, &ETH_RATE);
+        assert_ne!(Some(other), factory.get_pool(&xlm, &usdc, &eth));
+        assert_eq!(factory.pool_count(), 2);
+    }
+
+    #[test]
+    fn test_failed_initialize_rolls_back_deploy() {
+        let env = Env::default();
+        let (_, factory) = setup(&env);
+        factory.set_pool_wasm(&upload(&env, no_init::WASM));
+        let (xlm, usdc, eth) = three(&env);
+
+        assert!(factory.try_create_pool(&xlm, &usdc, &eth, &USDC_RATE, &ETH_RATE).is_err());
+        assert_eq!(factory.get_pool(&xlm, &usdc, &eth), None);
+        assert_eq!(factory.pool_count(), 0);
+
+        // Nothing was left behind at the address, so a fixed WASM can still use it
+        factory.set_pool_wasm(&upload(&env, pool::WASM));
+        let pool = factory.create_pool(&xlm, &usdc, &eth, &USDC_RATE, &ETH_RATE);
+        assert_eq!(pool, factory.pool_address(&xlm, &usdc, &eth));
+    }
+
+    // ─── Registry ───
+
+    #[test]
+    fn test_all_pools_pages_in_creation_order() {
+        let env = Env::default();
+        let (_, factory) = setup(&env);
+        let (xlm, usdc) = (Address::generate(&env), Address::generate(&env));
+
+        let mut created = Vec::new(&env);
+        for _ in 0..3 {
+            created.push_back(factory.create_pool(&xlm, &usdc, &Address::generate(&env), &USDC_RATE, &ETH_RATE));
+        }
+
+        assert_eq!(factory.all_pools(&0, &u32::MAX), created);
+        assert_eq!(factory.all_pools(&1, &5), created.slice(1..));
+        assert_eq!(factory.all_pools(&3, &5).len(), 0);
+    }
+
+    #[test]
+    fn test_pools_for_pair_in_either_order() {
+        let env = Env::default();
+        let (_, factory) = setup(&env);
+        let (xlm, usdc) = (Address::generate(&env), Address::generate(&env));
+        let (eth_a, eth_b) = (Address::generate(&env), Address::generate(&env));
+
+        let pool_a = factory.create_pool(&xlm, &usdc, &eth_a, &USDC_RATE, &ETH_RATE);
+        let pool_b = factory.create_pool(&xlm, &usdc, &eth_b, &USDC_RATE, &ETH_RATE);
+
+        let xlm_usdc = factory.pools_for_pair(&xlm, &usdc, &0, &10);
+        assert_eq!(factory.pair_pool_count(&usdc, &xlm), 2);
+        assert_eq!(xlm_usdc, factory.pools_for_pair(&usdc, &xlm, &0, &10));
+        assert_eq!(xlm_usdc, vec![&env, pool_a.clone(), pool_b.clone()]);
+        assert_eq!(factory.pools_for_pair(&xlm, &usdc, &1, &10), vec![&env, pool_b.clone()]);
+
+        let usdc_eth_a = factory.pools_for_pair(&eth_a, &usdc, &0, &10);
+        assert_eq!(usdc_eth_a, vec![&env, pool_a]);
+        assert_eq!(factory.pool_at(&1).unwrap().pair_slots, (1, 1, 0));
+        assert_eq!(factory.pools_for_pair(&eth_a, &eth_b, &0, &10).len(), 0);
+    }
+
+    #[test]
+    fn test_bump_pool_keeps_registry_alive() {
+        let env = Env::default();
+        let (_, factory) = setup(&env);
+        let (xlm, usdc, eth) = three(&env);
+        factory.create_pool(&xlm, &usdc, &eth, &USDC_RATE, &ETH_RATE);
+
+        let ttl = |key: DataKey| env.as_contract(&factory.address, || env.storage().persistent().get_ttl(&key));
+        let pair = crate::PoolFactory::pair_pool_key(&usdc, &eth, 0);
+        assert_eq!(ttl(DataKey::PoolAt(0)), TTL_EXTEND_TO);
+        assert_eq!(ttl(pair.clone()), TTL_EXTEND_TO);
+
+        env.ledger().with_mut(|li| li.sequence_number += TTL_EXTEND_TO - 10);
+        assert_eq!(ttl(DataKey::PoolAt(0)), 10);
+
+        factory.bump_pool(&0);
+        assert_eq!(ttl(DataKey::PoolAt(0)), TTL_EXTEND_TO);
+        assert_eq!(ttl(pair), TTL_EXTEND_TO);
+        assert_eq!(factory.get_pool(&xlm, &usdc, &eth), factory.pool_at(&0).map(|info| info.pool));
+    }
+}
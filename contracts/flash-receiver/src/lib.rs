@@ -0,0 +1,110 @@
+#![no_std]
+
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, panic_with_error, token, Address, Bytes,
+    Env,
+};
+
+// ─────────────────────────────────────────────
+//  Sample flash-loan receiver for StellarDex
+// ─────────────────────────────────────────────
+//  The owner asks the pool for a loan naming this contract as receiver.
+//  StellarDex transfers `amount` of `token` here, calls `on_flash_loan`,
+//  and then checks that `amount + fee` came back. A real borrower would
+//  use the funds (arbitrage, liquidation, …) before repaying; this one
+//  simply pays the loan back out of its own balance.
+//
+//  Callbacks are only honoured when they come from the configured pool
+//  and the loan or swap was started by the owner, so nobody else can
+//  make this contract pay fees or transfer funds.
+//
+//  `on_flash_swap` works the same way for `flash_swap`: the output
+//  arrives first and the quoted input is paid afterwards.
+
+// ─────────────────────────────────────────────
+//  STORAGE KEYS
+// ─────────────────────────────────────────────
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    Owner,
+    Pool,
+}
+
+// ─────────────────────────────────────────────
+//  ERRORS
+// ─────────────────────────────────────────────
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum ReceiverError {
+    AlreadyInit   = 1,
+    NotInit       = 2,
+    UnknownLender = 3,
+    NotOwner      = 4,
+}
+
+// ─────────────────────────────────────────────
+//  CONTRACT
+// ─────────────────────────────────────────────
+#[contract]
+pub struct FlashReceiver;
+
+#[contractimpl]
+impl FlashReceiver {
+    /// Bind the receiver to the account allowed to start loans and the
+    /// pool allowed to call back.
+    pub fn initialize(env: Env, owner: Address, pool: Address) {
+        if env.storage().instance().has(&DataKey::Owner) {
+            panic_with_error!(&env, ReceiverError::AlreadyInit);
+        }
+        env.storage().instance().set(&DataKey::Owner, &owner);
+        env.storage().instance().set(&DataKey::Pool,  &pool);
+    }
+
+    pub fn on_flash_loan(
+        env: Env,
+        lender: Address,
+        initiator: Address,
+        token: Address,
+        amount: i128,
+        fee: i128,
+        _data: Bytes,
+    ) {
+        Self::require_callback(&env, &lender, &initiator);
+        token::Client::new(&env, &token)
+            .transfer(&env.current_contract_address(), &lender, &(amount + fee));
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn on_flash_swap(
+        env: Env,
+        pool: Address,
+        initiator: Address,
+        token_in: Address,
+        amount_in: i128,
+        _token_out: Address,
+        _amount_out: i128,
+        _data: Bytes,
+    ) {
+        Self::require_callback(&env, &pool, &initiator);
+        token::Client::new(&env, &token_in)
+            .transfer(&env.current_contract_address(), &pool, &amount_in);
+    }
+
+    // ═══════════════════════════════════════
+    //  INTERNAL HELPERS
+    // ═══════════════════════════════════════
+
+    /// Accept a callback only from the configured pool, for a loan or swap
+    /// the owner started.
+    fn require_callback(env: &Env, lender: &Address, initiator: &Address) {
+        let pool: Address = env.storage().instance().get(&DataKey::Pool)
+            .unwrap_or_else(|| panic_with_error!(env, ReceiverError::NotInit));
+        if *lender != pool { panic_with_error!(env, ReceiverError::UnknownLender); }
+        lender.require_auth();
+
+        let owner: Address = env.storage().instance().get(&DataKey::Owner).unwrap();
+        if *initiator != owner { panic_with_error!(env, ReceiverError::NotOwner); }
+    }
+}
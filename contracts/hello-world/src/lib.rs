@@ -1,8 +1,8 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, contracterror, contractevent,
-    token, Address, Env, Symbol, symbol_short,
+    contract, contractclient, contractimpl, contracttype, contracterror, contractevent,
+    token, xdr::ToXdr, Address, Bytes, BytesN, Env, IntoVal, Symbol, Val, Vec, symbol_short,
     panic_with_error,
 };
 
@@ -26,6 +26,407 @@ pub enum DataKey {
     LpBalance(Address),
     TotalLp,
     Paused,
+    Role(Role, Address),
+    Guardians,
+    GuardianThreshold,
+    NextProposalId,
+    Proposal(u64),
+    OpPaused(PauseFlag),
+    AssetPaused(Address),
+    PairPaused(Address, Address),
+    Emergency,
+    WithdrawBlockedAt,
+    WithdrawOpenedAt,
+    TtlConfig,
+    SchemaVersion,
+    WasmAllowed(BytesN<32>),
+    WasmHistory,
+    FaucetConfig,
+    FaucetClaim(Address, Address),
+    FaucetSpent(Address, u64),
+    SupplyCap(Address),
+    MinterQuota(Address, Address),
+    MinterUsage(Address, Address),
+    FlashFeeBps,
+    Escrowed(Address),
+    NextOrderId,
+    Order(u64),
+    OwnerOrders(Address),
+    DcaTipBps,
+    NextDcaId,
+    Dca(u64),
+    OwnerDcas(Address),
+    NextTriggerId,
+    Trigger(u64),
+    OwnerTriggers(Address),
+    NextTwapId,
+    Twap(u64),
+    OwnerTwaps(Address),
+    CommitConfig,
+    NextCommitId,
+    Commit(u64),
+    AuctionLedgers,
+    MinIntent,
+    NextIntentId,
+    Intent(u64),
+    BatchIntents(u32),
+    RewardToken,
+    RewardPool,
+    Stake(Address),
+    Lock(Address),
+    ReferralBps,
+    ReferrerBps(Address),
+    Referral(Address, Address),
+}
+
+/// Storage layout version written by this build. Deployments that predate
+/// versioning have no `SchemaVersion` entry and read as version 0.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Longest stretch (seconds) that any pause configuration can keep
+/// `remove_liquidity` closed. Past this, withdrawals reopen on their own.
+pub const MAX_PAUSE_SECS: u64 = 7 * 24 * 60 * 60;
+
+// ─────────────────────────────────────────────
+//  Storage TTL  ← ledgers; ~17_280 per day at 5s close time
+// ─────────────────────────────────────────────
+const DAY_IN_LEDGERS: u32 = 17_280;
+pub const DEFAULT_TTL_THRESHOLD: u32 = 7 * DAY_IN_LEDGERS;
+pub const DEFAULT_TTL_EXTEND_TO: u32 = 30 * DAY_IN_LEDGERS;
+
+/// Recorded reserves next to the tokens the contract actually holds
+/// (excluding order escrow).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Solvency {
+    pub xlm_reserve:  i128,
+    pub xlm_balance:  i128,
+    pub usdc_reserve: i128,
+    pub usdc_balance: i128,
+    pub eth_reserve:  i128,
+    pub eth_balance:  i128,
+}
+
+/// Self-serve test-token faucet. Each address may claim one drip per
+/// token every `cooldown_secs`; the daily budgets cap all claims per UTC day.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FaucetConfig {
+    pub enabled:           bool,
+    pub usdc_per_claim:    i128,
+    pub eth_per_claim:     i128,
+    pub cooldown_secs:     u64,
+    pub daily_usdc_budget: i128,
+    pub daily_eth_budget:  i128,
+}
+
+/// Longest faucet cooldown the admin can configure.
+pub const MAX_FAUCET_COOLDOWN_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Most a minter may mint of one token in any span of `window_secs`.
+/// Mints are tallied in `QUOTA_BUCKETS` time buckets per window, and a
+/// bucket counts until all of it is older than the window, so a mint can
+/// hold quota for up to one bucket longer than `window_secs`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MinterQuota {
+    pub limit:       i128,
+    pub window_secs: u64,
+}
+
+/// Recent mints against a quota as (bucket index, amount), oldest first.
+/// Bucket `i` covers `[i * width, (i + 1) * width)` seconds.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MinterUsage {
+    pub buckets: Vec<(u64, i128)>,
+}
+
+pub const QUOTA_BUCKETS: u64 = 8;
+
+/// One exact-input leg of `swap_batch`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwapLeg {
+    pub token_in:  Address,
+    pub token_out: Address,
+    pub amount_in: i128,
+    pub min_out:   i128,
+}
+
+/// Resting order to sell `amount` of `sell` for `buy` once the pool pays
+/// at least `limit_price` of `buy` per `PRICE_SCALE` units of `sell`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LimitOrder {
+    pub owner:       Address,
+    pub sell:        Address,
+    pub buy:         Address,
+    pub amount:      i128,
+    pub limit_price: i128,
+    pub expiry:      u64,
+}
+
+/// Fixed-point scale of `LimitOrder::limit_price`.
+pub const PRICE_SCALE: i128 = 10_000_000;
+
+/// Dollar-cost-averaging plan: every `interval_secs`, sell `per_period`
+/// of `sell` (from the unspent `remaining` deposit) for `buy`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DcaPlan {
+    pub owner:         Address,
+    pub sell:          Address,
+    pub buy:           Address,
+    pub per_period:    i128,
+    pub min_out:       i128,
+    pub interval_secs: u64,
+    pub next_at:       u64,
+    pub remaining:     i128,
+}
+
+/// Direction in which a trigger order's pool price must cross.
+#[contracttype]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TriggerKind {
+    /// Fires once the price falls to `trigger_price` or below
+    StopLoss,
+    /// Fires once the price rises to `trigger_price` or above
+    TakeProfit,
+}
+
+/// Escrowed swap that fires when the pool price (of `buy` per
+/// `PRICE_SCALE` units of `sell`, after fees) crosses `trigger_price`,
+/// and only if it still gets at least `limit_price`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TriggerOrder {
+    pub owner:         Address,
+    pub sell:          Address,
+    pub buy:           Address,
+    pub amount:        i128,
+    pub kind:          TriggerKind,
+    pub trigger_price: i128,
+    pub limit_price:   i128,
+}
+
+/// Large swap split into `chunks` equal slices, executed by keepers no
+/// closer than `interval_ledgers` apart. The last slice also takes any
+/// rounding remainder.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TwapOrder {
+    pub owner:             Address,
+    pub sell:              Address,
+    pub buy:               Address,
+    pub chunk_size:        i128,
+    pub min_out_per_chunk: i128,
+    pub chunks_left:       u32,
+    pub interval_ledgers:  u32,
+    pub next_ledger:       u32,
+    pub remaining:         i128,
+}
+
+/// A committed, not yet revealed swap. Only the input token and the most
+/// it may spend (`escrow`) are public until reveal. It can be revealed
+/// from `opens_at` through `expires_at`, fixed when it is committed.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwapCommit {
+    pub trader:       Address,
+    pub commitment:   BytesN<32>,
+    pub token_in:     Address,
+    pub escrow:       i128,
+    pub committed_at: u32,
+    pub opens_at:     u32,
+    pub expires_at:   u32,
+}
+
+/// A commitment can be revealed from `reveal_delay` ledgers after it was
+/// made until `reveal_window` ledgers after that; then it expires. Changes
+/// only apply to commitments made afterwards.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommitConfig {
+    pub reveal_delay:  u32,
+    pub reveal_window: u32,
+}
+
+pub const DEFAULT_REVEAL_DELAY: u32  = 1;
+pub const DEFAULT_REVEAL_WINDOW: u32 = 120;
+/// Longest reveal delay or window the admin can configure.
+pub const MAX_REVEAL_LEDGERS: u32   = 3 * DAY_IN_LEDGERS;
+
+/// A swap waiting for its batch auction. `batch` is the ledger at which
+/// the batch closes and becomes clearable.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SwapIntent {
+    pub trader:    Address,
+    pub token_in:  Address,
+    pub token_out: Address,
+    pub amount_in: i128,
+    pub min_out:   i128,
+    pub batch:     u32,
+}
+
+pub const DEFAULT_AUCTION_LEDGERS: u32 = 5;
+pub const MAX_BATCH_INTENTS: u32       = 32;
+
+/// Smallest intent, valued in XLM stroops at the current rates, until the
+/// admin sets another (10 XLM). Keeps batches from being filled with dust.
+pub const DEFAULT_MIN_INTENT: i128 = 100_000_000;
+
+/// Global state of the LP reward emissions. `acc_per_share` is the reward
+/// earned by one unit of weight since staking began, times `REWARD_SCALE`.
+/// Weight is staked shares plus the boosted weight of locks.
+/// `budget` is funded reward not yet emitted; emissions stop when it runs out.
+/// Emission lost to rounding stays in the budget to be emitted later.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RewardPool {
+    pub rate:          i128,
+    pub budget:        i128,
+    pub acc_per_share: i128,
+    pub total_weight:  i128,
+    pub last_update:   u64,
+}
+
+/// One provider's staked LP shares. `reward_debt` is the part of the
+/// provider's `weight * acc_per_share` already accounted for.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Stake {
+    pub shares:      i128,
+    pub reward_debt: i128,
+    pub unclaimed:   i128,
+}
+
+/// Fixed-point scale of `acc_per_share`. Weights run to 1e18 and more for
+/// ordinary positions, so a smaller scale would round emissions to zero.
+pub const REWARD_SCALE: i128 = 1_000_000_000_000_000_000;
+
+/// LP shares locked until `unlock_at`. `weight` is their reward weight,
+/// boosted by the lock length chosen when the lock was last changed.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VeLock {
+    pub shares:    i128,
+    pub weight:    i128,
+    pub unlock_at: u64,
+}
+
+/// Longest lock. A maximum-length lock earns `MAX_LOCK_BOOST_BPS` extra
+/// reward weight (2.5x in total) and starts with voting power equal to
+/// its shares.
+pub const MAX_LOCK_SECS: u64      = 365 * 24 * 60 * 60;
+pub const MAX_LOCK_BOOST_BPS: i128 = 15_000;
+
+/// Share of the 0.3% input fee paid to a swap's referrer when the admin
+/// has set no rate (none, so only referrers the admin has registered are
+/// paid), and the most the admin can set (50%). The rate is the only cap:
+/// a referrer's accrued balance is always that share of the fees it sent.
+pub const DEFAULT_REFERRAL_BPS: u32 = 0;
+pub const MAX_REFERRAL_BPS: u32     = 5_000;
+
+/// Keeper tip on each executed DCA period when none is configured (0.1%).
+pub const DEFAULT_DCA_TIP_BPS: u32 = 10;
+/// Highest DCA keeper tip the FeeManager can set (1%).
+pub const MAX_DCA_TIP_BPS: u32 = 100;
+
+/// Once an entry's TTL drops below `threshold` it is bumped to `extend_to`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TtlConfig {
+    pub threshold: u32,
+    pub extend_to: u32,
+}
+
+// ─────────────────────────────────────────────
+//  Pause Flags  ← per-operation switches, on top of the global `Paused`
+// ─────────────────────────────────────────────
+#[contracttype]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PauseFlag {
+    Swaps,
+    Deposits,
+    Withdrawals,
+    Minting,
+}
+
+// ─────────────────────────────────────────────
+//  Roles  ← granted/revoked by the admin, who implicitly holds all of them
+// ─────────────────────────────────────────────
+#[contracttype]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Role {
+    Pauser,
+    RateSetter,
+    Minter,
+    Upgrader,
+    FeeManager,
+}
+
+/// Flash-loan fee charged when none is configured (0.09%).
+pub const DEFAULT_FLASH_FEE_BPS: u32 = 9;
+
+// ─────────────────────────────────────────────
+//  Flash-loan Receiver Interface
+// ─────────────────────────────────────────────
+/// Implemented by contracts borrowing through `flash_loan`. Before
+/// returning, the receiver must transfer `amount + fee` of `token` back
+/// to `lender`. `initiator` is the account that requested the loan, so a
+/// receiver can refuse loans it did not ask for.
+#[contractclient(name = "FlashReceiverClient")]
+pub trait FlashReceiver {
+    fn on_flash_loan(
+        env: Env,
+        lender: Address,
+        initiator: Address,
+        token: Address,
+        amount: i128,
+        fee: i128,
+        data: Bytes,
+    );
+}
+
+/// Implemented by contracts taking output through `flash_swap`. Before
+/// returning, the receiver must transfer at least `amount_in` of
+/// `token_in` to `pool`. `initiator` is the account that requested the
+/// swap.
+#[contractclient(name = "FlashSwapReceiverClient")]
+pub trait FlashSwapReceiver {
+    #[allow(clippy::too_many_arguments)]
+    fn on_flash_swap(
+        env: Env,
+        pool: Address,
+        initiator: Address,
+        token_in: Address,
+        amount_in: i128,
+        token_out: Address,
+        amount_out: i128,
+        data: Bytes,
+    );
+}
+
+// ─────────────────────────────────────────────
+//  Guardian Proposals  ← critical ops once an M-of-N set is installed
+// ─────────────────────────────────────────────
+#[contracttype]
+#[derive(Clone)]
+pub enum GuardedAction {
+    Upgrade(BytesN<32>),
+    Rollback,
+    TransferAdmin(Address),
+    SetGuardians(Vec<Address>, u32),
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct Proposal {
+    pub action:    GuardedAction,
+    pub approvals: Vec<Address>,
+    pub executed:  bool,
 }
 
 // ─────────────────────────────────────────────
@@ -43,6 +444,46 @@ pub enum DexError {
     AlreadyInit       = 6,
     BadToken          = 7,
     InsufficientLp    = 8,
+    MissingRole       = 9,
+    NotGuardian       = 10,
+    AlreadyApproved   = 11,
+    ThresholdNotMet   = 12,
+    NoProposal        = 13,
+    BadThreshold      = 14,
+    GuardiansActive   = 15,
+    OperationPaused   = 16,
+    AssetPaused       = 17,
+    BadTtl            = 18,
+    BadVersion        = 19,
+    WasmNotAllowed    = 20,
+    NoRollback        = 21,
+    FaucetDisabled    = 22,
+    FaucetCooldown    = 23,
+    FaucetExhausted   = 24,
+    MintCapExceeded   = 25,
+    FlashNotRepaid    = 26,
+    BadFee            = 27,
+    EmptyBatch        = 28,
+    NoOrder           = 29,
+    OrderExpired      = 30,
+    BadPrice          = 31,
+    NoDca             = 32,
+    NotDue            = 33,
+    NoTrigger         = 34,
+    NoTwap            = 35,
+    NoCommit          = 36,
+    BadReveal         = 37,
+    CommitExpired     = 38,
+    NoIntent          = 39,
+    BatchFull         = 40,
+    InsufficientStake = 41,
+    NoRewardToken     = 42,
+    LockActive        = 43,
+    NoLock            = 44,
+    BadLockTime       = 45,
+    BadFaucet         = 46,
+    IntentTooSmall    = 47,
+    BadCommitConfig   = 48,
 }
 
 // ─────────────────────────────────────────────
@@ -56,6 +497,85 @@ pub struct SwapEvent {
     pub to_token:   Symbol,
     pub amount_in:  i128,
     pub amount_out: i128,
+    pub referrer:   Option<Address>,
+}
+
+#[contractevent]
+pub struct ReservesEvent {
+    #[topic]
+    pub action: Symbol,
+    pub xlm:    i128,
+    pub usdc:   i128,
+    pub eth:    i128,
+}
+
+#[contractevent]
+pub struct FlashLoanEvent {
+    #[topic]
+    pub token:    Symbol,
+    pub receiver: Address,
+    pub amount:   i128,
+    pub fee:      i128,
+}
+
+#[contractevent]
+pub struct OrderEvent {
+    #[topic]
+    pub action:     Symbol,
+    #[topic]
+    pub order_id:   u64,
+    pub amount_in:  i128,
+    pub amount_out: i128,
+}
+
+#[contractevent]
+pub struct DcaEvent {
+    #[topic]
+    pub action:     Symbol,
+    #[topic]
+    pub plan_id:    u64,
+    pub amount_in:  i128,
+    pub amount_out: i128,
+}
+
+#[contractevent]
+pub struct TriggerEvent {
+    #[topic]
+    pub action:     Symbol,
+    #[topic]
+    pub order_id:   u64,
+    pub amount_in:  i128,
+    pub amount_out: i128,
+}
+
+#[contractevent]
+pub struct TwapEvent {
+    #[topic]
+    pub action:     Symbol,
+    #[topic]
+    pub order_id:   u64,
+    pub amount_in:  i128,
+    pub amount_out: i128,
+}
+
+#[contractevent]
+pub struct CommitEvent {
+    #[topic]
+    pub action:     Symbol,
+    #[topic]
+    pub commit_id:  u64,
+    pub amount_in:  i128,
+    pub amount_out: i128,
+}
+
+#[contractevent]
+pub struct AuctionEvent {
+    #[topic]
+    pub action:     Symbol,
+    #[topic]
+    pub intent_id:  u64,
+    pub amount_in:  i128,
+    pub amount_out: i128,
 }
 
 #[contractevent]
@@ -66,6 +586,69 @@ pub struct MintEvent {
     pub amount: i128,
 }
 
+#[contractevent]
+pub struct RoleEvent {
+    #[topic]
+    pub action:  Symbol,
+    pub role:    Role,
+    pub account: Address,
+}
+
+#[contractevent]
+pub struct GuardianEvent {
+    #[topic]
+    pub action:      Symbol,
+    pub proposal_id: u64,
+    pub guardian:    Address,
+}
+
+#[contractevent]
+pub struct UpgradeEvent {
+    #[topic]
+    pub action:    Symbol,
+    pub wasm_hash: BytesN<32>,
+}
+
+#[contractevent]
+pub struct MigrateEvent {
+    pub from_version: u32,
+    pub to_version:   u32,
+}
+
+#[contractevent]
+pub struct BurnEvent {
+    #[topic]
+    pub token:  Symbol,
+    pub from:   Address,
+    pub amount: i128,
+}
+
+#[contractevent]
+pub struct FaucetEvent {
+    #[topic]
+    pub token:   Symbol,
+    pub claimer: Address,
+    pub amount:  i128,
+}
+
+#[contractevent]
+pub struct StakeEvent {
+    #[topic]
+    pub action:   Symbol,
+    #[topic]
+    pub provider: Address,
+    pub amount:   i128,
+}
+
+#[contractevent]
+pub struct ReferralEvent {
+    #[topic]
+    pub referrer: Address,
+    #[topic]
+    pub token:    Address,
+    pub amount:   i128,
+}
+
 #[contractevent]
 pub struct LiquidityEvent {
     #[topic]
@@ -77,6 +660,14 @@ pub struct LiquidityEvent {
     pub lp_shares:   i128,
 }
 
+/// Where one of the three pool tokens lives in storage.
+struct PoolAsset {
+    token_key:   DataKey,
+    reserve_key: DataKey,
+    rate_key:    Option<DataKey>,
+    sym:         Symbol,
+}
+
 // ─────────────────────────────────────────────
 //  Contract
 // ─────────────────────────────────────────────
@@ -116,28 +707,58 @@ impl StellarDex {
         env.storage().instance().set(&DataKey::TotalEthMinted,  &0_i128);
         env.storage().instance().set(&DataKey::TotalLp,          &0_i128);
         env.storage().instance().set(&DataKey::Paused,            &false);
+        env.storage().instance().set(&DataKey::SchemaVersion, &SCHEMA_VERSION);
     }
 
     // ═══════════════════════════════════════
     //  ADMIN
     // ═══════════════════════════════════════
-    pub fn set_paused(env: Env, paused: bool) {
-        Self::require_admin(&env);
+    pub fn set_paused(env: Env, caller: Address, paused: bool) {
+        Self::require_role(&env, &caller, Role::Pauser);
         env.storage().instance().set(&DataKey::Paused, &paused);
+        Self::track_withdraw_block(&env);
     }
 
-    pub fn set_usdc_rate(env: Env, xlm_per_usdc: i128) {
-        Self::require_admin(&env);
+    pub fn set_op_paused(env: Env, caller: Address, flag: PauseFlag, paused: bool) {
+        Self::require_role(&env, &caller, Role::Pauser);
+        env.storage().instance().set(&DataKey::OpPaused(flag), &paused);
+        Self::track_withdraw_block(&env);
+    }
+
+    /// Halt swaps, deposits and minting while keeping pro-rata
+    /// `remove_liquidity` open regardless of the other pause switches.
+    pub fn set_emergency(env: Env, caller: Address, emergency: bool) {
+        Self::require_role(&env, &caller, Role::Pauser);
+        env.storage().instance().set(&DataKey::Emergency, &emergency);
+    }
+
+    /// Pause every swap, deposit and mint touching `token`. Withdrawals stay
+    /// open so LPs are never trapped by a single-asset incident.
+    pub fn set_asset_paused(env: Env, caller: Address, token: Address, paused: bool) {
+        Self::require_role(&env, &caller, Role::Pauser);
+        env.storage().instance().set(&DataKey::AssetPaused(token), &paused);
+    }
+
+    /// Pause swaps between `token_a` and `token_b` in either direction.
+    pub fn set_pair_paused(env: Env, caller: Address, token_a: Address, token_b: Address, paused: bool) {
+        Self::require_role(&env, &caller, Role::Pauser);
+        env.storage().instance().set(&DataKey::PairPaused(token_a.clone(), token_b.clone()), &paused);
+        env.storage().instance().set(&DataKey::PairPaused(token_b, token_a), &paused);
+    }
+
+    pub fn set_usdc_rate(env: Env, caller: Address, xlm_per_usdc: i128) {
+        Self::require_role(&env, &caller, Role::RateSetter);
         env.storage().instance().set(&DataKey::UsdcRate, &xlm_per_usdc);
     }
 
-    pub fn set_eth_rate(env: Env, xlm_per_eth: i128) {
-        Self::require_admin(&env);
+    pub fn set_eth_rate(env: Env, caller: Address, xlm_per_eth: i128) {
+        Self::require_role(&env, &caller, Role::RateSetter);
         env.storage().instance().set(&DataKey::EthRate, &xlm_per_eth);
     }
 
     pub fn transfer_admin(env: Env, new_admin: Address) {
         Self::require_admin(&env);
+        Self::require_no_guardians(&env);
         new_admin.require_auth();
         env.storage().instance().set(&DataKey::Admin, &new_admin);
     }
@@ -148,84 +769,380 @@ impl StellarDex {
         env.storage().instance().set(&DataKey::XlmToken, &xlm_token);
     }
 
-    /// Upgrade contract WASM in-place (admin only)
-    pub fn upgrade(env: Env, new_wasm_hash: soroban_sdk::BytesN<32>) {
+    pub fn set_ttl_config(env: Env, threshold: u32, extend_to: u32) {
         Self::require_admin(&env);
-        env.deployer().update_current_contract_wasm(new_wasm_hash);
+        if threshold > extend_to || extend_to > env.storage().max_ttl() {
+            panic_with_error!(&env, DexError::BadTtl);
+        }
+        env.storage().instance().set(&DataKey::TtlConfig, &TtlConfig { threshold, extend_to });
     }
 
-    // ═══════════════════════════════════════
-    //  MINTING
-    // ═══════════════════════════════════════
-    pub fn mint_usdc(env: Env, to: Address, amount: i128) {
+    /// Bring storage written by an older build up to `SCHEMA_VERSION`,
+    /// running each pending step once and in order (admin only). Call this
+    /// right after `upgrade`.
+    pub fn migrate(env: Env) {
         Self::require_admin(&env);
-        Self::require_not_paused(&env);
-        if amount <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        let from = Self::version(env.clone());
+        if from >= SCHEMA_VERSION {
+            panic_with_error!(&env, DexError::BadVersion);
+        }
 
-        let usdc: Address = env.storage().instance().get(&DataKey::UsdcToken).unwrap();
-        token::StellarAssetClient::new(&env, &usdc).mint(&to, &amount);
+        for v in from..SCHEMA_VERSION {
+            Self::migrate_step(&env, v);
+            env.storage().instance().set(&DataKey::SchemaVersion, &(v + 1));
+        }
+        Self::extend_instance(&env);
+
+        MigrateEvent { from_version: from, to_version: SCHEMA_VERSION }.publish(&env);
+    }
+
+    pub fn version(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::SchemaVersion).unwrap_or(0)
+    }
 
-        let prev: i128 = env.storage().instance().get(&DataKey::TotalUsdcMinted).unwrap_or(0);
-        env.storage().instance().set(&DataKey::TotalUsdcMinted, &(prev + amount));
+    /// Upgrade contract WASM in-place to an allowlisted hash (Upgrader role)
+    pub fn upgrade(env: Env, caller: Address, new_wasm_hash: BytesN<32>) {
+        Self::require_role(&env, &caller, Role::Upgrader);
+        Self::require_no_guardians(&env);
+        Self::install_wasm(&env, new_wasm_hash);
+    }
 
-        MintEvent { token: symbol_short!("usdc"), to, amount }.publish(&env);
+    /// Reinstall the WASM that was running before the last upgrade
+    /// (Upgrader role). The target must still be allowlisted.
+    pub fn rollback(env: Env, caller: Address) {
+        Self::require_role(&env, &caller, Role::Upgrader);
+        Self::require_no_guardians(&env);
+        Self::rollback_wasm(&env);
     }
 
-    pub fn mint_eth(env: Env, to: Address, amount: i128) {
+    /// Add or remove a WASM hash from the upgrade allowlist (admin only)
+    pub fn allow_wasm(env: Env, wasm_hash: BytesN<32>, allowed: bool) {
         Self::require_admin(&env);
-        Self::require_not_paused(&env);
-        if amount <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        if allowed {
+            env.storage().persistent().set(&DataKey::WasmAllowed(wasm_hash), &true);
+        } else {
+            env.storage().persistent().remove(&DataKey::WasmAllowed(wasm_hash));
+        }
+    }
 
-        let eth: Address = env.storage().instance().get(&DataKey::EthToken).unwrap();
-        token::StellarAssetClient::new(&env, &eth).mint(&to, &amount);
+    /// Record the hash of the WASM installed at deploy time so the first
+    /// upgrade can be rolled back (admin only, before any upgrade).
+    pub fn record_current_wasm(env: Env, wasm_hash: BytesN<32>) {
+        Self::require_admin(&env);
+        let history = Self::wasm_history(env.clone());
+        if !history.is_empty() {
+            panic_with_error!(&env, DexError::AlreadyInit);
+        }
+        let mut history = history;
+        history.push_back(wasm_hash);
+        env.storage().instance().set(&DataKey::WasmHistory, &history);
+    }
 
-        let prev: i128 = env.storage().instance().get(&DataKey::TotalEthMinted).unwrap_or(0);
-        env.storage().instance().set(&DataKey::TotalEthMinted, &(prev + amount));
+    pub fn is_wasm_allowed(env: Env, wasm_hash: BytesN<32>) -> bool {
+        env.storage().persistent().has(&DataKey::WasmAllowed(wasm_hash))
+    }
 
-        MintEvent { token: symbol_short!("eth"), to, amount }.publish(&env);
+    /// Installed WASM hashes, oldest first; the last one is running now.
+    pub fn wasm_history(env: Env) -> Vec<BytesN<32>> {
+        env.storage().instance().get(&DataKey::WasmHistory).unwrap_or(Vec::new(&env))
     }
 
     // ═══════════════════════════════════════
-    //  LIQUIDITY
+    //  ROLES
     // ═══════════════════════════════════════
-    pub fn add_liquidity(
-        env: Env,
-        provider: Address,
-        xlm_amount: i128,
-        usdc_amount: i128,
-        eth_amount: i128,
-    ) -> i128 {
-        provider.require_auth();
-        Self::require_not_paused(&env);
-        if xlm_amount <= 0 && usdc_amount <= 0 && eth_amount <= 0 {
-            panic_with_error!(&env, DexError::ZeroAmount);
-        }
-
-        if xlm_amount > 0 {
-            let xlm: Address = env.storage().instance().get(&DataKey::XlmToken).unwrap();
-            token::Client::new(&env, &xlm)
-                .transfer(&provider, &env.current_contract_address(), &xlm_amount);
-        }
-        if usdc_amount > 0 {
-            let usdc: Address = env.storage().instance().get(&DataKey::UsdcToken).unwrap();
-            token::Client::new(&env, &usdc)
-                .transfer(&provider, &env.current_contract_address(), &usdc_amount);
-        }
-        if eth_amount > 0 {
-            let eth: Address = env.storage().instance().get(&DataKey::EthToken).unwrap();
-            token::Client::new(&env, &eth)
-                .transfer(&provider, &env.current_contract_address(), &eth_amount);
-        }
+    pub fn grant_role(env: Env, role: Role, account: Address) {
+        Self::require_admin(&env);
+        env.storage().persistent().set(&DataKey::Role(role, account.clone()), &true);
+        RoleEvent { action: symbol_short!("grant"), role, account }.publish(&env);
+    }
 
-        let xlm_res: i128  = env.storage().instance().get(&DataKey::XlmReserve).unwrap_or(0);
-        let usdc_res: i128 = env.storage().instance().get(&DataKey::UsdcReserve).unwrap_or(0);
-        let eth_res: i128  = env.storage().instance().get(&DataKey::EthReserve).unwrap_or(0);
+    pub fn revoke_role(env: Env, role: Role, account: Address) {
+        Self::require_admin(&env);
+        env.storage().persistent().remove(&DataKey::Role(role, account.clone()));
+        RoleEvent { action: symbol_short!("revoke"), role, account }.publish(&env);
+    }
 
-        env.storage().instance().set(&DataKey::XlmReserve,  &(xlm_res  + xlm_amount));
-        env.storage().instance().set(&DataKey::UsdcReserve, &(usdc_res + usdc_amount));
-        env.storage().instance().set(&DataKey::EthReserve,  &(eth_res  + eth_amount));
+    /// True if `account` was granted `role` (the admin is not listed here
+    /// even though it passes every role check).
+    pub fn has_role(env: Env, role: Role, account: Address) -> bool {
+        env.storage().persistent().has(&DataKey::Role(role, account))
+    }
 
-        let usdc_rate: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap();
+    // ═══════════════════════════════════════
+    //  GUARDIANS
+    // ═══════════════════════════════════════
+    /// Install the initial M-of-N guardian set (admin only, once). From then
+    /// on `upgrade` and `transfer_admin` only run through proposals, and the
+    /// set itself can only be rotated by a `SetGuardians` proposal.
+    pub fn set_guardians(env: Env, guardians: Vec<Address>, threshold: u32) {
+        Self::require_admin(&env);
+        Self::require_no_guardians(&env);
+        Self::store_guardians(&env, &guardians, threshold);
+    }
+
+    /// Open a proposal; the proposer's approval is recorded immediately.
+    pub fn propose(env: Env, guardian: Address, action: GuardedAction) -> u64 {
+        guardian.require_auth();
+        Self::require_guardian(&env, &guardian);
+
+        let id: u64 = env.storage().instance().get(&DataKey::NextProposalId).unwrap_or(0);
+        env.storage().instance().set(&DataKey::NextProposalId, &(id + 1));
+
+        let mut approvals = Vec::new(&env);
+        approvals.push_back(guardian.clone());
+        let proposal = Proposal { action, approvals, executed: false };
+        env.storage().persistent().set(&DataKey::Proposal(id), &proposal);
+
+        GuardianEvent { action: symbol_short!("propose"), proposal_id: id, guardian }.publish(&env);
+        id
+    }
+
+    pub fn approve(env: Env, guardian: Address, proposal_id: u64) {
+        guardian.require_auth();
+        Self::require_guardian(&env, &guardian);
+
+        let mut proposal = Self::load_proposal(&env, proposal_id);
+        if proposal.approvals.contains(&guardian) {
+            panic_with_error!(&env, DexError::AlreadyApproved);
+        }
+        proposal.approvals.push_back(guardian.clone());
+        env.storage().persistent().set(&DataKey::Proposal(proposal_id), &proposal);
+
+        GuardianEvent { action: symbol_short!("approve"), proposal_id, guardian }.publish(&env);
+    }
+
+    /// Run a proposal once enough *current* guardians have approved it, so
+    /// approvals from rotated-out signers stop counting.
+    pub fn execute(env: Env, guardian: Address, proposal_id: u64) {
+        guardian.require_auth();
+        Self::require_guardian(&env, &guardian);
+
+        let mut proposal = Self::load_proposal(&env, proposal_id);
+        let guardians: Vec<Address> = env.storage().instance()
+            .get(&DataKey::Guardians).unwrap_or(Vec::new(&env));
+        let threshold: u32 = env.storage().instance()
+            .get(&DataKey::GuardianThreshold).unwrap_or(0);
+
+        let mut approved = 0_u32;
+        for g in proposal.approvals.iter() {
+            if guardians.contains(&g) { approved += 1; }
+        }
+        if threshold == 0 || approved < threshold {
+            panic_with_error!(&env, DexError::ThresholdNotMet);
+        }
+
+        proposal.executed = true;
+        env.storage().persistent().set(&DataKey::Proposal(proposal_id), &proposal);
+
+        match proposal.action {
+            GuardedAction::Upgrade(hash) => {
+                Self::install_wasm(&env, hash);
+            }
+            GuardedAction::Rollback => {
+                Self::rollback_wasm(&env);
+            }
+            GuardedAction::TransferAdmin(new_admin) => {
+                new_admin.require_auth();
+                env.storage().instance().set(&DataKey::Admin, &new_admin);
+            }
+            GuardedAction::SetGuardians(new_guardians, new_threshold) => {
+                Self::store_guardians(&env, &new_guardians, new_threshold);
+            }
+        }
+
+        GuardianEvent { action: symbol_short!("execute"), proposal_id, guardian }.publish(&env);
+    }
+
+    pub fn guardians(env: Env) -> (Vec<Address>, u32) {
+        let g: Vec<Address> = env.storage().instance()
+            .get(&DataKey::Guardians).unwrap_or(Vec::new(&env));
+        let t: u32 = env.storage().instance().get(&DataKey::GuardianThreshold).unwrap_or(0);
+        (g, t)
+    }
+
+    pub fn get_proposal(env: Env, proposal_id: u64) -> Proposal {
+        env.storage().persistent()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic_with_error!(&env, DexError::NoProposal))
+    }
+
+    // ═══════════════════════════════════════
+    //  MINTING
+    // ═══════════════════════════════════════
+    pub fn mint_usdc(env: Env, caller: Address, to: Address, amount: i128) {
+        Self::require_role(&env, &caller, Role::Minter);
+        Self::consume_quota(&env, &caller, DataKey::UsdcToken, amount);
+        Self::mint_asset(&env, DataKey::UsdcToken, DataKey::TotalUsdcMinted, symbol_short!("usdc"), &to, amount);
+    }
+
+    pub fn mint_eth(env: Env, caller: Address, to: Address, amount: i128) {
+        Self::require_role(&env, &caller, Role::Minter);
+        Self::consume_quota(&env, &caller, DataKey::EthToken, amount);
+        Self::mint_asset(&env, DataKey::EthToken, DataKey::TotalEthMinted, symbol_short!("eth"), &to, amount);
+    }
+
+    pub fn burn_usdc(env: Env, from: Address, amount: i128) {
+        Self::burn_asset(&env, DataKey::UsdcToken, DataKey::TotalUsdcMinted, symbol_short!("usdc"), &from, amount);
+    }
+
+    pub fn burn_eth(env: Env, from: Address, amount: i128) {
+        Self::burn_asset(&env, DataKey::EthToken, DataKey::TotalEthMinted, symbol_short!("eth"), &from, amount);
+    }
+
+    /// Hard cap on the total minted through the DEX for `token` (admin only).
+    /// `None` removes the cap.
+    pub fn set_supply_cap(env: Env, token: Address, cap: Option<i128>) {
+        Self::require_admin(&env);
+        match cap {
+            Some(c) => env.storage().instance().set(&DataKey::SupplyCap(token), &c),
+            None    => env.storage().instance().remove(&DataKey::SupplyCap(token)),
+        }
+    }
+
+    /// Rolling mint quota for `minter` on `token` (admin only). `None`
+    /// removes the quota. The admin itself is never quota-limited. The
+    /// limit can't be negative and the window must span at least one
+    /// second per bucket.
+    pub fn set_minter_quota(env: Env, minter: Address, token: Address, quota: Option<MinterQuota>) {
+        Self::require_admin(&env);
+        let key = DataKey::MinterQuota(minter.clone(), token.clone());
+        match quota {
+            Some(q) if q.limit < 0 || q.window_secs < QUOTA_BUCKETS => panic_with_error!(&env, DexError::ZeroAmount),
+            Some(q) => env.storage().persistent().set(&key, &q),
+            None    => env.storage().persistent().remove(&key),
+        }
+        env.storage().persistent().remove(&DataKey::MinterUsage(minter, token));
+    }
+
+    pub fn supply_cap(env: Env, token: Address) -> Option<i128> {
+        env.storage().instance().get(&DataKey::SupplyCap(token))
+    }
+
+    pub fn minter_quota(env: Env, minter: Address, token: Address) -> Option<MinterQuota> {
+        env.storage().persistent().get(&DataKey::MinterQuota(minter, token))
+    }
+
+    /// Amount the minter has minted of `token` within its current quota
+    /// window; 0 without a quota.
+    pub fn minter_usage(env: Env, minter: Address, token: Address) -> i128 {
+        let quota: Option<MinterQuota> = env.storage().persistent()
+            .get(&DataKey::MinterQuota(minter.clone(), token.clone()));
+        match quota {
+            Some(q) => Self::live_usage(&env, &q, env.storage().persistent().get(&DataKey::MinterUsage(minter, token))).1,
+            None    => 0,
+        }
+    }
+
+    // ═══════════════════════════════════════
+    //  FAUCET
+    // ═══════════════════════════════════════
+    /// Configure the self-serve test-token faucet (admin only)
+    pub fn set_faucet(env: Env, config: FaucetConfig) {
+        Self::require_admin(&env);
+        if config.cooldown_secs == 0 || config.cooldown_secs > MAX_FAUCET_COOLDOWN_SECS
+            || config.usdc_per_claim < 0 || config.eth_per_claim < 0
+            || config.daily_usdc_budget < 0 || config.daily_eth_budget < 0
+        {
+            panic_with_error!(&env, DexError::BadFaucet);
+        }
+        env.storage().instance().set(&DataKey::FaucetConfig, &config);
+    }
+
+    /// Mint one faucet drip of USDC or ETH to `claimer`, at most once per
+    /// cooldown per address and within the faucet's daily budget.
+    pub fn claim_faucet(env: Env, claimer: Address, token: Address) -> i128 {
+        claimer.require_auth();
+        let cfg: FaucetConfig = env.storage().instance().get(&DataKey::FaucetConfig)
+            .unwrap_or_else(|| panic_with_error!(&env, DexError::FaucetDisabled));
+        if !cfg.enabled { panic_with_error!(&env, DexError::FaucetDisabled); }
+
+        let usdc: Address = env.storage().instance().get(&DataKey::UsdcToken).unwrap();
+        let eth: Address  = env.storage().instance().get(&DataKey::EthToken).unwrap();
+        let (token_key, total_key, sym, amount, budget) = if token == usdc {
+            (DataKey::UsdcToken, DataKey::TotalUsdcMinted, symbol_short!("usdc"), cfg.usdc_per_claim, cfg.daily_usdc_budget)
+        } else if token == eth {
+            (DataKey::EthToken, DataKey::TotalEthMinted, symbol_short!("eth"), cfg.eth_per_claim, cfg.daily_eth_budget)
+        } else {
+            panic_with_error!(&env, DexError::BadToken);
+        };
+
+        let now = env.ledger().timestamp();
+        let claim_key = DataKey::FaucetClaim(claimer.clone(), token.clone());
+        if let Some(last) = env.storage().persistent().get::<_, u64>(&claim_key) {
+            if now < last.saturating_add(cfg.cooldown_secs) {
+                panic_with_error!(&env, DexError::FaucetCooldown);
+            }
+        }
+
+        let spent_key = DataKey::FaucetSpent(token, now / 86_400);
+        let spent: i128 = env.storage().temporary().get(&spent_key).unwrap_or(0);
+        if spent + amount > budget {
+            panic_with_error!(&env, DexError::FaucetExhausted);
+        }
+
+        Self::mint_asset(&env, token_key, total_key, sym.clone(), &claimer, amount);
+
+        // The last claim is persistent: an archived entry must be restored
+        // before it can be read, so it can never lapse into a free claim.
+        // Day budgets are keyed by day and only need to outlive it; keep
+        // them twice as long in case ledgers close faster than 5s.
+        Self::save_persistent(&env, &claim_key, &now);
+        env.storage().temporary().set(&spent_key, &(spent + amount));
+        env.storage().temporary().extend_ttl(&spent_key, 2 * DAY_IN_LEDGERS, 2 * DAY_IN_LEDGERS);
+
+        FaucetEvent { token: sym, claimer, amount }.publish(&env);
+        amount
+    }
+
+    pub fn faucet_config(env: Env) -> Option<FaucetConfig> {
+        env.storage().instance().get(&DataKey::FaucetConfig)
+    }
+
+    // ═══════════════════════════════════════
+    //  LIQUIDITY
+    // ═══════════════════════════════════════
+    pub fn add_liquidity(
+        env: Env,
+        provider: Address,
+        xlm_amount: i128,
+        usdc_amount: i128,
+        eth_amount: i128,
+    ) -> i128 {
+        provider.require_auth();
+        Self::require_not_paused(&env);
+        Self::require_op_open(&env, PauseFlag::Deposits);
+        if xlm_amount <= 0 && usdc_amount <= 0 && eth_amount <= 0 {
+            panic_with_error!(&env, DexError::ZeroAmount);
+        }
+
+        if xlm_amount > 0 {
+            let xlm: Address = env.storage().instance().get(&DataKey::XlmToken).unwrap();
+            Self::require_asset_open(&env, &xlm);
+            token::Client::new(&env, &xlm)
+                .transfer(&provider, env.current_contract_address(), &xlm_amount);
+        }
+        if usdc_amount > 0 {
+            let usdc: Address = env.storage().instance().get(&DataKey::UsdcToken).unwrap();
+            Self::require_asset_open(&env, &usdc);
+            token::Client::new(&env, &usdc)
+                .transfer(&provider, env.current_contract_address(), &usdc_amount);
+        }
+        if eth_amount > 0 {
+            let eth: Address = env.storage().instance().get(&DataKey::EthToken).unwrap();
+            Self::require_asset_open(&env, &eth);
+            token::Client::new(&env, &eth)
+                .transfer(&provider, env.current_contract_address(), &eth_amount);
+        }
+
+        let xlm_res: i128  = env.storage().instance().get(&DataKey::XlmReserve).unwrap_or(0);
+        let usdc_res: i128 = env.storage().instance().get(&DataKey::UsdcReserve).unwrap_or(0);
+        let eth_res: i128  = env.storage().instance().get(&DataKey::EthReserve).unwrap_or(0);
+
+        env.storage().instance().set(&DataKey::XlmReserve,  &(xlm_res  + xlm_amount));
+        env.storage().instance().set(&DataKey::UsdcReserve, &(usdc_res + usdc_amount));
+        env.storage().instance().set(&DataKey::EthReserve,  &(eth_res  + eth_amount));
+
+        let usdc_rate: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap();
         let eth_rate: i128  = env.storage().instance().get(&DataKey::EthRate).unwrap();
         let lp_shares = xlm_amount
             + (usdc_amount * usdc_rate)
@@ -238,6 +1155,8 @@ impl StellarDex {
         env.storage().instance().set(&DataKey::TotalLp, &(total_lp + lp_shares));
         env.storage().persistent()
             .set(&DataKey::LpBalance(provider.clone()), &(prev_bal + lp_shares));
+        Self::extend_instance(&env);
+        Self::extend_lp(&env, &provider);
 
         LiquidityEvent {
             action: symbol_short!("add"),
@@ -252,7 +1171,7 @@ impl StellarDex {
 
     pub fn remove_liquidity(env: Env, provider: Address, lp_amount: i128) -> (i128, i128, i128) {
         provider.require_auth();
-        Self::require_not_paused(&env);
+        Self::require_withdrawals_open(&env);
         if lp_amount <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
 
         let bal: i128 = env.storage().persistent()
@@ -274,6 +1193,8 @@ impl StellarDex {
         env.storage().instance().set(&DataKey::TotalLp,      &(total_lp - lp_amount));
         env.storage().persistent()
             .set(&DataKey::LpBalance(provider.clone()), &(bal - lp_amount));
+        Self::extend_instance(&env);
+        Self::extend_lp(&env, &provider);
 
         if xlm_out > 0 {
             let xlm: Address = env.storage().instance().get(&DataKey::XlmToken).unwrap();
@@ -305,10 +1226,11 @@ impl StellarDex {
     // ═════════════════════════════════════
     //  SWAPS
     // ═══════════════════════════════════════
-    pub fn swap_xlm_for_usdc(env: Env, buyer: Address, xlm_in: i128, min_usdc_out: i128) -> i128 {
+    pub fn swap_xlm_for_usdc(env: Env, buyer: Address, xlm_in: i128, min_usdc_out: i128, referrer: Option<Address>) -> i128 {
         buyer.require_auth();
-        Self::require_not_paused(&env);
+        Self::require_swap_open(&env, DataKey::XlmToken, DataKey::UsdcToken);
         if xlm_in <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        Self::extend_instance(&env);
 
         let rate: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap();
         let usdc_out = (xlm_in * 997 / 1000) / rate;
@@ -320,24 +1242,26 @@ impl StellarDex {
         // Transfer XLM from buyer to contract
         let xlm: Address = env.storage().instance().get(&DataKey::XlmToken).unwrap();
         token::Client::new(&env, &xlm)
-            .transfer(&buyer, &env.current_contract_address(), &xlm_in);
+            .transfer(&buyer, env.current_contract_address(), &xlm_in);
+        let cut = Self::pay_referrer(&env, &buyer, &referrer, &xlm, xlm_in);
 
         let xlm_res: i128 = env.storage().instance().get(&DataKey::XlmReserve).unwrap();
-        env.storage().instance().set(&DataKey::XlmReserve,  &(xlm_res  + xlm_in));
+        env.storage().instance().set(&DataKey::XlmReserve,  &(xlm_res  + xlm_in - cut));
         env.storage().instance().set(&DataKey::UsdcReserve, &(usdc_res - usdc_out));
 
         let usdc: Address = env.storage().instance().get(&DataKey::UsdcToken).unwrap();
         token::Client::new(&env, &usdc)
             .transfer(&env.current_contract_address(), &buyer, &usdc_out);
 
-        SwapEvent { from_token: symbol_short!("xlm"), to_token: symbol_short!("usdc"), amount_in: xlm_in, amount_out: usdc_out }.publish(&env);
+        SwapEvent { from_token: symbol_short!("xlm"), to_token: symbol_short!("usdc"), amount_in: xlm_in, amount_out: usdc_out, referrer }.publish(&env);
         usdc_out
     }
 
-    pub fn swap_usdc_for_xlm(env: Env, seller: Address, usdc_in: i128, min_xlm_out: i128) -> i128 {
+    pub fn swap_usdc_for_xlm(env: Env, seller: Address, usdc_in: i128, min_xlm_out: i128, referrer: Option<Address>) -> i128 {
         seller.require_auth();
-        Self::require_not_paused(&env);
+        Self::require_swap_open(&env, DataKey::UsdcToken, DataKey::XlmToken);
         if usdc_in <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        Self::extend_instance(&env);
 
         let rate: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap();
         let xlm_out = (usdc_in * 997 / 1000) * rate;
@@ -348,7 +1272,8 @@ impl StellarDex {
 
         let usdc: Address = env.storage().instance().get(&DataKey::UsdcToken).unwrap();
         token::Client::new(&env, &usdc)
-            .transfer(&seller, &env.current_contract_address(), &usdc_in);
+            .transfer(&seller, env.current_contract_address(), &usdc_in);
+        let cut = Self::pay_referrer(&env, &seller, &referrer, &usdc, usdc_in);
 
         // Transfer XLM from contract to seller
         let xlm: Address = env.storage().instance().get(&DataKey::XlmToken).unwrap();
@@ -357,16 +1282,17 @@ impl StellarDex {
 
         let usdc_res: i128 = env.storage().instance().get(&DataKey::UsdcReserve).unwrap();
         env.storage().instance().set(&DataKey::XlmReserve,  &(xlm_res  - xlm_out));
-        env.storage().instance().set(&DataKey::UsdcReserve, &(usdc_res + usdc_in));
+        env.storage().instance().set(&DataKey::UsdcReserve, &(usdc_res + usdc_in - cut));
 
-        SwapEvent { from_token: symbol_short!("usdc"), to_token: symbol_short!("xlm"), amount_in: usdc_in, amount_out: xlm_out }.publish(&env);
+        SwapEvent { from_token: symbol_short!("usdc"), to_token: symbol_short!("xlm"), amount_in: usdc_in, amount_out: xlm_out, referrer }.publish(&env);
         xlm_out
     }
 
-    pub fn swap_xlm_for_eth(env: Env, buyer: Address, xlm_in: i128, min_eth_out: i128) -> i128 {
+    pub fn swap_xlm_for_eth(env: Env, buyer: Address, xlm_in: i128, min_eth_out: i128, referrer: Option<Address>) -> i128 {
         buyer.require_auth();
-        Self::require_not_paused(&env);
+        Self::require_swap_open(&env, DataKey::XlmToken, DataKey::EthToken);
         if xlm_in <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        Self::extend_instance(&env);
 
         let rate: i128 = env.storage().instance().get(&DataKey::EthRate).unwrap();
         let eth_out = (xlm_in * 997 / 1000) / rate;
@@ -378,24 +1304,26 @@ impl StellarDex {
         // Transfer XLM from buyer to contract
         let xlm: Address = env.storage().instance().get(&DataKey::XlmToken).unwrap();
         token::Client::new(&env, &xlm)
-            .transfer(&buyer, &env.current_contract_address(), &xlm_in);
+            .transfer(&buyer, env.current_contract_address(), &xlm_in);
+        let cut = Self::pay_referrer(&env, &buyer, &referrer, &xlm, xlm_in);
 
         let xlm_res: i128 = env.storage().instance().get(&DataKey::XlmReserve).unwrap();
-        env.storage().instance().set(&DataKey::XlmReserve, &(xlm_res + xlm_in));
+        env.storage().instance().set(&DataKey::XlmReserve, &(xlm_res + xlm_in - cut));
         env.storage().instance().set(&DataKey::EthReserve, &(eth_res - eth_out));
 
         let eth: Address = env.storage().instance().get(&DataKey::EthToken).unwrap();
         token::Client::new(&env, &eth)
             .transfer(&env.current_contract_address(), &buyer, &eth_out);
 
-        SwapEvent { from_token: symbol_short!("xlm"), to_token: symbol_short!("eth"), amount_in: xlm_in, amount_out: eth_out }.publish(&env);
+        SwapEvent { from_token: symbol_short!("xlm"), to_token: symbol_short!("eth"), amount_in: xlm_in, amount_out: eth_out, referrer }.publish(&env);
         eth_out
     }
 
-    pub fn swap_eth_for_xlm(env: Env, seller: Address, eth_in: i128, min_xlm_out: i128) -> i128 {
+    pub fn swap_eth_for_xlm(env: Env, seller: Address, eth_in: i128, min_xlm_out: i128, referrer: Option<Address>) -> i128 {
         seller.require_auth();
-        Self::require_not_paused(&env);
+        Self::require_swap_open(&env, DataKey::EthToken, DataKey::XlmToken);
         if eth_in <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        Self::extend_instance(&env);
 
         let rate: i128 = env.storage().instance().get(&DataKey::EthRate).unwrap();
         let xlm_out = (eth_in * 997 / 1000) * rate;
@@ -406,7 +1334,8 @@ impl StellarDex {
 
         let eth: Address = env.storage().instance().get(&DataKey::EthToken).unwrap();
         token::Client::new(&env, &eth)
-            .transfer(&seller, &env.current_contract_address(), &eth_in);
+            .transfer(&seller, env.current_contract_address(), &eth_in);
+        let cut = Self::pay_referrer(&env, &seller, &referrer, &eth, eth_in);
 
         // Transfer XLM from contract to seller
         let xlm: Address = env.storage().instance().get(&DataKey::XlmToken).unwrap();
@@ -415,16 +1344,17 @@ impl StellarDex {
 
         let eth_res: i128 = env.storage().instance().get(&DataKey::EthReserve).unwrap();
         env.storage().instance().set(&DataKey::XlmReserve, &(xlm_res - xlm_out));
-        env.storage().instance().set(&DataKey::EthReserve, &(eth_res  + eth_in));
+        env.storage().instance().set(&DataKey::EthReserve, &(eth_res  + eth_in - cut));
 
-        SwapEvent { from_token: symbol_short!("eth"), to_token: symbol_short!("xlm"), amount_in: eth_in, amount_out: xlm_out }.publish(&env);
+        SwapEvent { from_token: symbol_short!("eth"), to_token: symbol_short!("xlm"), amount_in: eth_in, amount_out: xlm_out, referrer }.publish(&env);
         xlm_out
     }
 
-    pub fn swap_usdc_for_eth(env: Env, swapper: Address, usdc_in: i128, min_eth_out: i128) -> i128 {
+    pub fn swap_usdc_for_eth(env: Env, swapper: Address, usdc_in: i128, min_eth_out: i128, referrer: Option<Address>) -> i128 {
         swapper.require_auth();
-        Self::require_not_paused(&env);
+        Self::require_swap_open(&env, DataKey::UsdcToken, DataKey::EthToken);
         if usdc_in <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        Self::extend_instance(&env);
 
         let usdc_rate: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap();
         let eth_rate:  i128 = env.storage().instance().get(&DataKey::EthRate).unwrap();
@@ -437,24 +1367,26 @@ impl StellarDex {
 
         let usdc: Address = env.storage().instance().get(&DataKey::UsdcToken).unwrap();
         token::Client::new(&env, &usdc)
-            .transfer(&swapper, &env.current_contract_address(), &usdc_in);
+            .transfer(&swapper, env.current_contract_address(), &usdc_in);
+        let cut = Self::pay_referrer(&env, &swapper, &referrer, &usdc, usdc_in);
 
         let usdc_res: i128 = env.storage().instance().get(&DataKey::UsdcReserve).unwrap();
-        env.storage().instance().set(&DataKey::UsdcReserve, &(usdc_res + usdc_in));
+        env.storage().instance().set(&DataKey::UsdcReserve, &(usdc_res + usdc_in - cut));
         env.storage().instance().set(&DataKey::EthReserve,  &(eth_res  - eth_out));
 
         let eth: Address = env.storage().instance().get(&DataKey::EthToken).unwrap();
         token::Client::new(&env, &eth)
             .transfer(&env.current_contract_address(), &swapper, &eth_out);
 
-        SwapEvent { from_token: symbol_short!("usdc"), to_token: symbol_short!("eth"), amount_in: usdc_in, amount_out: eth_out }.publish(&env);
+        SwapEvent { from_token: symbol_short!("usdc"), to_token: symbol_short!("eth"), amount_in: usdc_in, amount_out: eth_out, referrer }.publish(&env);
         eth_out
     }
 
-    pub fn swap_eth_for_usdc(env: Env, swapper: Address, eth_in: i128, min_usdc_out: i128) -> i128 {
+    pub fn swap_eth_for_usdc(env: Env, swapper: Address, eth_in: i128, min_usdc_out: i128, referrer: Option<Address>) -> i128 {
         swapper.require_auth();
-        Self::require_not_paused(&env);
+        Self::require_swap_open(&env, DataKey::EthToken, DataKey::UsdcToken);
         if eth_in <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        Self::extend_instance(&env);
 
         let usdc_rate: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap();
         let eth_rate:  i128 = env.storage().instance().get(&DataKey::EthRate).unwrap();
@@ -467,85 +1399,1884 @@ impl StellarDex {
 
         let eth: Address = env.storage().instance().get(&DataKey::EthToken).unwrap();
         token::Client::new(&env, &eth)
-            .transfer(&swapper, &env.current_contract_address(), &eth_in);
+            .transfer(&swapper, env.current_contract_address(), &eth_in);
+        let cut = Self::pay_referrer(&env, &swapper, &referrer, &eth, eth_in);
 
         let eth_res: i128 = env.storage().instance().get(&DataKey::EthReserve).unwrap();
-        env.storage().instance().set(&DataKey::EthReserve,  &(eth_res  + eth_in));
+        env.storage().instance().set(&DataKey::EthReserve,  &(eth_res  + eth_in - cut));
         env.storage().instance().set(&DataKey::UsdcReserve, &(usdc_res - usdc_out));
 
         let usdc: Address = env.storage().instance().get(&DataKey::UsdcToken).unwrap();
         token::Client::new(&env, &usdc)
             .transfer(&env.current_contract_address(), &swapper, &usdc_out);
 
-        SwapEvent { from_token: symbol_short!("eth"), to_token: symbol_short!("usdc"), amount_in: eth_in, amount_out: usdc_out }.publish(&env);
+        SwapEvent { from_token: symbol_short!("eth"), to_token: symbol_short!("usdc"), amount_in: eth_in, amount_out: usdc_out, referrer }.publish(&env);
         usdc_out
     }
 
+    /// Exact-input swap between any two pool tokens, priced like the
+    /// dedicated `swap_*` entrypoints. Used by routers that only know
+    /// token addresses.
+    pub fn swap(
+        env: Env,
+        trader: Address,
+        token_in: Address,
+        token_out: Address,
+        amount_in: i128,
+        min_out: i128,
+        referrer: Option<Address>,
+    ) -> i128 {
+        trader.require_auth();
+        Self::swap_exact_in(&env, &trader, &token_in, &token_out, amount_in, min_out, &referrer)
+    }
+
+    /// Run several exact-input swaps under one auth. Every leg has its own
+    /// `min_out`; if any leg fails the whole batch reverts. Returns each
+    /// leg's output in order.
+    pub fn swap_batch(env: Env, trader: Address, legs: Vec<SwapLeg>, referrer: Option<Address>) -> Vec<i128> {
+        trader.require_auth();
+        if legs.is_empty() { panic_with_error!(&env, DexError::EmptyBatch); }
+
+        let mut outs = Vec::new(&env);
+        for leg in legs.iter() {
+            outs.push_back(Self::swap_exact_in(
+                &env, &trader, &leg.token_in, &leg.token_out, leg.amount_in, leg.min_out, &referrer,
+            ));
+        }
+        outs
+    }
+
+    /// Pay-later swap: send `amount_out` of `token_out` to `recipient`,
+    /// call its `on_flash_swap`, then require that enough `token_in` came
+    /// back to buy `amount_out` at the current rate and fee. Returns the
+    /// input actually received. `initiator` authorises the swap, as for
+    /// `flash_loan`.
+    pub fn flash_swap(
+        env: Env,
+        initiator: Address,
+        recipient: Address,
+        token_in: Address,
+        token_out: Address,
+        amount_out: i128,
+        data: Bytes,
+    ) -> i128 {
+        initiator.require_auth();
+        if amount_out <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        let from = Self::pool_asset(&env, &token_in);
+        let to   = Self::pool_asset(&env, &token_out);
+        Self::require_swap_open(&env, from.token_key.clone(), to.token_key.clone());
+        Self::extend_instance(&env);
+
+        let out_res: i128 = env.storage().instance().get(&to.reserve_key).unwrap();
+        if out_res < amount_out { panic_with_error!(&env, DexError::InsufficientFunds); }
+        let amount_in = Self::quote_in(&env, &from, &to, amount_out);
+
+        let in_client = token::Client::new(&env, &token_in);
+        let before = in_client.balance(&env.current_contract_address());
+        token::Client::new(&env, &token_out)
+            .transfer(&env.current_contract_address(), &recipient, &amount_out);
+
+        FlashSwapReceiverClient::new(&env, &recipient).on_flash_swap(
+            &env.current_contract_address(), &initiator, &token_in, &amount_in, &token_out, &amount_out, &data,
+        );
+
+        let received = in_client.balance(&env.current_contract_address()) - before;
+        if Self::quote_out(&env, &from, &to, received) < amount_out {
+            panic_with_error!(&env, DexError::FlashNotRepaid);
+        }
+
+        let in_res: i128 = env.storage().instance().get(&from.reserve_key).unwrap();
+        env.storage().instance().set(&from.reserve_key, &(in_res + received));
+        env.storage().instance().set(&to.reserve_key,   &(out_res - amount_out));
+
+        SwapEvent { from_token: from.sym, to_token: to.sym, amount_in: received, amount_out, referrer: None }.publish(&env);
+        received
+    }
+
     // ═══════════════════════════════════════
-    //  VIEW / QUOTE
+    //  LIMIT ORDERS
     // ═══════════════════════════════════════
-    pub fn get_rates(env: Env) -> (i128, i128) {
-        let u: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap_or(0);
-        let e: i128 = env.storage().instance().get(&DataKey::EthRate).unwrap_or(0);
-        (u, e)
+
+    /// Escrow `amount` of `sell` until the pool pays at least `limit_price`
+    /// of `buy` per `PRICE_SCALE` units of `sell`, or until `expiry`.
+    pub fn place_limit_order(
+        env: Env,
+        owner: Address,
+        sell: Address,
+        buy: Address,
+        amount: i128,
+        limit_price: i128,
+        expiry: u64,
+    ) -> u64 {
+        owner.require_auth();
+        let from = Self::pool_asset(&env, &sell);
+        let to   = Self::pool_asset(&env, &buy);
+        if from.sym == to.sym { panic_with_error!(&env, DexError::BadToken); }
+        Self::require_swap_open(&env, from.token_key, to.token_key);
+        if amount <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        if limit_price <= 0 { panic_with_error!(&env, DexError::BadPrice); }
+        if expiry <= env.ledger().timestamp() { panic_with_error!(&env, DexError::OrderExpired); }
+        Self::extend_instance(&env);
+
+        token::Client::new(&env, &sell)
+            .transfer(&owner, env.current_contract_address(), &amount);
+        Self::add_escrow(&env, &sell, amount);
+
+        let order_id: u64 = env.storage().instance().get(&DataKey::NextOrderId).unwrap_or(0);
+        env.storage().instance().set(&DataKey::NextOrderId, &(order_id + 1));
+        let order = LimitOrder { owner: owner.clone(), sell, buy, amount, limit_price, expiry };
+        Self::store_order(&env, order_id, &order);
+
+        OrderEvent { action: symbol_short!("placed"), order_id, amount_in: amount, amount_out: 0 }.publish(&env);
+        order_id
     }
 
-    pub fn get_reserves(env: Env) -> (i128, i128, i128) {
-        let x: i128 = env.storage().instance().get(&DataKey::XlmReserve).unwrap_or(0);
-        let u: i128 = env.storage().instance().get(&DataKey::UsdcReserve).unwrap_or(0);
-        let e: i128 = env.storage().instance().get(&DataKey::EthReserve).unwrap_or(0);
-        (x, u, e)
+    /// Fill every listed order whose limit the pool currently meets, at the
+    /// pool rate. Orders that are missing, expired, not yet crossed, priced
+    /// too high to ever cross, too large for the reserves or on a paused
+    /// asset or pair are skipped.
+    /// Anyone may call this; returns the ids that were filled.
+    pub fn fill_orders(env: Env, order_ids: Vec<u64>) -> Vec<u64> {
+        Self::require_not_paused(&env);
+        Self::require_op_open(&env, PauseFlag::Swaps);
+        Self::extend_instance(&env);
+        let now = env.ledger().timestamp();
+        let mut filled = Vec::new(&env);
+
+        for order_id in order_ids.iter() {
+            let order: LimitOrder = match env.storage().persistent().get(&DataKey::Order(order_id)) {
+                Some(o) => o,
+                None => continue,
+            };
+            if now > order.expiry { continue; }
+
+            let from = Self::pool_asset(&env, &order.sell);
+            let to   = Self::pool_asset(&env, &order.buy);
+            if !Self::pair_open(&env, &from.token_key, &to.token_key) { continue; }
+
+            let Some(wanted) = order.amount.checked_mul(order.limit_price) else { continue };
+            let amount_out = Self::quote_out(&env, &from, &to, order.amount);
+            if amount_out * PRICE_SCALE < wanted { continue; }
+            let out_res: i128 = env.storage().instance().get(&to.reserve_key).unwrap();
+            if out_res < amount_out { continue; }
+
+            Self::remove_order(&env, order_id, &order.owner);
+            Self::settle_escrowed(&env, from, to, order.amount, amount_out, &order.owner);
+            OrderEvent { action: symbol_short!("filled"), order_id, amount_in: order.amount, amount_out }.publish(&env);
+            filled.push_back(order_id);
+        }
+        filled
     }
 
-    pub fn quote_xlm_to_usdc(env: Env, xlm_in: i128) -> i128 {
-        let rate: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap();
-        (xlm_in * 997 / 1000) / rate
+    /// Return an open order's escrow to its owner. Allowed while paused
+    /// and after expiry.
+    pub fn cancel_order(env: Env, order_id: u64) -> i128 {
+        let order = Self::load_order(&env, order_id);
+        order.owner.require_auth();
+
+        Self::add_escrow(&env, &order.sell, -order.amount);
+        Self::remove_order(&env, order_id, &order.owner);
+        token::Client::new(&env, &order.sell)
+            .transfer(&env.current_contract_address(), &order.owner, &order.amount);
+
+        OrderEvent { action: symbol_short!("cancel"), order_id, amount_in: order.amount, amount_out: 0 }.publish(&env);
+        order.amount
     }
 
-    pub fn quote_xlm_to_eth(env: Env, xlm_in: i128) -> i128 {
-        let rate: i128 = env.storage().instance().get(&DataKey::EthRate).unwrap();
-        (xlm_in * 997 / 1000) / rate
+    pub fn get_order(env: Env, order_id: u64) -> LimitOrder {
+        Self::load_order(&env, order_id)
     }
 
-    pub fn quote_usdc_to_xlm(env: Env, usdc_in: i128) -> i128 {
-        let rate: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap();
-        (usdc_in * 997 / 1000) * rate
+    /// Ids of `owner`'s open orders, oldest first.
+    pub fn orders_of(env: Env, owner: Address) -> Vec<u64> {
+        env.storage().persistent()
+            .get(&DataKey::OwnerOrders(owner))
+            .unwrap_or(Vec::new(&env))
     }
 
-    pub fn quote_eth_to_xlm(env: Env, eth_in: i128) -> i128 {
-        let rate: i128 = env.storage().instance().get(&DataKey::EthRate).unwrap();
-        (eth_in * 997 / 1000) * rate
+    /// Amount of `token` held in escrow for open orders (not pool reserves).
+    pub fn escrowed(env: Env, token: Address) -> i128 {
+        env.storage().instance().get(&DataKey::Escrowed(token)).unwrap_or(0)
     }
 
-    pub fn lp_balance(env: Env, user: Address) -> i128 {
+    // ═══════════════════════════════════════
+    //  DCA
+    // ═══════════════════════════════════════
+
+    /// Escrow `deposit` of `sell` and buy `buy` with `per_period` of it every
+    /// `interval_secs`, starting now. Each period fails rather than receive
+    /// less than `min_out` (pro rata for a short final period).
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_dca(
+        env: Env,
+        owner: Address,
+        sell: Address,
+        buy: Address,
+        deposit: i128,
+        per_period: i128,
+        interval_secs: u64,
+        min_out: i128,
+    ) -> u64 {
+        owner.require_auth();
+        let from = Self::pool_asset(&env, &sell);
+        let to   = Self::pool_asset(&env, &buy);
+        if from.sym == to.sym { panic_with_error!(&env, DexError::BadToken); }
+        Self::require_swap_open(&env, from.token_key, to.token_key);
+        if deposit <= 0 || per_period <= 0 || interval_secs == 0 {
+            panic_with_error!(&env, DexError::ZeroAmount);
+        }
+        Self::extend_instance(&env);
+
+        token::Client::new(&env, &sell)
+            .transfer(&owner, env.current_contract_address(), &deposit);
+        Self::add_escrow(&env, &sell, deposit);
+
+        let plan_id: u64 = env.storage().instance().get(&DataKey::NextDcaId).unwrap_or(0);
+        env.storage().instance().set(&DataKey::NextDcaId, &(plan_id + 1));
+        let plan = DcaPlan {
+            owner: owner.clone(), sell, buy, per_period, min_out, interval_secs,
+            next_at: env.ledger().timestamp(),
+            remaining: deposit,
+        };
+        Self::save_persistent(&env, &DataKey::Dca(plan_id), &plan);
+        Self::index_add(&env, DataKey::OwnerDcas(owner), plan_id);
+
+        DcaEvent { action: symbol_short!("created"), plan_id, amount_in: deposit, amount_out: 0 }.publish(&env);
+        plan_id
+    }
+
+    /// Execute one due period of a plan at the pool rate. The keeper keeps
+    /// `dca_tip_bps` of the period's amount; the rest is swapped for the
+    /// owner. Missed periods can be caught up with further calls. Returns
+    /// the amount bought.
+    pub fn execute_dca(env: Env, keeper: Address, plan_id: u64) -> i128 {
+        keeper.require_auth();
+        let mut plan = Self::load_dca(&env, plan_id);
+        if env.ledger().timestamp() < plan.next_at { panic_with_error!(&env, DexError::NotDue); }
+        let from = Self::pool_asset(&env, &plan.sell);
+        let to   = Self::pool_asset(&env, &plan.buy);
+        Self::require_swap_open(&env, from.token_key.clone(), to.token_key.clone());
+        Self::extend_instance(&env);
+
+        let amount    = plan.per_period.min(plan.remaining);
+        let tip       = amount * Self::dca_tip_bps(env.clone()) as i128 / 10_000;
+        let amount_in = amount - tip;
+        let min_out   = plan.min_out * amount / plan.per_period;
+
+        let amount_out = Self::quote_out(&env, &from, &to, amount_in);
+        if amount_out < min_out { panic_with_error!(&env, DexError::SlippageExceeded); }
+        let out_res: i128 = env.storage().instance().get(&to.reserve_key).unwrap();
+        if out_res < amount_out { panic_with_error!(&env, DexError::InsufficientFunds); }
+
+        Self::settle_escrowed(&env, from, to, amount_in, amount_out, &plan.owner);
+        if tip > 0 {
+            Self::add_escrow(&env, &plan.sell, -tip);
+            token::Client::new(&env, &plan.sell)
+                .transfer(&env.current_contract_address(), &keeper, &tip);
+        }
+
+        plan.remaining -= amount;
+        plan.next_at   += plan.interval_secs;
+        if plan.remaining == 0 {
+            env.storage().persistent().remove(&DataKey::Dca(plan_id));
+            Self::index_remove(&env, DataKey::OwnerDcas(plan.owner), plan_id);
+        } else {
+            Self::save_persistent(&env, &DataKey::Dca(plan_id), &plan);
+        }
+
+        DcaEvent { action: symbol_short!("executed"), plan_id, amount_in: amount, amount_out }.publish(&env);
+        amount_out
+    }
+
+    /// Close a plan and return its unspent deposit to the owner. Allowed
+    /// at any time, including while paused.
+    pub fn withdraw_dca(env: Env, plan_id: u64) -> i128 {
+        let plan = Self::load_dca(&env, plan_id);
+        plan.owner.require_auth();
+
+        Self::add_escrow(&env, &plan.sell, -plan.remaining);
+        env.storage().persistent().remove(&DataKey::Dca(plan_id));
+        Self::index_remove(&env, DataKey::OwnerDcas(plan.owner.clone()), plan_id);
+        token::Client::new(&env, &plan.sell)
+            .transfer(&env.current_contract_address(), &plan.owner, &plan.remaining);
+
+        DcaEvent { action: symbol_short!("withdrawn"), plan_id, amount_in: plan.remaining, amount_out: 0 }.publish(&env);
+        plan.remaining
+    }
+
+    /// Set the keeper tip on DCA periods (FeeManager role)
+    pub fn set_dca_tip(env: Env, caller: Address, tip_bps: u32) {
+        Self::require_role(&env, &caller, Role::FeeManager);
+        if tip_bps > MAX_DCA_TIP_BPS { panic_with_error!(&env, DexError::BadFee); }
+        env.storage().instance().set(&DataKey::DcaTipBps, &tip_bps);
+    }
+
+    pub fn dca_tip_bps(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::DcaTipBps).unwrap_or(DEFAULT_DCA_TIP_BPS)
+    }
+
+    pub fn get_dca(env: Env, plan_id: u64) -> DcaPlan {
+        Self::load_dca(&env, plan_id)
+    }
+
+    /// Ids of `owner`'s open DCA plans, oldest first.
+    pub fn dca_plans_of(env: Env, owner: Address) -> Vec<u64> {
         env.storage().persistent()
-            .get(&DataKey::LpBalance(user)).unwrap_or(0)
+            .get(&DataKey::OwnerDcas(owner))
+            .unwrap_or(Vec::new(&env))
     }
 
-    pub fn total_lp(env: Env) -> i128 {
-        env.storage().instance().get(&DataKey::TotalLp).unwrap_or(0)
+    // ═══════════════════════════════════════
+    //  TRIGGER ORDERS
+    // ═══════════════════════════════════════
+
+    /// Escrow `amount` of `sell` to swap for `buy` once the pool price
+    /// crosses `trigger_price` in the `kind` direction. `limit_price` is
+    /// the worst price the swap may execute at and must not exceed
+    /// `trigger_price`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn place_trigger_order(
+        env: Env,
+        owner: Address,
+        sell: Address,
+        buy: Address,
+        amount: i128,
+        kind: TriggerKind,
+        trigger_price: i128,
+        limit_price: i128,
+    ) -> u64 {
+        owner.require_auth();
+        let from = Self::pool_asset(&env, &sell);
+        let to   = Self::pool_asset(&env, &buy);
+        if from.sym == to.sym { panic_with_error!(&env, DexError::BadToken); }
+        Self::require_swap_open(&env, from.token_key, to.token_key);
+        if amount <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        if limit_price <= 0 || limit_price > trigger_price {
+            panic_with_error!(&env, DexError::BadPrice);
+        }
+        Self::extend_instance(&env);
+
+        token::Client::new(&env, &sell)
+            .transfer(&owner, env.current_contract_address(), &amount);
+        Self::add_escrow(&env, &sell, amount);
+
+        let order_id: u64 = env.storage().instance().get(&DataKey::NextTriggerId).unwrap_or(0);
+        env.storage().instance().set(&DataKey::NextTriggerId, &(order_id + 1));
+        let order = TriggerOrder { owner: owner.clone(), sell, buy, amount, kind, trigger_price, limit_price };
+        Self::save_persistent(&env, &DataKey::Trigger(order_id), &order);
+        Self::index_add(&env, DataKey::OwnerTriggers(owner), order_id);
+
+        TriggerEvent { action: symbol_short!("placed"), order_id, amount_in: amount, amount_out: 0 }.publish(&env);
+        order_id
     }
 
-    pub fn mint_stats(env: Env) -> (i128, i128) {
-        let u: i128 = env.storage().instance().get(&DataKey::TotalUsdcMinted).unwrap_or(0);
-        let e: i128 = env.storage().instance().get(&DataKey::TotalEthMinted).unwrap_or(0);
-        (u, e)
+    /// Execute every listed trigger order whose price has crossed and
+    /// whose swap still meets its `limit_price`. Others, including orders
+    /// on a paused asset or pair and orders priced too high to compare,
+    /// are skipped. Anyone may call this;
+    /// returns the ids that executed.
+    pub fn fill_triggers(env: Env, order_ids: Vec<u64>) -> Vec<u64> {
+        Self::require_not_paused(&env);
+        Self::require_op_open(&env, PauseFlag::Swaps);
+        Self::extend_instance(&env);
+        let mut filled = Vec::new(&env);
+
+        for order_id in order_ids.iter() {
+            let order: TriggerOrder = match env.storage().persistent().get(&DataKey::Trigger(order_id)) {
+                Some(o) => o,
+                None => continue,
+            };
+            let from = Self::pool_asset(&env, &order.sell);
+            let to   = Self::pool_asset(&env, &order.buy);
+            if !Self::pair_open(&env, &from.token_key, &to.token_key) { continue; }
+
+            let (Some(trigger), Some(limit)) = (
+                order.amount.checked_mul(order.trigger_price),
+                order.amount.checked_mul(order.limit_price),
+            ) else { continue };
+            let amount_out = Self::quote_out(&env, &from, &to, order.amount);
+            let got = amount_out * PRICE_SCALE;
+            let crossed = match order.kind {
+                TriggerKind::StopLoss   => got <= trigger,
+                TriggerKind::TakeProfit => got >= trigger,
+            };
+            if !crossed || got < limit { continue; }
+            let out_res: i128 = env.storage().instance().get(&to.reserve_key).unwrap();
+            if out_res < amount_out { continue; }
+
+            env.storage().persistent().remove(&DataKey::Trigger(order_id));
+            Self::index_remove(&env, DataKey::OwnerTriggers(order.owner.clone()), order_id);
+            Self::settle_escrowed(&env, from, to, order.amount, amount_out, &order.owner);
+            TriggerEvent { action: symbol_short!("filled"), order_id, amount_in: order.amount, amount_out }.publish(&env);
+            filled.push_back(order_id);
+        }
+        filled
     }
 
-    pub fn is_paused(env: Env) -> bool {
-        env.storage().instance().get(&DataKey::Paused).unwrap_or(false)
+    /// Return a trigger order's escrow to its owner. Allowed while paused.
+    pub fn cancel_trigger(env: Env, order_id: u64) -> i128 {
+        let order = Self::load_trigger(&env, order_id);
+        order.owner.require_auth();
+
+        Self::add_escrow(&env, &order.sell, -order.amount);
+        env.storage().persistent().remove(&DataKey::Trigger(order_id));
+        Self::index_remove(&env, DataKey::OwnerTriggers(order.owner.clone()), order_id);
+        token::Client::new(&env, &order.sell)
+            .transfer(&env.current_contract_address(), &order.owner, &order.amount);
+
+        TriggerEvent { action: symbol_short!("cancel"), order_id, amount_in: order.amount, amount_out: 0 }.publish(&env);
+        order.amount
+    }
+
+    pub fn get_trigger(env: Env, order_id: u64) -> TriggerOrder {
+        Self::load_trigger(&env, order_id)
+    }
+
+    /// Ids of `owner`'s open trigger orders, oldest first.
+    pub fn triggers_of(env: Env, owner: Address) -> Vec<u64> {
+        env.storage().persistent()
+            .get(&DataKey::OwnerTriggers(owner))
+            .unwrap_or(Vec::new(&env))
     }
 
     // ═══════════════════════════════════════
-    //  INTERNAL HELPERS
+    //  TWAP ORDERS
     // ═══════════════════════════════════════
-    fn require_admin(env: &Env) {
-        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
-        admin.require_auth();
+
+    /// Escrow `total` of `sell` and swap it for `buy` in `chunks` slices,
+    /// at least `interval_ledgers` (one or more) apart, starting now. Each
+    /// slice fails rather than receive less than `min_out_per_chunk` (pro
+    /// rata for the larger final slice), which can't be negative.
+    #[allow(clippy::too_many_arguments)]
+    pub fn place_twap(
+        env: Env,
+        owner: Address,
+        sell: Address,
+        buy: Address,
+        total: i128,
+        chunks: u32,
+        interval_ledgers: u32,
+        min_out_per_chunk: i128,
+    ) -> u64 {
+        owner.require_auth();
+        let from = Self::pool_asset(&env, &sell);
+        let to   = Self::pool_asset(&env, &buy);
+        if from.sym == to.sym { panic_with_error!(&env, DexError::BadToken); }
+        Self::require_swap_open(&env, from.token_key, to.token_key);
+        if chunks == 0 || total < chunks as i128 || interval_ledgers == 0 {
+            panic_with_error!(&env, DexError::ZeroAmount);
+        }
+        if min_out_per_chunk < 0 { panic_with_error!(&env, DexError::BadPrice); }
+        Self::extend_instance(&env);
+
+        token::Client::new(&env, &sell)
+            .transfer(&owner, env.current_contract_address(), &total);
+        Self::add_escrow(&env, &sell, total);
+
+        let order_id: u64 = env.storage().instance().get(&DataKey::NextTwapId).unwrap_or(0);
+        env.storage().instance().set(&DataKey::NextTwapId, &(order_id + 1));
+        let order = TwapOrder {
+            owner: owner.clone(), sell, buy,
+            chunk_size: total / chunks as i128,
+            min_out_per_chunk,
+            chunks_left: chunks,
+            interval_ledgers,
+            next_ledger: env.ledger().sequence(),
+            remaining: total,
+        };
+        Self::save_persistent(&env, &DataKey::Twap(order_id), &order);
+        Self::index_add(&env, DataKey::OwnerTwaps(owner), order_id);
+
+        TwapEvent { action: symbol_short!("placed"), order_id, amount_in: total, amount_out: 0 }.publish(&env);
+        order_id
     }
 
-    fn require_not_paused(env: &Env) {
-        let paused: bool = env.storage().instance().get(&DataKey::Paused).unwrap_or(false);
-        if paused { panic_with_error!(env, DexError::ContractPaused); }
+    /// Execute the next slice of a TWAP order at the pool rate. Anyone
+    /// may call this once the slice is due; returns the amount bought.
+    pub fn execute_twap(env: Env, order_id: u64) -> i128 {
+        let mut order = Self::load_twap(&env, order_id);
+        if env.ledger().sequence() < order.next_ledger { panic_with_error!(&env, DexError::NotDue); }
+        let from = Self::pool_asset(&env, &order.sell);
+        let to   = Self::pool_asset(&env, &order.buy);
+        Self::require_swap_open(&env, from.token_key.clone(), to.token_key.clone());
+        Self::extend_instance(&env);
+
+        let amount  = if order.chunks_left == 1 { order.remaining } else { order.chunk_size };
+        let min_out = order.min_out_per_chunk * amount / order.chunk_size;
+
+        let amount_out = Self::quote_out(&env, &from, &to, amount);
+        if amount_out < min_out { panic_with_error!(&env, DexError::SlippageExceeded); }
+        let out_res: i128 = env.storage().instance().get(&to.reserve_key).unwrap();
+        if out_res < amount_out { panic_with_error!(&env, DexError::InsufficientFunds); }
+        Self::settle_escrowed(&env, from, to, amount, amount_out, &order.owner);
+
+        order.remaining   -= amount;
+        order.chunks_left -= 1;
+        order.next_ledger  = env.ledger().sequence() + order.interval_ledgers;
+        if order.chunks_left == 0 {
+            env.storage().persistent().remove(&DataKey::Twap(order_id));
+            Self::index_remove(&env, DataKey::OwnerTwaps(order.owner), order_id);
+        } else {
+            Self::save_persistent(&env, &DataKey::Twap(order_id), &order);
+        }
+
+        TwapEvent { action: symbol_short!("executed"), order_id, amount_in: amount, amount_out }.publish(&env);
+        amount_out
+    }
+
+    /// Stop a TWAP order and return the unsold remainder to its owner.
+    /// Allowed at any time, including while paused.
+    pub fn cancel_twap(env: Env, order_id: u64) -> i128 {
+        let order = Self::load_twap(&env, order_id);
+        order.owner.require_auth();
+
+        Self::add_escrow(&env, &order.sell, -order.remaining);
+        env.storage().persistent().remove(&DataKey::Twap(order_id));
+        Self::index_remove(&env, DataKey::OwnerTwaps(order.owner.clone()), order_id);
+        token::Client::new(&env, &order.sell)
+            .transfer(&env.current_contract_address(), &order.owner, &order.remaining);
+
+        TwapEvent { action: symbol_short!("cancel"), order_id, amount_in: order.remaining, amount_out: 0 }.publish(&env);
+        order.remaining
+    }
+
+    pub fn get_twap(env: Env, order_id: u64) -> TwapOrder {
+        Self::load_twap(&env, order_id)
+    }
+
+    /// Ids of `owner`'s open TWAP orders, oldest first.
+    pub fn twaps_of(env: Env, owner: Address) -> Vec<u64> {
+        env.storage().persistent()
+            .get(&DataKey::OwnerTwaps(owner))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    // ═══════════════════════════════════════
+    //  COMMIT-REVEAL SWAPS
+    // ═══════════════════════════════════════
+
+    /// Escrow up to `escrow` of `token_in` behind `commitment`, the
+    /// `commitment_hash` of the swap to be revealed later. The reveal
+    /// window follows the commit config in force now.
+    pub fn commit_swap(env: Env, trader: Address, commitment: BytesN<32>, token_in: Address, escrow: i128) -> u64 {
+        trader.require_auth();
+        Self::pool_asset(&env, &token_in);
+        Self::require_not_paused(&env);
+        if escrow <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        Self::extend_instance(&env);
+
+        token::Client::new(&env, &token_in)
+            .transfer(&trader, env.current_contract_address(), &escrow);
+        Self::add_escrow(&env, &token_in, escrow);
+
+        let commit_id: u64 = env.storage().instance().get(&DataKey::NextCommitId).unwrap_or(0);
+        env.storage().instance().set(&DataKey::NextCommitId, &(commit_id + 1));
+        let cfg = Self::commit_config(env.clone());
+        let committed_at = env.ledger().sequence();
+        let opens_at = committed_at.saturating_add(cfg.reveal_delay);
+        let commit = SwapCommit {
+            trader, commitment, token_in, escrow, committed_at,
+            opens_at,
+            expires_at: opens_at.saturating_add(cfg.reveal_window),
+        };
+        Self::save_persistent(&env, &DataKey::Commit(commit_id), &commit);
+
+        CommitEvent { action: symbol_short!("commit"), commit_id, amount_in: escrow, amount_out: 0 }.publish(&env);
+        commit_id
+    }
+
+    /// Reveal a committed swap and execute it at the rates in effect now.
+    /// Unused escrow is returned. Returns the amount bought.
+    pub fn reveal_swap(
+        env: Env,
+        commit_id: u64,
+        token_out: Address,
+        amount_in: i128,
+        min_out: i128,
+        salt: BytesN<32>,
+    ) -> i128 {
+        let commit = Self::load_commit(&env, commit_id);
+        commit.trader.require_auth();
+        let now = env.ledger().sequence();
+        if now < commit.opens_at { panic_with_error!(&env, DexError::NotDue); }
+        if now > commit.expires_at { panic_with_error!(&env, DexError::CommitExpired); }
+
+        let expected = Self::commitment_hash(
+            env.clone(), commit.trader.clone(), commit.token_in.clone(), token_out.clone(), amount_in, min_out, salt,
+        );
+        if expected != commit.commitment || amount_in <= 0 || amount_in > commit.escrow {
+            panic_with_error!(&env, DexError::BadReveal);
+        }
+
+        let from = Self::pool_asset(&env, &commit.token_in);
+        let to   = Self::pool_asset(&env, &token_out);
+        Self::require_swap_open(&env, from.token_key.clone(), to.token_key.clone());
+        Self::extend_instance(&env);
+
+        let amount_out = Self::quote_out(&env, &from, &to, amount_in);
+        if amount_out < min_out { panic_with_error!(&env, DexError::SlippageExceeded); }
+        let out_res: i128 = env.storage().instance().get(&to.reserve_key).unwrap();
+        if out_res < amount_out { panic_with_error!(&env, DexError::InsufficientFunds); }
+
+        env.storage().persistent().remove(&DataKey::Commit(commit_id));
+        Self::settle_escrowed(&env, from, to, amount_in, amount_out, &commit.trader);
+        let unused = commit.escrow - amount_in;
+        if unused > 0 {
+            Self::add_escrow(&env, &commit.token_in, -unused);
+            token::Client::new(&env, &commit.token_in)
+                .transfer(&env.current_contract_address(), &commit.trader, &unused);
+        }
+
+        CommitEvent { action: symbol_short!("reveal"), commit_id, amount_in, amount_out }.publish(&env);
+        amount_out
+    }
+
+    /// Refund the escrow of a commitment whose reveal window has passed.
+    pub fn reclaim_commit(env: Env, commit_id: u64) -> i128 {
+        let commit = Self::load_commit(&env, commit_id);
+        if env.ledger().sequence() <= commit.expires_at { panic_with_error!(&env, DexError::NotDue); }
+
+        Self::add_escrow(&env, &commit.token_in, -commit.escrow);
+        env.storage().persistent().remove(&DataKey::Commit(commit_id));
+        token::Client::new(&env, &commit.token_in)
+            .transfer(&env.current_contract_address(), &commit.trader, &commit.escrow);
+
+        CommitEvent { action: symbol_short!("reclaim"), commit_id, amount_in: commit.escrow, amount_out: 0 }.publish(&env);
+        commit.escrow
     }
-}
\ No newline at end of file
+
+    /// Hash to commit to: sha256 over the XDR of each field in order.
+    /// Compute it off-chain or by simulating this call; never submit it.
+    pub fn commitment_hash(
+        env: Env,
+        trader: Address,
+        token_in: Address,
+        token_out: Address,
+        amount_in: i128,
+        min_out: i128,
+        salt: BytesN<32>,
+    ) -> BytesN<32> {
+        let mut preimage = Bytes::new(&env);
+        preimage.append(&trader.to_xdr(&env));
+        preimage.append(&token_in.to_xdr(&env));
+        preimage.append(&token_out.to_xdr(&env));
+        preimage.append(&amount_in.to_xdr(&env));
+        preimage.append(&min_out.to_xdr(&env));
+        preimage.append(&salt.to_xdr(&env));
+        env.crypto().sha256(&preimage).into()
+    }
+
+    /// Reveal delay and window for new commitments, each at most
+    /// `MAX_REVEAL_LEDGERS`. Pending commitments keep their own.
+    pub fn set_commit_config(env: Env, reveal_delay: u32, reveal_window: u32) {
+        Self::require_admin(&env);
+        if reveal_window == 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        if reveal_delay > MAX_REVEAL_LEDGERS || reveal_window > MAX_REVEAL_LEDGERS {
+            panic_with_error!(&env, DexError::BadCommitConfig);
+        }
+        env.storage().instance().set(&DataKey::CommitConfig, &CommitConfig { reveal_delay, reveal_window });
+    }
+
+    pub fn commit_config(env: Env) -> CommitConfig {
+        env.storage().instance().get(&DataKey::CommitConfig).unwrap_or(CommitConfig {
+            reveal_delay:  DEFAULT_REVEAL_DELAY,
+            reveal_window: DEFAULT_REVEAL_WINDOW,
+        })
+    }
+
+    pub fn get_commit(env: Env, commit_id: u64) -> SwapCommit {
+        Self::load_commit(&env, commit_id)
+    }
+
+    // ═══════════════════════════════════════
+    //  BATCH AUCTIONS
+    // ═══════════════════════════════════════
+
+    /// Escrow a swap into the batch collecting at this ledger. Returns the
+    /// intent id; the batch it joined is in `get_intent`. Intents worth
+    /// less than `min_intent` XLM are rejected.
+    pub fn submit_intent(
+        env: Env,
+        trader: Address,
+        token_in: Address,
+        token_out: Address,
+        amount_in: i128,
+        min_out: i128,
+    ) -> u64 {
+        trader.require_auth();
+        let from = Self::pool_asset(&env, &token_in);
+        let to   = Self::pool_asset(&env, &token_out);
+        if from.sym == to.sym { panic_with_error!(&env, DexError::BadToken); }
+        Self::require_swap_open(&env, from.token_key.clone(), to.token_key);
+        if amount_in <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        let xlm_value = match Self::rate_of(&env, &from) {
+            Some(rate) => amount_in * rate,
+            None       => amount_in,
+        };
+        if xlm_value < Self::min_intent(env.clone()) { panic_with_error!(&env, DexError::IntentTooSmall); }
+        Self::extend_instance(&env);
+
+        let len = Self::auction_ledgers(env.clone());
+        let batch = (env.ledger().sequence() / len + 1) * len;
+        let ids: Vec<u64> = env.storage().persistent()
+            .get(&DataKey::BatchIntents(batch)).unwrap_or(Vec::new(&env));
+        if ids.len() >= MAX_BATCH_INTENTS { panic_with_error!(&env, DexError::BatchFull); }
+
+        token::Client::new(&env, &token_in)
+            .transfer(&trader, env.current_contract_address(), &amount_in);
+        Self::add_escrow(&env, &token_in, amount_in);
+
+        let intent_id: u64 = env.storage().instance().get(&DataKey::NextIntentId).unwrap_or(0);
+        env.storage().instance().set(&DataKey::NextIntentId, &(intent_id + 1));
+        let intent = SwapIntent { trader, token_in, token_out, amount_in, min_out, batch };
+        Self::save_persistent(&env, &DataKey::Intent(intent_id), &intent);
+        Self::index_add(&env, DataKey::BatchIntents(batch), intent_id);
+
+        AuctionEvent { action: symbol_short!("submit"), intent_id, amount_in, amount_out: 0 }.publish(&env);
+        intent_id
+    }
+
+    /// Clear a closed batch. Every intent trades at the rates in effect
+    /// now, whatever its position in the batch. Opposite flows offset each
+    /// other, so only the net amount of each token moves the reserves.
+    /// Intents whose `min_out` is not met, or whose asset or pair is
+    /// paused, are refunded. If the rest would still overdraw a reserve,
+    /// the intent taking the most of that token is refunded until they
+    /// fit, so a batch can always clear. Anyone may call this; returns the
+    /// ids that were filled.
+    pub fn clear_batch(env: Env, batch: u32) -> Vec<u64> {
+        if env.ledger().sequence() < batch { panic_with_error!(&env, DexError::NotDue); }
+        Self::require_not_paused(&env);
+        Self::require_op_open(&env, PauseFlag::Swaps);
+        let ids: Vec<u64> = env.storage().persistent()
+            .get(&DataKey::BatchIntents(batch))
+            .unwrap_or_else(|| panic_with_error!(&env, DexError::EmptyBatch));
+        Self::extend_instance(&env);
+
+        // Net reserve change per token, in XLM / USDC / ETH order
+        let reserve_keys = [DataKey::XlmReserve, DataKey::UsdcReserve, DataKey::EthReserve];
+        let slot = |key: &DataKey| match key { DataKey::XlmReserve => 0, DataKey::UsdcReserve => 1, _ => 2 };
+        let mut reserves = [0_i128; 3];
+        for (i, key) in reserve_keys.iter().enumerate() {
+            reserves[i] = env.storage().instance().get(key).unwrap();
+        }
+        let mut net = [0_i128; 3];
+        // (id, intent, amount_out, input slot, output slot) of each intent still set to fill
+        let mut fills: Vec<(u64, SwapIntent, i128, u32, u32)> = Vec::new(&env);
+
+        for intent_id in ids.iter() {
+            let intent: SwapIntent = env.storage().persistent().get(&DataKey::Intent(intent_id)).unwrap();
+            env.storage().persistent().remove(&DataKey::Intent(intent_id));
+            Self::add_escrow(&env, &intent.token_in, -intent.amount_in);
+
+            let from = Self::pool_asset(&env, &intent.token_in);
+            let to   = Self::pool_asset(&env, &intent.token_out);
+            let open = Self::pair_open(&env, &from.token_key, &to.token_key);
+
+            let amount_out = Self::quote_out(&env, &from, &to, intent.amount_in);
+            if !open || amount_out < intent.min_out {
+                Self::refund_intent(&env, intent_id, &intent);
+                continue;
+            }
+
+            let (in_slot, out_slot) = (slot(&from.reserve_key), slot(&to.reserve_key));
+            net[in_slot]  += intent.amount_in;
+            net[out_slot] -= amount_out;
+            fills.push_back((intent_id, intent, amount_out, in_slot as u32, out_slot as u32));
+        }
+
+        // Refund the biggest draw on an overdrawn reserve until every
+        // reserve covers the batch's net flow
+        while let Some(short) = (0..3).find(|&i| reserves[i] + net[i] < 0) {
+            let mut biggest: Option<(u32, i128)> = None;
+            for (i, (_, _, amount_out, _, out_slot)) in fills.iter().enumerate() {
+                if out_slot as usize == short && biggest.is_none_or(|(_, most)| amount_out > most) {
+                    biggest = Some((i as u32, amount_out));
+                }
+            }
+            let at = biggest.unwrap().0;
+            let (intent_id, intent, amount_out, in_slot, out_slot) = fills.get_unchecked(at);
+            fills.remove(at);
+            net[in_slot as usize]  -= intent.amount_in;
+            net[out_slot as usize] += amount_out;
+            Self::refund_intent(&env, intent_id, &intent);
+        }
+
+        let mut filled = Vec::new(&env);
+        for (intent_id, intent, amount_out, _, _) in fills.iter() {
+            token::Client::new(&env, &intent.token_out)
+                .transfer(&env.current_contract_address(), &intent.trader, &amount_out);
+            AuctionEvent { action: symbol_short!("fill"), intent_id, amount_in: intent.amount_in, amount_out }.publish(&env);
+            filled.push_back(intent_id);
+        }
+
+        for (i, key) in reserve_keys.iter().enumerate() {
+            env.storage().instance().set(key, &(reserves[i] + net[i]));
+        }
+        env.storage().persistent().remove(&DataKey::BatchIntents(batch));
+        filled
+    }
+
+    /// Take an intent out of its batch and return the escrow. Allowed
+    /// until the batch is cleared, and while paused.
+    pub fn cancel_intent(env: Env, intent_id: u64) -> i128 {
+        let intent = Self::load_intent(&env, intent_id);
+        intent.trader.require_auth();
+
+        Self::add_escrow(&env, &intent.token_in, -intent.amount_in);
+        env.storage().persistent().remove(&DataKey::Intent(intent_id));
+        Self::index_remove(&env, DataKey::BatchIntents(intent.batch), intent_id);
+        token::Client::new(&env, &intent.token_in)
+            .transfer(&env.current_contract_address(), &intent.trader, &intent.amount_in);
+
+        AuctionEvent { action: symbol_short!("cancel"), intent_id, amount_in: intent.amount_in, amount_out: 0 }.publish(&env);
+        intent.amount_in
+    }
+
+    /// Length of a batch in ledgers. Applies to batches opened from now on.
+    pub fn set_auction_ledgers(env: Env, ledgers: u32) {
+        Self::require_admin(&env);
+        if ledgers == 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        env.storage().instance().set(&DataKey::AuctionLedgers, &ledgers);
+    }
+
+    pub fn auction_ledgers(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::AuctionLedgers).unwrap_or(DEFAULT_AUCTION_LEDGERS)
+    }
+
+    /// Smallest intent `submit_intent` accepts, valued in XLM stroops.
+    pub fn set_min_intent(env: Env, min_xlm: i128) {
+        Self::require_admin(&env);
+        if min_xlm <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        env.storage().instance().set(&DataKey::MinIntent, &min_xlm);
+    }
+
+    pub fn min_intent(env: Env) -> i128 {
+        env.storage().instance().get(&DataKey::MinIntent).unwrap_or(DEFAULT_MIN_INTENT)
+    }
+
+    pub fn get_intent(env: Env, intent_id: u64) -> SwapIntent {
+        Self::load_intent(&env, intent_id)
+    }
+
+    pub fn batch_intents(env: Env, batch: u32) -> Vec<u64> {
+        env.storage().persistent().get(&DataKey::BatchIntents(batch)).unwrap_or(Vec::new(&env))
+    }
+
+    // ═══════════════════════════════════════
+    //  LP STAKING
+    // ═══════════════════════════════════════
+
+    /// Token paid out as staking rewards. Can only be set once.
+    pub fn set_reward_token(env: Env, token: Address) {
+        Self::require_admin(&env);
+        if env.storage().instance().has(&DataKey::RewardToken) {
+            panic_with_error!(&env, DexError::AlreadyInit);
+        }
+        env.storage().instance().set(&DataKey::RewardToken, &token);
+    }
+
+    /// Move `amount` of the reward token from the admin into the emission
+    /// budget. Funded rewards are escrowed, so `sync` never counts them as
+    /// reserves.
+    pub fn fund_rewards(env: Env, amount: i128) {
+        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        admin.require_auth();
+        if amount <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        let token = Self::reward_token(&env);
+
+        let mut pool = Self::update_rewards(&env);
+        token::Client::new(&env, &token)
+            .transfer(&admin, env.current_contract_address(), &amount);
+        Self::add_escrow(&env, &token, amount);
+        pool.budget += amount;
+        env.storage().instance().set(&DataKey::RewardPool, &pool);
+    }
+
+    /// Reward emitted per second, shared pro rata by all staked shares.
+    pub fn set_reward_rate(env: Env, rate: i128) {
+        Self::require_admin(&env);
+        if rate < 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        let mut pool = Self::update_rewards(&env);
+        pool.rate = rate;
+        env.storage().instance().set(&DataKey::RewardPool, &pool);
+    }
+
+    /// Stake LP shares. Staked shares earn rewards but cannot be removed
+    /// as liquidity until unstaked.
+    pub fn stake(env: Env, provider: Address, shares: i128) {
+        provider.require_auth();
+        Self::require_not_paused(&env);
+        if shares <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        let bal: i128 = env.storage().persistent()
+            .get(&DataKey::LpBalance(provider.clone())).unwrap_or(0);
+        if bal < shares { panic_with_error!(&env, DexError::InsufficientLp); }
+        Self::extend_instance(&env);
+
+        let mut pool = Self::update_rewards(&env);
+        let mut stake = Self::settle_stake(&env, &pool, &provider);
+        stake.shares += shares;
+        pool.total_weight += shares;
+        Self::store_stake(&env, &pool, &provider, stake);
+
+        env.storage().persistent().set(&DataKey::LpBalance(provider.clone()), &(bal - shares));
+        Self::extend_lp(&env, &provider);
+        StakeEvent { action: symbol_short!("stake"), provider, amount: shares }.publish(&env);
+    }
+
+    /// Return staked shares to the provider's LP balance. Rewards earned
+    /// so far stay claimable. Allowed while paused.
+    pub fn unstake(env: Env, provider: Address, shares: i128) {
+        provider.require_auth();
+        if shares <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        Self::extend_instance(&env);
+
+        let mut pool = Self::update_rewards(&env);
+        let mut stake = Self::settle_stake(&env, &pool, &provider);
+        if stake.shares < shares { panic_with_error!(&env, DexError::InsufficientStake); }
+        stake.shares -= shares;
+        pool.total_weight -= shares;
+        Self::store_stake(&env, &pool, &provider, stake);
+
+        let bal: i128 = env.storage().persistent()
+            .get(&DataKey::LpBalance(provider.clone())).unwrap_or(0);
+        env.storage().persistent().set(&DataKey::LpBalance(provider.clone()), &(bal + shares));
+        Self::extend_lp(&env, &provider);
+        StakeEvent { action: symbol_short!("unstake"), provider, amount: shares }.publish(&env);
+    }
+
+    /// Pay out everything the provider has earned. Allowed while paused.
+    pub fn claim_rewards(env: Env, provider: Address) -> i128 {
+        provider.require_auth();
+        Self::extend_instance(&env);
+
+        let pool = Self::update_rewards(&env);
+        let mut stake = Self::settle_stake(&env, &pool, &provider);
+        let amount = stake.unclaimed;
+        stake.unclaimed = 0;
+        Self::store_stake(&env, &pool, &provider, stake);
+
+        if amount > 0 {
+            let token = Self::reward_token(&env);
+            Self::add_escrow(&env, &token, -amount);
+            token::Client::new(&env, &token)
+                .transfer(&env.current_contract_address(), &provider, &amount);
+        }
+        StakeEvent { action: symbol_short!("claim"), provider, amount }.publish(&env);
+        amount
+    }
+
+    pub fn pending_rewards(env: Env, provider: Address) -> i128 {
+        let pool = Self::accrue(&env, Self::load_reward_pool(&env));
+        let stake = Self::stake_of(env.clone(), provider.clone());
+        let weight = Self::reward_weight(&env, &provider, &stake);
+        stake.unclaimed + weight * pool.acc_per_share / REWARD_SCALE - stake.reward_debt
+    }
+
+    pub fn stake_of(env: Env, provider: Address) -> Stake {
+        env.storage().persistent().get(&DataKey::Stake(provider))
+            .unwrap_or(Stake { shares: 0, reward_debt: 0, unclaimed: 0 })
+    }
+
+    pub fn reward_pool(env: Env) -> RewardPool {
+        Self::accrue(&env, Self::load_reward_pool(&env))
+    }
+
+    // ═══════════════════════════════════════
+    //  VOTE-ESCROWED LOCKS
+    // ═══════════════════════════════════════
+
+    /// Lock `shares` of the provider's LP balance until `unlock_at`, at
+    /// most `MAX_LOCK_SECS` away. Adding to an existing lock or extending
+    /// it (pass 0 shares) re-boosts the whole lock for the new length; a
+    /// lock can never be shortened.
+    pub fn lock(env: Env, provider: Address, shares: i128, unlock_at: u64) {
+        provider.require_auth();
+        Self::require_not_paused(&env);
+        let now = env.ledger().timestamp();
+        if unlock_at <= now || unlock_at > now + MAX_LOCK_SECS {
+            panic_with_error!(&env, DexError::BadLockTime);
+        }
+        let key = DataKey::Lock(provider.clone());
+        let prev: VeLock = env.storage().persistent().get(&key)
+            .unwrap_or(VeLock { shares: 0, weight: 0, unlock_at: 0 });
+        if unlock_at < prev.unlock_at { panic_with_error!(&env, DexError::BadLockTime); }
+        if shares < 0 || prev.shares + shares == 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+        let bal: i128 = env.storage().persistent()
+            .get(&DataKey::LpBalance(provider.clone())).unwrap_or(0);
+        if bal < shares { panic_with_error!(&env, DexError::InsufficientLp); }
+        Self::extend_instance(&env);
+
+        let mut pool = Self::update_rewards(&env);
+        let stake = Self::settle_stake(&env, &pool, &provider);
+        let locked = prev.shares + shares;
+        let boost = locked * MAX_LOCK_BOOST_BPS * (unlock_at - now) as i128 / (MAX_LOCK_SECS as i128 * 10_000);
+        let lock = VeLock { shares: locked, weight: locked + boost, unlock_at };
+        pool.total_weight += lock.weight - prev.weight;
+        Self::save_persistent(&env, &key, &lock);
+        Self::store_stake(&env, &pool, &provider, stake);
+
+        env.storage().persistent().set(&DataKey::LpBalance(provider.clone()), &(bal - shares));
+        Self::extend_lp(&env, &provider);
+        StakeEvent { action: symbol_short!("lock"), provider, amount: shares }.publish(&env);
+    }
+
+    /// Return an expired lock's shares to the provider's LP balance.
+    /// Anyone may call this once the lock has expired, so expired locks
+    /// stop earning the boost. Returns the shares released.
+    pub fn unlock(env: Env, provider: Address) -> i128 {
+        let key = DataKey::Lock(provider.clone());
+        let lock: VeLock = env.storage().persistent().get(&key)
+            .unwrap_or_else(|| panic_with_error!(&env, DexError::NoLock));
+        if env.ledger().timestamp() < lock.unlock_at { panic_with_error!(&env, DexError::LockActive); }
+        Self::extend_instance(&env);
+
+        let mut pool = Self::update_rewards(&env);
+        let stake = Self::settle_stake(&env, &pool, &provider);
+        pool.total_weight -= lock.weight;
+        env.storage().persistent().remove(&key);
+        Self::store_stake(&env, &pool, &provider, stake);
+
+        let bal: i128 = env.storage().persistent()
+            .get(&DataKey::LpBalance(provider.clone())).unwrap_or(0);
+        env.storage().persistent().set(&DataKey::LpBalance(provider.clone()), &(bal + lock.shares));
+        Self::extend_lp(&env, &provider);
+        StakeEvent { action: symbol_short!("unlock"), provider, amount: lock.shares }.publish(&env);
+        lock.shares
+    }
+
+    /// Locked shares scaled by the time left until unlock over
+    /// `MAX_LOCK_SECS`, so it falls linearly to zero at `unlock_at`.
+    pub fn voting_power(env: Env, provider: Address) -> i128 {
+        let now = env.ledger().timestamp();
+        match Self::lock_of(env, provider) {
+            Some(lock) if lock.unlock_at > now => {
+                lock.shares * (lock.unlock_at - now) as i128 / MAX_LOCK_SECS as i128
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn lock_of(env: Env, provider: Address) -> Option<VeLock> {
+        env.storage().persistent().get(&DataKey::Lock(provider))
+    }
+
+    // ═══════════════════════════════════════
+    //  REFERRALS
+    // ═══════════════════════════════════════
+
+    /// Share of the input fee (in bps of the fee) paid to referrers that
+    /// have no rate of their own. Zero until set, so by default only
+    /// registered referrers earn anything.
+    pub fn set_referral_bps(env: Env, bps: u32) {
+        Self::require_admin(&env);
+        if bps > MAX_REFERRAL_BPS { panic_with_error!(&env, DexError::BadFee); }
+        env.storage().instance().set(&DataKey::ReferralBps, &bps);
+    }
+
+    /// Give one referrer its own share of the fee, or `None` to fall back
+    /// to the default.
+    pub fn set_referrer_bps(env: Env, referrer: Address, bps: Option<u32>) {
+        Self::require_admin(&env);
+        let key = DataKey::ReferrerBps(referrer);
+        match bps {
+            Some(b) if b > MAX_REFERRAL_BPS => panic_with_error!(&env, DexError::BadFee),
+            Some(b) => Self::save_persistent(&env, &key, &b),
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Pay out the referrer's accrued fees in `token`. Allowed while paused.
+    pub fn claim_referral(env: Env, referrer: Address, token: Address) -> i128 {
+        referrer.require_auth();
+        let key = DataKey::Referral(referrer.clone(), token.clone());
+        let amount: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        if amount == 0 { return 0; }
+        Self::extend_instance(&env);
+
+        env.storage().persistent().remove(&key);
+        Self::add_escrow(&env, &token, -amount);
+        token::Client::new(&env, &token)
+            .transfer(&env.current_contract_address(), &referrer, &amount);
+        ReferralEvent { referrer, token, amount }.publish(&env);
+        amount
+    }
+
+    pub fn referral_bps(env: Env, referrer: Address) -> u32 {
+        env.storage().persistent().get(&DataKey::ReferrerBps(referrer))
+            .unwrap_or_else(|| env.storage().instance().get(&DataKey::ReferralBps).unwrap_or(DEFAULT_REFERRAL_BPS))
+    }
+
+    pub fn referral_balance(env: Env, referrer: Address, token: Address) -> i128 {
+        env.storage().persistent().get(&DataKey::Referral(referrer, token)).unwrap_or(0)
+    }
+
+    // ═══════════════════════════════════════
+    //  STORAGE TTL
+    // ═══════════════════════════════════════
+    /// Extend the instance and `user`'s LP entry. Anyone may pay for this.
+    pub fn bump(env: Env, user: Address) {
+        Self::extend_instance(&env);
+        if env.storage().persistent().has(&DataKey::LpBalance(user.clone())) {
+            Self::extend_lp(&env, &user);
+        }
+        let cfg = Self::load_ttl_config(&env);
+        for key in [DataKey::Stake(user.clone()), DataKey::Lock(user)] {
+            if env.storage().persistent().has(&key) {
+                env.storage().persistent().extend_ttl(&key, cfg.threshold, cfg.extend_to);
+            }
+        }
+    }
+
+    pub fn ttl_config(env: Env) -> TtlConfig {
+        Self::load_ttl_config(&env)
+    }
+
+    // ═══════════════════════════════════════
+    //  FLASH LOANS
+    // ═══════════════════════════════════════
+    /// Lend `amount` of a pool token to `receiver` for the duration of its
+    /// `on_flash_loan` callback. The fee stays in the pool for LPs.
+    ///
+    /// `initiator` authorises the loan and is passed on to the receiver.
+    /// A receiver contract cannot start the loan itself, since Soroban does
+    /// not allow the callback to re-enter it.
+    pub fn flash_loan(
+        env: Env,
+        initiator: Address,
+        receiver: Address,
+        token: Address,
+        amount: i128,
+        data: Bytes,
+    ) -> i128 {
+        initiator.require_auth();
+        Self::require_not_paused(&env);
+        Self::require_asset_open(&env, &token);
+        if amount <= 0 { panic_with_error!(&env, DexError::ZeroAmount); }
+
+        let PoolAsset { reserve_key, sym, .. } = Self::pool_asset(&env, &token);
+        let reserve: i128 = env.storage().instance().get(&reserve_key).unwrap_or(0);
+        if amount > reserve { panic_with_error!(&env, DexError::InsufficientFunds); }
+
+        let bps: i128 = Self::flash_fee_bps(env.clone()) as i128;
+        let fee = (amount * bps + 9_999) / 10_000;
+
+        let client = token::Client::new(&env, &token);
+        let before = client.balance(&env.current_contract_address());
+        client.transfer(&env.current_contract_address(), &receiver, &amount);
+
+        FlashReceiverClient::new(&env, &receiver)
+            .on_flash_loan(&env.current_contract_address(), &initiator, &token, &amount, &fee, &data);
+
+        if client.balance(&env.current_contract_address()) < before + fee {
+            panic_with_error!(&env, DexError::FlashNotRepaid);
+        }
+        env.storage().instance().set(&reserve_key, &(reserve + fee));
+        Self::extend_instance(&env);
+
+        FlashLoanEvent { token: sym, receiver, amount, fee }.publish(&env);
+        fee
+    }
+
+    pub fn set_flash_fee(env: Env, caller: Address, fee_bps: u32) {
+        Self::require_role(&env, &caller, Role::FeeManager);
+        if fee_bps > 10_000 { panic_with_error!(&env, DexError::BadFee); }
+        env.storage().instance().set(&DataKey::FlashFeeBps, &fee_bps);
+    }
+
+    pub fn flash_fee_bps(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::FlashFeeBps).unwrap_or(DEFAULT_FLASH_FEE_BPS)
+    }
+
+    // ═══════════════════════════════════════
+    //  RESERVE RECONCILIATION
+    // ═══════════════════════════════════════
+    /// Set recorded reserves to the real token balances, so direct
+    /// transfers into the pool accrue to LPs. Anyone may call this.
+    pub fn sync(env: Env) -> (i128, i128, i128) {
+        let x = Self::pool_balance(&env, DataKey::XlmToken);
+        let u = Self::pool_balance(&env, DataKey::UsdcToken);
+        let e = Self::pool_balance(&env, DataKey::EthToken);
+
+        env.storage().instance().set(&DataKey::XlmReserve,  &x);
+        env.storage().instance().set(&DataKey::UsdcReserve, &u);
+        env.storage().instance().set(&DataKey::EthReserve,  &e);
+        Self::extend_instance(&env);
+
+        ReservesEvent { action: symbol_short!("sync"), xlm: x, usdc: u, eth: e }.publish(&env);
+        (x, u, e)
+    }
+
+    /// Send any balance above the recorded reserves to `to` (FeeManager role)
+    pub fn skim(env: Env, caller: Address, to: Address) -> (i128, i128, i128) {
+        Self::require_role(&env, &caller, Role::FeeManager);
+        let x = Self::skim_token(&env, DataKey::XlmToken,  DataKey::XlmReserve,  &to);
+        let u = Self::skim_token(&env, DataKey::UsdcToken, DataKey::UsdcReserve, &to);
+        let e = Self::skim_token(&env, DataKey::EthToken,  DataKey::EthReserve,  &to);
+
+        ReservesEvent { action: symbol_short!("skim"), xlm: x, usdc: u, eth: e }.publish(&env);
+        (x, u, e)
+    }
+
+    pub fn solvency(env: Env) -> Solvency {
+        let (xlm_reserve, usdc_reserve, eth_reserve) = Self::get_reserves(env.clone());
+        Solvency {
+            xlm_reserve,
+            xlm_balance:  Self::pool_balance(&env, DataKey::XlmToken),
+            usdc_reserve,
+            usdc_balance: Self::pool_balance(&env, DataKey::UsdcToken),
+            eth_reserve,
+            eth_balance:  Self::pool_balance(&env, DataKey::EthToken),
+        }
+    }
+
+    // ═══════════════════════════════════════
+    //  VIEW / QUOTE
+    // ═══════════════════════════════════════
+    /// Current admin, or `None` before `initialize`.
+    pub fn get_admin(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::Admin)
+    }
+
+    pub fn get_rates(env: Env) -> (i128, i128) {
+        let u: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap_or(0);
+        let e: i128 = env.storage().instance().get(&DataKey::EthRate).unwrap_or(0);
+        (u, e)
+    }
+
+    pub fn get_reserves(env: Env) -> (i128, i128, i128) {
+        let x: i128 = env.storage().instance().get(&DataKey::XlmReserve).unwrap_or(0);
+        let u: i128 = env.storage().instance().get(&DataKey::UsdcReserve).unwrap_or(0);
+        let e: i128 = env.storage().instance().get(&DataKey::EthReserve).unwrap_or(0);
+        (x, u, e)
+    }
+
+    pub fn quote_xlm_to_usdc(env: Env, xlm_in: i128) -> i128 {
+        let rate: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap();
+        (xlm_in * 997 / 1000) / rate
+    }
+
+    pub fn quote_xlm_to_eth(env: Env, xlm_in: i128) -> i128 {
+        let rate: i128 = env.storage().instance().get(&DataKey::EthRate).unwrap();
+        (xlm_in * 997 / 1000) / rate
+    }
+
+    pub fn quote_usdc_to_xlm(env: Env, usdc_in: i128) -> i128 {
+        let rate: i128 = env.storage().instance().get(&DataKey::UsdcRate).unwrap();
+        (usdc_in * 997 / 1000) * rate
+    }
+
+    pub fn quote_eth_to_xlm(env: Env, eth_in: i128) -> i128 {
+        let rate: i128 = env.storage().instance().get(&DataKey::EthRate).unwrap();
+        (eth_in * 997 / 1000) * rate
+    }
+
+    /// Output of `swap` for `amount_in`, ignoring reserves and pauses.
+    pub fn quote(env: Env, token_in: Address, token_out: Address, amount_in: i128) -> i128 {
+        let from = Self::pool_asset(&env, &token_in);
+        let to   = Self::pool_asset(&env, &token_out);
+        Self::quote_out(&env, &from, &to, amount_in)
+    }
+
+    pub fn lp_balance(env: Env, user: Address) -> i128 {
+        env.storage().persistent()
+            .get(&DataKey::LpBalance(user)).unwrap_or(0)
+    }
+
+    pub fn total_lp(env: Env) -> i128 {
+        env.storage().instance().get(&DataKey::TotalLp).unwrap_or(0)
+    }
+
+    pub fn mint_stats(env: Env) -> (i128, i128) {
+        let u: i128 = env.storage().instance().get(&DataKey::TotalUsdcMinted).unwrap_or(0);
+        let e: i128 = env.storage().instance().get(&DataKey::TotalEthMinted).unwrap_or(0);
+        (u, e)
+    }
+
+    pub fn is_paused(env: Env) -> bool {
+        env.storage().instance().get(&DataKey::Paused).unwrap_or(false)
+    }
+
+    pub fn is_emergency(env: Env) -> bool {
+        env.storage().instance().get(&DataKey::Emergency).unwrap_or(false)
+    }
+
+    /// Timestamp at which withdrawals reopen despite the current pause
+    /// switches, or 0 when nothing is blocking them.
+    pub fn withdrawals_forced_open_at(env: Env) -> u64 {
+        let paused: bool = env.storage().instance().get(&DataKey::Paused).unwrap_or(false);
+        let flagged: bool = env.storage().instance()
+            .get(&DataKey::OpPaused(PauseFlag::Withdrawals)).unwrap_or(false);
+        if !(paused || flagged) { return 0; }
+        let at: u64 = env.storage().instance().get(&DataKey::WithdrawBlockedAt).unwrap_or(0);
+        at + MAX_PAUSE_SECS
+    }
+
+    pub fn is_op_paused(env: Env, flag: PauseFlag) -> bool {
+        env.storage().instance().get(&DataKey::OpPaused(flag)).unwrap_or(false)
+    }
+
+    pub fn is_asset_paused(env: Env, token: Address) -> bool {
+        env.storage().instance().get(&DataKey::AssetPaused(token)).unwrap_or(false)
+    }
+
+    pub fn is_pair_paused(env: Env, token_a: Address, token_b: Address) -> bool {
+        env.storage().instance().get(&DataKey::PairPaused(token_a, token_b)).unwrap_or(false)
+    }
+
+    // ═══════════════════════════════════════
+    //  INTERNAL HELPERS
+    // ═══════════════════════════════════════
+    fn require_admin(env: &Env) {
+        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        admin.require_auth();
+    }
+
+    /// Authorise `caller` and check it is the admin or holds `role`.
+    fn require_role(env: &Env, caller: &Address, role: Role) {
+        caller.require_auth();
+        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if *caller == admin { return; }
+        if !env.storage().persistent().has(&DataKey::Role(role, caller.clone())) {
+            panic_with_error!(env, DexError::MissingRole);
+        }
+    }
+
+    fn mint_asset(env: &Env, token_key: DataKey, total_key: DataKey, sym: Symbol, to: &Address, amount: i128) {
+        Self::require_not_paused(env);
+        Self::require_op_open(env, PauseFlag::Minting);
+        if amount <= 0 { panic_with_error!(env, DexError::ZeroAmount); }
+        Self::extend_instance(env);
+
+        let tok: Address = env.storage().instance().get(&token_key).unwrap();
+        Self::require_asset_open(env, &tok);
+
+        let prev: i128 = env.storage().instance().get(&total_key).unwrap_or(0);
+        let cap: Option<i128> = env.storage().instance().get(&DataKey::SupplyCap(tok.clone()));
+        if let Some(cap) = cap {
+            if prev + amount > cap { panic_with_error!(env, DexError::MintCapExceeded); }
+        }
+
+        token::StellarAssetClient::new(env, &tok).mint(to, &amount);
+        env.storage().instance().set(&total_key, &(prev + amount));
+
+        MintEvent { token: sym, to: to.clone(), amount }.publish(env);
+    }
+
+    fn burn_asset(env: &Env, token_key: DataKey, total_key: DataKey, sym: Symbol, from: &Address, amount: i128) {
+        from.require_auth();
+        if amount <= 0 { panic_with_error!(env, DexError::ZeroAmount); }
+        Self::extend_instance(env);
+
+        let tok: Address = env.storage().instance().get(&token_key).unwrap();
+        token::Client::new(env, &tok).burn(from, &amount);
+
+        // Tokens minted outside the DEX can be burned here too; the
+        // minted total never drops below zero.
+        let prev: i128 = env.storage().instance().get(&total_key).unwrap_or(0);
+        env.storage().instance().set(&total_key, &(prev - amount).max(0));
+
+        BurnEvent { token: sym, from: from.clone(), amount }.publish(env);
+    }
+
+    /// Charge `amount` against `minter`'s quota for the token under `token_key`.
+    fn consume_quota(env: &Env, minter: &Address, token_key: DataKey, amount: i128) {
+        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if *minter == admin { return; }
+
+        let tok: Address = env.storage().instance().get(&token_key).unwrap();
+        let quota_key = DataKey::MinterQuota(minter.clone(), tok.clone());
+        let quota: MinterQuota = match env.storage().persistent().get(&quota_key) {
+            Some(q) => q,
+            None    => return,
+        };
+
+        let usage_key = DataKey::MinterUsage(minter.clone(), tok);
+        let (mut usage, used) = Self::live_usage(env, &quota, env.storage().persistent().get(&usage_key));
+        if used + amount > quota.limit {
+            panic_with_error!(env, DexError::MintCapExceeded);
+        }
+
+        let width = (quota.window_secs / QUOTA_BUCKETS).max(1);
+        let current = env.ledger().timestamp() / width;
+        match usage.buckets.last() {
+            Some((idx, minted)) if idx == current => usage.buckets.set(usage.buckets.len() - 1, (idx, minted + amount)),
+            _ => usage.buckets.push_back((current, amount)),
+        }
+        Self::save_persistent(env, &usage_key, &usage);
+    }
+
+    /// `usage` without buckets that have left the window, and the total
+    /// still counted against the quota.
+    fn live_usage(env: &Env, quota: &MinterQuota, usage: Option<MinterUsage>) -> (MinterUsage, i128) {
+        let width = (quota.window_secs / QUOTA_BUCKETS).max(1);
+        let span = quota.window_secs.div_ceil(width);
+        let oldest = (env.ledger().timestamp() / width).saturating_sub(span);
+
+        let mut live = MinterUsage { buckets: Vec::new(env) };
+        let mut used = 0;
+        for (idx, minted) in usage.map(|u| u.buckets).unwrap_or(Vec::new(env)).iter() {
+            if idx >= oldest {
+                live.buckets.push_back((idx, minted));
+                used += minted;
+            }
+        }
+        (live, used)
+    }
+
+    /// Storage keys and event symbol for one of the three pool tokens.
+    /// Shared body of `swap` and `swap_batch`; the caller handles auth.
+    fn swap_exact_in(
+        env: &Env,
+        trader: &Address,
+        token_in: &Address,
+        token_out: &Address,
+        amount_in: i128,
+        min_out: i128,
+        referrer: &Option<Address>,
+    ) -> i128 {
+        let from = Self::pool_asset(env, token_in);
+        let to   = Self::pool_asset(env, token_out);
+        Self::require_swap_open(env, from.token_key.clone(), to.token_key.clone());
+        if amount_in <= 0 { panic_with_error!(env, DexError::ZeroAmount); }
+        Self::extend_instance(env);
+
+        let amount_out = Self::quote_out(env, &from, &to, amount_in);
+        if amount_out < min_out { panic_with_error!(env, DexError::SlippageExceeded); }
+        let out_res: i128 = env.storage().instance().get(&to.reserve_key).unwrap();
+        if out_res < amount_out { panic_with_error!(env, DexError::InsufficientFunds); }
+
+        token::Client::new(env, token_in)
+            .transfer(trader, env.current_contract_address(), &amount_in);
+        let cut = Self::pay_referrer(env, trader, referrer, token_in, amount_in);
+
+        let in_res: i128 = env.storage().instance().get(&from.reserve_key).unwrap();
+        env.storage().instance().set(&from.reserve_key, &(in_res + amount_in - cut));
+        env.storage().instance().set(&to.reserve_key,   &(out_res - amount_out));
+
+        token::Client::new(env, token_out)
+            .transfer(&env.current_contract_address(), trader, &amount_out);
+
+        SwapEvent { from_token: from.sym, to_token: to.sym, amount_in, amount_out, referrer: referrer.clone() }.publish(env);
+        amount_out
+    }
+
+    /// Set aside the referrer's share of the 0.3% fee on `amount_in` of
+    /// `token` as their claimable balance. Returns that share, which the
+    /// caller keeps out of the reserves. Traders can't refer themselves.
+    fn pay_referrer(env: &Env, trader: &Address, referrer: &Option<Address>, token: &Address, amount_in: i128) -> i128 {
+        let Some(referrer) = referrer else { return 0 };
+        if referrer == trader { return 0; }
+        let bps = Self::referral_bps(env.clone(), referrer.clone());
+        let cut = amount_in * 3 / 1000 * bps as i128 / 10_000;
+        if cut > 0 {
+            let key = DataKey::Referral(referrer.clone(), token.clone());
+            let owed: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+            Self::save_persistent(env, &key, &(owed + cut));
+            Self::add_escrow(env, token, cut);
+        }
+        cut
+    }
+
+    /// Return a cleared batch intent's input to its trader. The caller has
+    /// already released its escrow.
+    fn refund_intent(env: &Env, intent_id: u64, intent: &SwapIntent) {
+        token::Client::new(env, &intent.token_in)
+            .transfer(&env.current_contract_address(), &intent.trader, &intent.amount_in);
+        AuctionEvent { action: symbol_short!("refund"), intent_id, amount_in: intent.amount_in, amount_out: 0 }.publish(env);
+    }
+
+    fn add_escrow(env: &Env, token: &Address, delta: i128) {
+        let key = DataKey::Escrowed(token.clone());
+        let held: i128 = env.storage().instance().get(&key).unwrap_or(0);
+        env.storage().instance().set(&key, &(held + delta));
+    }
+
+    fn load_order(env: &Env, order_id: u64) -> LimitOrder {
+        env.storage().persistent()
+            .get(&DataKey::Order(order_id))
+            .unwrap_or_else(|| panic_with_error!(env, DexError::NoOrder))
+    }
+
+    fn store_order(env: &Env, order_id: u64, order: &LimitOrder) {
+        Self::save_persistent(env, &DataKey::Order(order_id), order);
+        Self::index_add(env, DataKey::OwnerOrders(order.owner.clone()), order_id);
+    }
+
+    fn remove_order(env: &Env, order_id: u64, owner: &Address) {
+        env.storage().persistent().remove(&DataKey::Order(order_id));
+        Self::index_remove(env, DataKey::OwnerOrders(owner.clone()), order_id);
+    }
+
+    fn load_dca(env: &Env, plan_id: u64) -> DcaPlan {
+        env.storage().persistent()
+            .get(&DataKey::Dca(plan_id))
+            .unwrap_or_else(|| panic_with_error!(env, DexError::NoDca))
+    }
+
+    fn load_trigger(env: &Env, order_id: u64) -> TriggerOrder {
+        env.storage().persistent()
+            .get(&DataKey::Trigger(order_id))
+            .unwrap_or_else(|| panic_with_error!(env, DexError::NoTrigger))
+    }
+
+    fn load_twap(env: &Env, order_id: u64) -> TwapOrder {
+        env.storage().persistent()
+            .get(&DataKey::Twap(order_id))
+            .unwrap_or_else(|| panic_with_error!(env, DexError::NoTwap))
+    }
+
+    fn load_commit(env: &Env, commit_id: u64) -> SwapCommit {
+        env.storage().persistent()
+            .get(&DataKey::Commit(commit_id))
+            .unwrap_or_else(|| panic_with_error!(env, DexError::NoCommit))
+    }
+
+    fn reward_token(env: &Env) -> Address {
+        env.storage().instance().get(&DataKey::RewardToken)
+            .unwrap_or_else(|| panic_with_error!(env, DexError::NoRewardToken))
+    }
+
+    fn load_reward_pool(env: &Env) -> RewardPool {
+        env.storage().instance().get(&DataKey::RewardPool).unwrap_or(RewardPool {
+            rate: 0, budget: 0, acc_per_share: 0, total_weight: 0, last_update: env.ledger().timestamp(),
+        })
+    }
+
+    /// `pool` brought up to now: emit `rate` per elapsed second, capped by
+    /// the budget, into `acc_per_share`. Nothing is emitted while no
+    /// shares are staked, and only what the accumulator credits (rounded
+    /// up, so claims never outrun the budget) leaves the budget.
+    fn accrue(env: &Env, mut pool: RewardPool) -> RewardPool {
+        let now = env.ledger().timestamp();
+        if pool.total_weight > 0 && now > pool.last_update {
+            let emitted = (pool.rate * (now - pool.last_update) as i128).min(pool.budget);
+            let per_share = emitted * REWARD_SCALE / pool.total_weight;
+            pool.acc_per_share += per_share;
+            pool.budget -= (per_share * pool.total_weight + REWARD_SCALE - 1) / REWARD_SCALE;
+        }
+        pool.last_update = now;
+        pool
+    }
+
+    fn update_rewards(env: &Env) -> RewardPool {
+        let pool = Self::accrue(env, Self::load_reward_pool(env));
+        env.storage().instance().set(&DataKey::RewardPool, &pool);
+        pool
+    }
+
+    /// The provider's stake with rewards earned up to `pool` moved into
+    /// `unclaimed`.
+    /// Call before the provider's stake or lock changes.
+    fn settle_stake(env: &Env, pool: &RewardPool, provider: &Address) -> Stake {
+        let mut stake = Self::stake_of(env.clone(), provider.clone());
+        let weight = Self::reward_weight(env, provider, &stake);
+        stake.unclaimed += weight * pool.acc_per_share / REWARD_SCALE - stake.reward_debt;
+        stake
+    }
+
+    /// Save `stake` (and `pool`, whose `total_weight` may have changed)
+    /// with its debt reset to the current accumulator. Call after any
+    /// change to the provider's lock has been saved.
+    fn store_stake(env: &Env, pool: &RewardPool, provider: &Address, mut stake: Stake) {
+        env.storage().instance().set(&DataKey::RewardPool, pool);
+        let key = DataKey::Stake(provider.clone());
+        let weight = Self::reward_weight(env, provider, &stake);
+        if weight == 0 && stake.unclaimed == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            stake.reward_debt = weight * pool.acc_per_share / REWARD_SCALE;
+            Self::save_persistent(env, &key, &stake);
+        }
+    }
+
+    fn reward_weight(env: &Env, provider: &Address, stake: &Stake) -> i128 {
+        let lock: Option<VeLock> = env.storage().persistent().get(&DataKey::Lock(provider.clone()));
+        stake.shares + lock.map(|l| l.weight).unwrap_or(0)
+    }
+
+    fn load_intent(env: &Env, intent_id: u64) -> SwapIntent {
+        env.storage().persistent()
+            .get(&DataKey::Intent(intent_id))
+            .unwrap_or_else(|| panic_with_error!(env, DexError::NoIntent))
+    }
+
+    /// Swap `amount_in` already escrowed in the contract for `amount_out`
+    /// (priced and checked by the caller), paying `recipient`.
+    fn settle_escrowed(env: &Env, from: PoolAsset, to: PoolAsset, amount_in: i128, amount_out: i128, recipient: &Address) {
+        let sell: Address = env.storage().instance().get(&from.token_key).unwrap();
+        let buy: Address  = env.storage().instance().get(&to.token_key).unwrap();
+        Self::add_escrow(env, &sell, -amount_in);
+
+        let in_res: i128  = env.storage().instance().get(&from.reserve_key).unwrap();
+        let out_res: i128 = env.storage().instance().get(&to.reserve_key).unwrap();
+        env.storage().instance().set(&from.reserve_key, &(in_res + amount_in));
+        env.storage().instance().set(&to.reserve_key,   &(out_res - amount_out));
+
+        token::Client::new(env, &buy)
+            .transfer(&env.current_contract_address(), recipient, &amount_out);
+        SwapEvent { from_token: from.sym, to_token: to.sym, amount_in, amount_out, referrer: None }.publish(env);
+    }
+
+    /// Persistent write that also bumps the entry's TTL.
+    fn save_persistent<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+        let cfg = Self::load_ttl_config(env);
+        env.storage().persistent().set(key, value);
+        env.storage().persistent().extend_ttl(key, cfg.threshold, cfg.extend_to);
+    }
+
+    /// Append `id` to the id list stored under `key` (per-owner indexes).
+    fn index_add(env: &Env, key: DataKey, id: u64) {
+        let mut ids: Vec<u64> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+        ids.push_back(id);
+        Self::save_persistent(env, &key, &ids);
+    }
+
+    fn index_remove(env: &Env, key: DataKey, id: u64) {
+        let mut ids: Vec<u64> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+        if let Some(i) = ids.first_index_of(id) {
+            ids.remove(i);
+        }
+        if ids.is_empty() {
+            env.storage().persistent().remove(&key);
+        } else {
+            Self::save_persistent(env, &key, &ids);
+        }
+    }
+
+    fn pool_asset(env: &Env, token: &Address) -> PoolAsset {
+        let xlm: Address  = env.storage().instance().get(&DataKey::XlmToken).unwrap();
+        let usdc: Address = env.storage().instance().get(&DataKey::UsdcToken).unwrap();
+        let eth: Address  = env.storage().instance().get(&DataKey::EthToken).unwrap();
+        if *token == xlm {
+            PoolAsset { token_key: DataKey::XlmToken, reserve_key: DataKey::XlmReserve, rate_key: None, sym: symbol_short!("xlm") }
+        } else if *token == usdc {
+            PoolAsset { token_key: DataKey::UsdcToken, reserve_key: DataKey::UsdcReserve, rate_key: Some(DataKey::UsdcRate), sym: symbol_short!("usdc") }
+        } else if *token == eth {
+            PoolAsset { token_key: DataKey::EthToken, reserve_key: DataKey::EthReserve, rate_key: Some(DataKey::EthRate), sym: symbol_short!("eth") }
+        } else {
+            panic_with_error!(env, DexError::BadToken);
+        }
+    }
+
+    fn rate_of(env: &Env, asset: &PoolAsset) -> Option<i128> {
+        asset.rate_key.as_ref().map(|k| env.storage().instance().get(k).unwrap())
+    }
+
+    /// Output for `amount_in`, priced exactly like the `swap_*` entrypoints:
+    /// a 0.3% fee per hop, routed through XLM for USDC↔ETH.
+    fn quote_out(env: &Env, from: &PoolAsset, to: &PoolAsset, amount_in: i128) -> i128 {
+        if from.sym == to.sym { panic_with_error!(env, DexError::BadToken); }
+        match (Self::rate_of(env, from), Self::rate_of(env, to)) {
+            (None, Some(r))      => (amount_in * 997 / 1000) / r,
+            (Some(r), None)      => (amount_in * 997 / 1000) * r,
+            (Some(ri), Some(ro)) => {
+                let xlm_mid = (amount_in * 997 / 1000) * ri;
+                (xlm_mid * 997 / 1000) / ro
+            }
+            (None, None) => panic_with_error!(env, DexError::BadToken),
+        }
+    }
+
+    /// Smallest input for which `quote_out` yields at least `amount_out`.
+    fn quote_in(env: &Env, from: &PoolAsset, to: &PoolAsset, amount_out: i128) -> i128 {
+        let ceil = |n: i128, d: i128| (n + d - 1) / d;
+        // Undo one fee hop: smallest x with x * 997 / 1000 >= m
+        let unfee = |m: i128| ceil(m * 1000, 997);
+        if from.sym == to.sym { panic_with_error!(env, DexError::BadToken); }
+        match (Self::rate_of(env, from), Self::rate_of(env, to)) {
+            (None, Some(r))      => unfee(amount_out * r),
+            (Some(r), None)      => unfee(ceil(amount_out, r)),
+            (Some(ri), Some(ro)) => unfee(ceil(amount_out * ro * 1000, ri * 997)),
+            (None, None) => panic_with_error!(env, DexError::BadToken),
+        }
+    }
+
+    /// Balance of the pool token under `token_key` held by the contract,
+    /// less anything escrowed for open orders.
+    fn pool_balance(env: &Env, token_key: DataKey) -> i128 {
+        let tok: Address = env.storage().instance().get(&token_key).unwrap();
+        let escrowed: i128 = env.storage().instance().get(&DataKey::Escrowed(tok.clone())).unwrap_or(0);
+        token::Client::new(env, &tok).balance(&env.current_contract_address()) - escrowed
+    }
+
+    fn skim_token(env: &Env, token_key: DataKey, reserve_key: DataKey, to: &Address) -> i128 {
+        let tok: Address = env.storage().instance().get(&token_key).unwrap();
+        let reserve: i128 = env.storage().instance().get(&reserve_key).unwrap_or(0);
+        let excess = Self::pool_balance(env, token_key) - reserve;
+        if excess <= 0 { return 0; }
+        token::Client::new(env, &tok).transfer(&env.current_contract_address(), to, &excess);
+        excess
+    }
+
+    fn install_wasm(env: &Env, wasm_hash: BytesN<32>) {
+        if !env.storage().persistent().has(&DataKey::WasmAllowed(wasm_hash.clone())) {
+            panic_with_error!(env, DexError::WasmNotAllowed);
+        }
+        let mut history = Self::wasm_history(env.clone());
+        history.push_back(wasm_hash.clone());
+        env.storage().instance().set(&DataKey::WasmHistory, &history);
+
+        env.deployer().update_current_contract_wasm(wasm_hash.clone());
+        UpgradeEvent { action: symbol_short!("upgrade"), wasm_hash }.publish(env);
+    }
+
+    fn rollback_wasm(env: &Env) {
+        let mut history = Self::wasm_history(env.clone());
+        if history.len() < 2 {
+            panic_with_error!(env, DexError::NoRollback);
+        }
+        history.pop_back();
+        let previous = history.last().unwrap();
+        if !env.storage().persistent().has(&DataKey::WasmAllowed(previous.clone())) {
+            panic_with_error!(env, DexError::WasmNotAllowed);
+        }
+        env.storage().instance().set(&DataKey::WasmHistory, &history);
+
+        env.deployer().update_current_contract_wasm(previous.clone());
+        UpgradeEvent { action: symbol_short!("rollback"), wasm_hash: previous }.publish(env);
+    }
+
+    /// Upgrade storage from layout `from` to `from + 1`.
+    fn migrate_step(env: &Env, from: u32) {
+        match from {
+            // v0 → v1: start the withdrawal clock if the contract was
+            // paused before `WithdrawBlockedAt` was tracked.
+            0 => Self::track_withdraw_block(env),
+            _ => panic_with_error!(env, DexError::BadVersion),
+        }
+    }
+
+    fn load_ttl_config(env: &Env) -> TtlConfig {
+        env.storage().instance().get(&DataKey::TtlConfig).unwrap_or(TtlConfig {
+            threshold: DEFAULT_TTL_THRESHOLD,
+            extend_to: DEFAULT_TTL_EXTEND_TO,
+        })
+    }
+
+    fn extend_instance(env: &Env) {
+        let cfg = Self::load_ttl_config(env);
+        env.storage().instance().extend_ttl(cfg.threshold, cfg.extend_to);
+    }
+
+    fn extend_lp(env: &Env, user: &Address) {
+        let cfg = Self::load_ttl_config(env);
+        env.storage().persistent()
+            .extend_ttl(&DataKey::LpBalance(user.clone()), cfg.threshold, cfg.extend_to);
+    }
+
+    fn require_no_guardians(env: &Env) {
+        if env.storage().instance().has(&DataKey::GuardianThreshold) {
+            panic_with_error!(env, DexError::GuardiansActive);
+        }
+    }
+
+    fn require_guardian(env: &Env, guardian: &Address) {
+        let guardians: Vec<Address> = env.storage().instance()
+            .get(&DataKey::Guardians).unwrap_or(Vec::new(env));
+        if !guardians.contains(guardian) {
+            panic_with_error!(env, DexError::NotGuardian);
+        }
+    }
+
+    fn store_guardians(env: &Env, guardians: &Vec<Address>, threshold: u32) {
+        if threshold == 0 || threshold > guardians.len() {
+            panic_with_error!(env, DexError::BadThreshold);
+        }
+        for (i, g) in guardians.iter().enumerate() {
+            if guardians.first_index_of(&g) != Some(i as u32) {
+                panic_with_error!(env, DexError::BadThreshold);
+            }
+        }
+        env.storage().instance().set(&DataKey::Guardians, guardians);
+        env.storage().instance().set(&DataKey::GuardianThreshold, &threshold);
+    }
+
+    fn load_proposal(env: &Env, proposal_id: u64) -> Proposal {
+        let proposal: Proposal = env.storage().persistent()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic_with_error!(env, DexError::NoProposal));
+        if proposal.executed {
+            panic_with_error!(env, DexError::NoProposal);
+        }
+        proposal
+    }
+
+    fn require_not_paused(env: &Env) {
+        let paused: bool = env.storage().instance().get(&DataKey::Paused).unwrap_or(false);
+        let emergency: bool = env.storage().instance().get(&DataKey::Emergency).unwrap_or(false);
+        if paused || emergency { panic_with_error!(env, DexError::ContractPaused); }
+    }
+
+    /// Withdrawals ignore the pause switches in emergency mode, and once
+    /// they have been blocked for `MAX_PAUSE_SECS`.
+    fn require_withdrawals_open(env: &Env) {
+        let emergency: bool = env.storage().instance().get(&DataKey::Emergency).unwrap_or(false);
+        if emergency { return; }
+
+        let blocked_at: Option<u64> = env.storage().instance().get(&DataKey::WithdrawBlockedAt);
+        if let Some(at) = blocked_at {
+            if env.ledger().timestamp() >= at + MAX_PAUSE_SECS { return; }
+        }
+        Self::require_not_paused(env);
+        Self::require_op_open(env, PauseFlag::Withdrawals);
+    }
+
+    /// Keep `WithdrawBlockedAt` in step with the switches that block
+    /// withdrawals. A new block only restarts the clock after withdrawals
+    /// have stayed open for a full `MAX_PAUSE_SECS`, so toggling the pause
+    /// off and on cannot extend it.
+    fn track_withdraw_block(env: &Env) {
+        let now = env.ledger().timestamp();
+        let paused: bool = env.storage().instance().get(&DataKey::Paused).unwrap_or(false);
+        let flagged: bool = env.storage().instance()
+            .get(&DataKey::OpPaused(PauseFlag::Withdrawals)).unwrap_or(false);
+        let blocked_at: Option<u64> = env.storage().instance().get(&DataKey::WithdrawBlockedAt);
+        let opened_at: Option<u64> = env.storage().instance().get(&DataKey::WithdrawOpenedAt);
+
+        if paused || flagged {
+            let restart = match (blocked_at, opened_at) {
+                (None, _) => true,
+                (Some(_), Some(open)) => now >= open + MAX_PAUSE_SECS,
+                (Some(_), None) => false,
+            };
+            if restart {
+                env.storage().instance().set(&DataKey::WithdrawBlockedAt, &now);
+            }
+            env.storage().instance().remove(&DataKey::WithdrawOpenedAt);
+        } else if blocked_at.is_some() && opened_at.is_none() {
+            env.storage().instance().set(&DataKey::WithdrawOpenedAt, &now);
+        }
+    }
+
+    fn require_op_open(env: &Env, flag: PauseFlag) {
+        let paused: bool = env.storage().instance().get(&DataKey::OpPaused(flag)).unwrap_or(false);
+        if paused { panic_with_error!(env, DexError::OperationPaused); }
+    }
+
+    fn require_asset_open(env: &Env, token: &Address) {
+        let paused: bool = env.storage().instance()
+            .get(&DataKey::AssetPaused(token.clone())).unwrap_or(false);
+        if paused { panic_with_error!(env, DexError::AssetPaused); }
+    }
+
+    fn require_swap_open(env: &Env, from_key: DataKey, to_key: DataKey) {
+        Self::require_not_paused(env);
+        Self::require_op_open(env, PauseFlag::Swaps);
+        if !Self::pair_open(env, &from_key, &to_key) {
+            panic_with_error!(env, DexError::AssetPaused);
+        }
+    }
+
+    /// Asset and pair half of `require_swap_open`, for loops that skip
+    /// paused markets instead of failing the whole call.
+    fn pair_open(env: &Env, from_key: &DataKey, to_key: &DataKey) -> bool {
+        let from: Address = env.storage().instance().get(from_key).unwrap();
+        let to: Address   = env.storage().instance().get(to_key).unwrap();
+        let paused = |key: DataKey| env.storage().instance().get(&key).unwrap_or(false);
+        !paused(DataKey::AssetPaused(from.clone()))
+            && !paused(DataKey::AssetPaused(to.clone()))
+            && !paused(DataKey::PairPaused(from, to))
+    }
+}
+mod test;
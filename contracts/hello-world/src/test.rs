@@ -3,11 +3,15 @@
 extern crate std;
 
 use soroban_sdk::{
-    testutils::Address as _,
-    token, Address, Env,
+    testutils::{storage::{Instance as _, Persistent as _}, Address as _, Ledger},
+    contract, contractimpl, token, vec, Address, Bytes, BytesN, Env, Vec,
 };
 
-use crate::{StellarDex, StellarDexClient};
+use crate::{
+    DataKey, DexError, FaucetConfig, GuardedAction, MinterQuota, PauseFlag, Role, Solvency, StellarDex, StellarDexClient, SwapLeg, LimitOrder, DcaPlan, TriggerKind, TwapOrder, CommitConfig, SwapIntent, Stake, VeLock,
+    TtlConfig, DEFAULT_DCA_TIP_BPS, DEFAULT_FLASH_FEE_BPS, DEFAULT_REFERRAL_BPS, DEFAULT_REVEAL_WINDOW, DEFAULT_MIN_INTENT, MAX_BATCH_INTENTS, MAX_REVEAL_LEDGERS, MAX_LOCK_SECS, PRICE_SCALE,
+    DEFAULT_TTL_EXTEND_TO, MAX_FAUCET_COOLDOWN_SECS, MAX_PAUSE_SECS, QUOTA_BUCKETS, REWARD_SCALE, SCHEMA_VERSION,
+};
 
 // ─────────────────────────────────────────────
 //  Helpers
@@ -24,28 +28,43 @@ fn create_token<'a>(
     )
 }
 
-fn create_dex(env: &Env) -> StellarDexClient {
+fn create_dex(env: &Env) -> StellarDexClient<'_> {
     StellarDexClient::new(env, &env.register(StellarDex, ()))
 }
 
+/// Register an XLM SAC and initialise the DEX with it.
+fn init_dex<'a>(
+    env:   &'a Env,
+    dex:   &StellarDexClient,
+    admin: &Address,
+    usdc:  &Address,
+    eth:   &Address,
+) -> token::StellarAssetClient<'a> {
+    let (xlm, xlm_s) = create_token(env, admin);
+    dex.initialize(admin, &xlm.address, usdc, eth, &USDC_RATE, &ETH_RATE);
+    xlm_s
+}
+
 // Rates matching the real-world deploy
 const USDC_RATE: i128 = 61_900_000;
 const ETH_RATE:  i128 = 121_200_000_000;
 
-/// Seed DEX reserves by minting tokens directly into the contract
-/// then calling add_liquidity to keep internal accounting correct.
+/// Seed DEX reserves by minting tokens to the admin
+/// then calling add_liquidity to move them into the pool.
+#[allow(clippy::too_many_arguments)]
 fn seed(
-    env:      &Env,
     dex:      &StellarDexClient,
     admin:    &Address,
+    xlm_sac:  &token::StellarAssetClient,
     usdc_sac: &token::StellarAssetClient,
     eth_sac:  &token::StellarAssetClient,
     xlm:  i128,
     usdc: i128,
     eth:  i128,
 ) {
-    if usdc > 0 { usdc_sac.mint(&dex.address, &usdc); }
-    if eth  > 0 { eth_sac.mint(&dex.address,  &eth); }
+    if xlm  > 0 { xlm_sac.mint(admin,  &xlm); }
+    if usdc > 0 { usdc_sac.mint(admin, &usdc); }
+    if eth  > 0 { eth_sac.mint(admin,  &eth); }
     dex.add_liquidity(admin, &xlm, &usdc, &eth);
 }
 
@@ -61,8 +80,10 @@ fn test_init_stores_correct_state() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
+    assert_eq!(dex.get_admin(), None);
 
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    assert_eq!(dex.get_admin(), Some(admin.clone()));
 
     let (ur, er) = dex.get_rates();
     assert_eq!(ur, USDC_RATE);
@@ -87,8 +108,8 @@ fn test_init_twice_panics() {
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
 
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE); // must panic
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.initialize(&admin, &usdc.address, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE); // must panic
 }
 
 // ─────────────────────────────────────────────
@@ -103,11 +124,11 @@ fn test_pause_and_unpause() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
-    dex.set_paused(&true);
+    dex.set_paused(&admin, &true);
     assert!(dex.is_paused());
-    dex.set_paused(&false);
+    dex.set_paused(&admin, &false);
     assert!(!dex.is_paused());
 }
 
@@ -119,10 +140,10 @@ fn test_update_rates() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
-    dex.set_usdc_rate(&50_000_000_i128);
-    dex.set_eth_rate(&100_000_000_000_i128);
+    dex.set_usdc_rate(&admin, &50_000_000_i128);
+    dex.set_eth_rate(&admin, &100_000_000_000_i128);
 
     let (ur, er) = dex.get_rates();
     assert_eq!(ur, 50_000_000);
@@ -138,12 +159,12 @@ fn test_transfer_admin() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
     dex.transfer_admin(&new_admin);
-    // If admin changed correctly, further admin calls still work
-    // (mock_all_auths accepts any auth)
-    dex.set_usdc_rate(&42_000_000_i128);
+    assert_eq!(dex.get_admin(), Some(new_admin.clone()));
+    // If admin changed correctly, the new admin passes role checks
+    dex.set_usdc_rate(&new_admin, &42_000_000_i128);
     let (ur, _) = dex.get_rates();
     assert_eq!(ur, 42_000_000);
 }
@@ -161,9 +182,9 @@ fn test_mint_usdc_balance_and_stats() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
-    dex.mint_usdc(&user, &1_000_i128);
+    dex.mint_usdc(&admin, &user, &1_000_i128);
 
     assert_eq!(usdc.balance(&user), 1_000);
     let (um, em) = dex.mint_stats();
@@ -178,11 +199,11 @@ fn test_mint_eth_balance_and_stats() {
     let admin = Address::generate(&env);
     let user  = Address::generate(&env);
     let (usdc, _)     = create_token(&env, &admin);
-    let (eth,  eth_s) = create_token(&env, &admin);
+    let (eth,  _)     = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
-    dex.mint_eth(&user, &5_i128);
+    dex.mint_eth(&admin, &user, &5_i128);
 
     assert_eq!(eth.balance(&user), 5);
     let (um, em) = dex.mint_stats();
@@ -200,8 +221,8 @@ fn test_mint_zero_panics() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    dex.mint_usdc(&user, &0_i128);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.mint_usdc(&admin, &user, &0_i128);
 }
 
 #[test]
@@ -214,9 +235,9 @@ fn test_mint_while_paused_panics() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    dex.set_paused(&true);
-    dex.mint_usdc(&user, &100_i128);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.set_paused(&admin, &true);
+    dex.mint_usdc(&admin, &user, &100_i128);
 }
 
 // ─────────────────────────────────────────────
@@ -231,8 +252,9 @@ fn test_add_liquidity_updates_reserves_and_lp() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
+    xlm_s.mint(&admin,  &1_000_000_000_i128);
     usdc_s.mint(&admin, &500_i128);
     eth_s.mint(&admin,  &2_i128);
 
@@ -256,8 +278,9 @@ fn test_remove_liquidity_proportional_share() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
+    xlm_s.mint(&admin,  &6_190_000_000_i128);
     usdc_s.mint(&admin, &1_000_i128);
     eth_s.mint(&admin,  &10_i128);
 
@@ -282,11 +305,13 @@ fn test_two_providers_lp_balances() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  _)      = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
+    xlm_s.mint(&admin,  &1_000_000_000_i128);
     usdc_s.mint(&admin, &1_000_i128);
     let lp1 = dex.add_liquidity(&admin, &1_000_000_000_i128, &1_000_i128, &0_i128);
 
+    xlm_s.mint(&user2,  &500_000_000_i128);
     usdc_s.mint(&user2, &500_i128);
     let lp2 = dex.add_liquidity(&user2, &500_000_000_i128, &500_i128, &0_i128);
 
@@ -304,7 +329,7 @@ fn test_remove_more_lp_than_owned_panics() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  _)      = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
     usdc_s.mint(&admin, &100_i128);
     let lp = dex.add_liquidity(&admin, &0_i128, &100_i128, &0_i128);
@@ -324,15 +349,16 @@ fn test_swap_xlm_for_usdc() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 10_000_000_000, 10_000, 0);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 10_000, 0);
 
     let xlm_in: i128 = 619_000_000; // ~61.9 XLM
     let quote = dex.quote_xlm_to_usdc(&xlm_in);
     assert!(quote > 0);
 
+    xlm_s.mint(&user, &xlm_in);
     let before = usdc.balance(&user);
-    dex.swap_xlm_for_usdc(&user, &xlm_in, &0_i128);
+    dex.swap_xlm_for_usdc(&user, &xlm_in, &0_i128, &None);
     assert_eq!(usdc.balance(&user) - before, quote);
 }
 
@@ -345,11 +371,11 @@ fn test_swap_usdc_for_xlm() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 100_000_000_000, 1_000, 0);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 100_000_000_000, 1_000, 0);
 
     usdc_s.mint(&user, &100_i128);
-    dex.swap_usdc_for_xlm(&user, &10_i128, &0_i128);
+    dex.swap_usdc_for_xlm(&user, &10_i128, &0_i128, &None);
 
     assert_eq!(usdc.balance(&user), 90); // 10 spent
 }
@@ -363,15 +389,16 @@ fn test_swap_xlm_for_eth() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 1_000_000_000_000, 0, 100);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000, 0, 100);
 
     let xlm_in: i128 = 121_200_000_000 * 10; // 10× ETH rate
     let quote = dex.quote_xlm_to_eth(&xlm_in);
     assert!(quote >= 9);
 
+    xlm_s.mint(&user, &xlm_in);
     let before = eth.balance(&user);
-    dex.swap_xlm_for_eth(&user, &xlm_in, &0_i128);
+    dex.swap_xlm_for_eth(&user, &xlm_in, &0_i128, &None);
     assert_eq!(eth.balance(&user) - before, quote);
 }
 
@@ -384,11 +411,11 @@ fn test_swap_eth_for_xlm() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 1_000_000_000_000, 0, 10);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000, 0, 10);
 
     eth_s.mint(&user, &1_i128);
-    dex.swap_eth_for_xlm(&user, &1_i128, &0_i128);
+    dex.swap_eth_for_xlm(&user, &1_i128, &0_i128, &None);
     assert_eq!(eth.balance(&user), 0);
 }
 
@@ -401,15 +428,15 @@ fn test_swap_usdc_for_eth_cross_pair() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 1_000_000_000_000, 10_000, 500);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000, 10_000, 500);
 
-    usdc_s.mint(&user, &1_000_i128);
+    usdc_s.mint(&user, &10_000_i128);
     let before = eth.balance(&user);
-    dex.swap_usdc_for_eth(&user, &100_i128, &0_i128);
+    dex.swap_usdc_for_eth(&user, &5_000_i128, &0_i128, &None);
 
     assert!(eth.balance(&user) > before);
-    assert_eq!(usdc.balance(&user), 900);
+    assert_eq!(usdc.balance(&user), 5_000);
 }
 
 #[test]
@@ -421,12 +448,12 @@ fn test_swap_eth_for_usdc_cross_pair() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 1_000_000_000_000, 10_000, 100);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000, 10_000, 100);
 
-    eth_s.mint(&user, &2_i128);
+    eth_s.mint(&user, &3_i128);
     let before = usdc.balance(&user);
-    dex.swap_eth_for_usdc(&user, &1_i128, &0_i128);
+    dex.swap_eth_for_usdc(&user, &2_i128, &0_i128, &None);
 
     assert!(usdc.balance(&user) > before);
     assert_eq!(eth.balance(&user), 1);
@@ -446,11 +473,11 @@ fn test_slippage_xlm_for_usdc_panics() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 10_000_000_000, 1_000, 0);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 1_000, 0);
 
     // 1 XLM gives ~0 USDC; demanding 9_999 should fail
-    dex.swap_xlm_for_usdc(&user, &61_900_000_i128, &9_999_i128);
+    dex.swap_xlm_for_usdc(&user, &61_900_000_i128, &9_999_i128, &None);
 }
 
 #[test]
@@ -463,11 +490,11 @@ fn test_slippage_usdc_for_xlm_panics() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 10_000_000_000, 1_000, 0);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 1_000, 0);
 
     usdc_s.mint(&user, &10_i128);
-    dex.swap_usdc_for_xlm(&user, &10_i128, &999_999_999_999_i128);
+    dex.swap_usdc_for_xlm(&user, &10_i128, &999_999_999_999_i128, &None);
 }
 
 // ─────────────────────────────────────────────
@@ -484,9 +511,9 @@ fn test_swap_fails_on_empty_usdc_reserve() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
     // No liquidity added
-    dex.swap_xlm_for_usdc(&user, &1_000_000_i128, &0_i128);
+    dex.swap_xlm_for_usdc(&user, &1_000_000_i128, &0_i128, &None);
 }
 
 #[test]
@@ -499,8 +526,8 @@ fn test_swap_fails_on_empty_eth_reserve() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    dex.swap_xlm_for_eth(&user, &1_000_000_i128, &0_i128);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.swap_xlm_for_eth(&user, &1_000_000_i128, &0_i128, &None);
 }
 
 // ─────────────────────────────────────────────
@@ -517,10 +544,10 @@ fn test_swap_blocked_when_paused() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 10_000_000_000, 1_000, 10);
-    dex.set_paused(&true);
-    dex.swap_xlm_for_usdc(&user, &61_900_000_i128, &0_i128);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 1_000, 10);
+    dex.set_paused(&admin, &true);
+    dex.swap_xlm_for_usdc(&user, &61_900_000_i128, &0_i128, &None);
 }
 
 #[test]
@@ -532,8 +559,8 @@ fn test_add_liquidity_blocked_when_paused() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  _)      = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    dex.set_paused(&true);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.set_paused(&admin, &true);
     usdc_s.mint(&admin, &100_i128);
     dex.add_liquidity(&admin, &0_i128, &100_i128, &0_i128);
 }
@@ -547,13 +574,14 @@ fn test_swap_works_after_unpause() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 10_000_000_000, 1_000, 0);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 1_000, 0);
 
-    dex.set_paused(&true);
-    dex.set_paused(&false);
+    dex.set_paused(&admin, &true);
+    dex.set_paused(&admin, &false);
 
-    let out = dex.swap_xlm_for_usdc(&user, &619_000_000_i128, &0_i128);
+    xlm_s.mint(&user, &619_000_000_i128);
+    let out = dex.swap_xlm_for_usdc(&user, &619_000_000_i128, &0_i128, &None);
     assert!(out > 0);
 }
 
@@ -569,7 +597,7 @@ fn test_quote_xlm_to_usdc_reflects_fee() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
     // 619 XLM  → 619_000_000 * 0.997 / 61_900_000 = 9.97 → 9
     let q = dex.quote_xlm_to_usdc(&619_000_000_i128);
@@ -584,11 +612,11 @@ fn test_quote_usdc_to_xlm_reflects_fee() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
-    // 9 USDC * 0.997 * 61_900_000 = 554_877_300
-    let q = dex.quote_usdc_to_xlm(&9_i128);
-    assert_eq!(q, 554_877_300);
+    // 1_000 USDC → 997 after fee → 997 * 61_900_000 = 61_714_300_000
+    let q = dex.quote_usdc_to_xlm(&1_000_i128);
+    assert_eq!(q, 61_714_300_000);
 }
 
 #[test]
@@ -599,7 +627,7 @@ fn test_quote_eth_large_amount() {
     let (usdc, _) = create_token(&env, &admin);
     let (eth,  _) = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
 
     // 10× ETH worth of XLM should yield ≥9 ETH after fee
     let q = dex.quote_xlm_to_eth(&(ETH_RATE * 10));
@@ -619,12 +647,13 @@ fn test_reserves_update_correctly_after_xlm_usdc_swap() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 10_000_000_000, 5_000, 0);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 5_000, 0);
 
     let (xlm_before, usdc_before, _) = dex.get_reserves();
     let xlm_in: i128 = 619_000_000;
-    let usdc_out = dex.swap_xlm_for_usdc(&user, &xlm_in, &0_i128);
+    xlm_s.mint(&user, &xlm_in);
+    let usdc_out = dex.swap_xlm_for_usdc(&user, &xlm_in, &0_i128, &None);
 
     let (xlm_after, usdc_after, _) = dex.get_reserves();
     assert_eq!(xlm_after,  xlm_before  + xlm_in);
@@ -640,14 +669,3135 @@ fn test_reserves_update_correctly_after_eth_swap() {
     let (usdc, usdc_s) = create_token(&env, &admin);
     let (eth,  eth_s)  = create_token(&env, &admin);
     let dex = create_dex(&env);
-    dex.initialize(&admin, &usdc.address, &eth.address, &USDC_RATE, &ETH_RATE);
-    seed(&env, &dex, &admin, &usdc_s, &eth_s, 1_000_000_000_000, 0, 100);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000, 0, 100);
 
     let (xlm_before, _, eth_before) = dex.get_reserves();
     let xlm_in: i128 = ETH_RATE * 10;
-    let eth_out = dex.swap_xlm_for_eth(&user, &xlm_in, &0_i128);
+    xlm_s.mint(&user, &xlm_in);
+    let eth_out = dex.swap_xlm_for_eth(&user, &xlm_in, &0_i128, &None);
 
     let (xlm_after, _, eth_after) = dex.get_reserves();
     assert_eq!(xlm_after, xlm_before + xlm_in);
     assert_eq!(eth_after, eth_before - eth_out);
-}
\ No newline at end of file
+}
+// ─────────────────────────────────────────────
+//  11. ROLES
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_grant_and_revoke_role() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let bot   = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    assert!(!dex.has_role(&Role::RateSetter, &bot));
+    dex.grant_role(&Role::RateSetter, &bot);
+    assert!(dex.has_role(&Role::RateSetter, &bot));
+    assert!(!dex.has_role(&Role::Upgrader, &bot));
+
+    dex.revoke_role(&Role::RateSetter, &bot);
+    assert!(!dex.has_role(&Role::RateSetter, &bot));
+}
+
+#[test]
+fn test_rate_setter_can_set_rates() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let bot   = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.grant_role(&Role::RateSetter, &bot);
+    dex.set_usdc_rate(&bot, &60_000_000_i128);
+    dex.set_eth_rate(&bot, &120_000_000_000_i128);
+
+    assert_eq!(dex.get_rates(), (60_000_000, 120_000_000_000));
+}
+
+#[test]
+fn test_minter_and_pauser_roles() {
+    let env = Env::default();
+    // The SAC admin authorises the nested `mint` call, not the root one
+    env.mock_all_auths_allowing_non_root_auth();
+    let admin  = Address::generate(&env);
+    let minter = Address::generate(&env);
+    let pauser = Address::generate(&env);
+    let user   = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.grant_role(&Role::Minter, &minter);
+    dex.grant_role(&Role::Pauser, &pauser);
+
+    dex.mint_usdc(&minter, &user, &250_i128);
+    assert_eq!(usdc.balance(&user), 250);
+
+    dex.set_paused(&pauser, &true);
+    assert!(dex.is_paused());
+}
+
+#[test]
+#[should_panic]
+fn test_rate_setter_cannot_pause() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let bot   = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.grant_role(&Role::RateSetter, &bot);
+    dex.set_paused(&bot, &true);
+}
+
+#[test]
+#[should_panic]
+fn test_revoked_minter_cannot_mint() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin  = Address::generate(&env);
+    let minter = Address::generate(&env);
+    let user   = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.grant_role(&Role::Minter, &minter);
+    dex.revoke_role(&Role::Minter, &minter);
+    dex.mint_usdc(&minter, &user, &100_i128);
+}
+
+#[test]
+#[should_panic]
+fn test_non_upgrader_cannot_upgrade() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let bot   = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.grant_role(&Role::RateSetter, &bot);
+    dex.upgrade(&bot, &BytesN::from_array(&env, &[0; 32]));
+}
+
+// ─────────────────────────────────────────────
+//  12. GUARDIANS (M-of-N)
+// ─────────────────────────────────────────────
+
+#[test]
+#[should_panic]
+fn test_transfer_admin_blocked_once_guardians_set() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let g1 = Address::generate(&env);
+    let g2 = Address::generate(&env);
+    dex.set_guardians(&vec![&env, g1, g2], &2);
+    dex.transfer_admin(&Address::generate(&env));
+}
+
+#[test]
+fn test_guardian_transfer_admin_reaches_threshold() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin     = Address::generate(&env);
+    let new_admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let g1 = Address::generate(&env);
+    let g2 = Address::generate(&env);
+    let g3 = Address::generate(&env);
+    dex.set_guardians(&vec![&env, g1.clone(), g2.clone(), g3.clone()], &2);
+
+    let id = dex.propose(&g1, &GuardedAction::TransferAdmin(new_admin.clone()));
+    assert_eq!(
+        dex.try_execute(&g1, &id),
+        Err(Ok(DexError::ThresholdNotMet.into()))
+    );
+
+    dex.approve(&g3, &id);
+    dex.execute(&g2, &id);
+    assert!(dex.get_proposal(&id).executed);
+
+    // Old admin lost its powers, the new one has them
+    assert!(dex.try_set_usdc_rate(&admin, &1_i128).is_err());
+    dex.set_usdc_rate(&new_admin, &1_i128);
+}
+
+#[test]
+#[should_panic]
+fn test_guardian_cannot_approve_twice() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let g1 = Address::generate(&env);
+    let g2 = Address::generate(&env);
+    dex.set_guardians(&vec![&env, g1.clone(), g2], &2);
+
+    let id = dex.propose(&g1, &GuardedAction::Upgrade(BytesN::from_array(&env, &[1; 32])));
+    dex.approve(&g1, &id);
+}
+
+#[test]
+#[should_panic]
+fn test_executed_proposal_cannot_rerun() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let g1 = Address::generate(&env);
+    dex.set_guardians(&vec![&env, g1.clone()], &1);
+
+    let id = dex.propose(&g1, &GuardedAction::TransferAdmin(Address::generate(&env)));
+    dex.execute(&g1, &id);
+    dex.execute(&g1, &id);
+}
+
+#[test]
+fn test_guardian_threshold_change() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let g1 = Address::generate(&env);
+    let g2 = Address::generate(&env);
+    let g3 = Address::generate(&env);
+    let set = vec![&env, g1.clone(), g2.clone(), g3.clone()];
+    dex.set_guardians(&set, &2);
+
+    // Raise the bar to 3-of-3
+    let id = dex.propose(&g1, &GuardedAction::SetGuardians(set.clone(), 3));
+    dex.approve(&g2, &id);
+    dex.execute(&g1, &id);
+    assert_eq!(dex.guardians(), (set, 3));
+
+    // Two approvals are no longer enough
+    let id = dex.propose(&g1, &GuardedAction::TransferAdmin(Address::generate(&env)));
+    dex.approve(&g2, &id);
+    assert_eq!(
+        dex.try_execute(&g1, &id),
+        Err(Ok(DexError::ThresholdNotMet.into()))
+    );
+    dex.approve(&g3, &id);
+    dex.execute(&g3, &id);
+}
+
+#[test]
+fn test_guardian_rotation_drops_old_signer() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let g1 = Address::generate(&env);
+    let g2 = Address::generate(&env);
+    let g3 = Address::generate(&env);
+    let g4 = Address::generate(&env);
+    dex.set_guardians(&vec![&env, g1.clone(), g2.clone(), g3.clone()], &2);
+
+    // g3 approves a pending proposal, then gets rotated out for g4
+    let pending = dex.propose(&g3, &GuardedAction::TransferAdmin(Address::generate(&env)));
+    let rotate = dex.propose(&g1, &GuardedAction::SetGuardians(
+        vec![&env, g1.clone(), g2.clone(), g4.clone()], 2,
+    ));
+    dex.approve(&g2, &rotate);
+    dex.execute(&g2, &rotate);
+
+    assert_eq!(
+        dex.try_propose(&g3, &GuardedAction::TransferAdmin(g3.clone())),
+        Err(Ok(DexError::NotGuardian.into()))
+    );
+
+    // g3's earlier approval no longer counts towards the threshold
+    dex.approve(&g4, &pending);
+    assert_eq!(
+        dex.try_execute(&g4, &pending),
+        Err(Ok(DexError::ThresholdNotMet.into()))
+    );
+    dex.approve(&g1, &pending);
+    dex.execute(&g4, &pending);
+}
+
+#[test]
+#[should_panic]
+fn test_guardian_threshold_above_set_size_panics() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let g1 = Address::generate(&env);
+    dex.set_guardians(&vec![&env, g1], &2);
+}
+
+// ─────────────────────────────────────────────
+//  13. GRANULAR PAUSE FLAGS
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_asset_pause_only_blocks_that_asset() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000, 10_000, 100);
+
+    dex.set_asset_paused(&admin, &eth.address, &true);
+    assert!(dex.is_asset_paused(&eth.address));
+
+    xlm_s.mint(&user, &(ETH_RATE * 10));
+    assert_eq!(
+        dex.try_swap_xlm_for_eth(&user, &(ETH_RATE * 10), &0_i128, &None),
+        Err(Ok(DexError::AssetPaused.into()))
+    );
+    assert_eq!(
+        dex.try_mint_eth(&admin, &user, &1_i128),
+        Err(Ok(DexError::AssetPaused.into()))
+    );
+
+    // USDC trading and LP withdrawals keep working
+    assert!(dex.swap_xlm_for_usdc(&user, &619_000_000_i128, &0_i128, &None) > 0);
+    let (_, _, eth_out) = dex.remove_liquidity(&admin, &(dex.lp_balance(&admin) / 2));
+    assert!(eth_out > 0);
+}
+
+#[test]
+fn test_pair_pause_blocks_both_directions() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000, 10_000, 100);
+
+    dex.set_pair_paused(&admin, &usdc.address, &eth.address, &true);
+    assert!(dex.is_pair_paused(&eth.address, &usdc.address));
+
+    usdc_s.mint(&user, &5_000_i128);
+    eth_s.mint(&user, &2_i128);
+    assert_eq!(
+        dex.try_swap_usdc_for_eth(&user, &5_000_i128, &0_i128, &None),
+        Err(Ok(DexError::AssetPaused.into()))
+    );
+    assert_eq!(
+        dex.try_swap_eth_for_usdc(&user, &2_i128, &0_i128, &None),
+        Err(Ok(DexError::AssetPaused.into()))
+    );
+
+    // Each leg against XLM is still open
+    assert!(dex.swap_eth_for_xlm(&user, &2_i128, &0_i128, &None) > 0);
+
+    dex.set_pair_paused(&admin, &usdc.address, &eth.address, &false);
+    assert!(dex.swap_usdc_for_eth(&user, &5_000_i128, &0_i128, &None) > 0);
+}
+
+#[test]
+fn test_swap_pause_leaves_liquidity_open() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 1_000, 0);
+
+    dex.set_op_paused(&admin, &PauseFlag::Swaps, &true);
+    assert!(dex.is_op_paused(&PauseFlag::Swaps));
+
+    xlm_s.mint(&user, &619_000_000_i128);
+    assert_eq!(
+        dex.try_swap_xlm_for_usdc(&user, &619_000_000_i128, &0_i128, &None),
+        Err(Ok(DexError::OperationPaused.into()))
+    );
+
+    let lp = dex.add_liquidity(&user, &619_000_000_i128, &0_i128, &0_i128);
+    dex.remove_liquidity(&user, &lp);
+}
+
+#[test]
+fn test_withdrawal_and_deposit_flags_are_independent() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    usdc_s.mint(&admin, &200_i128);
+    dex.set_op_paused(&admin, &PauseFlag::Withdrawals, &true);
+    let lp = dex.add_liquidity(&admin, &0_i128, &100_i128, &0_i128);
+    assert_eq!(
+        dex.try_remove_liquidity(&admin, &lp),
+        Err(Ok(DexError::OperationPaused.into()))
+    );
+
+    dex.set_op_paused(&admin, &PauseFlag::Withdrawals, &false);
+    dex.set_op_paused(&admin, &PauseFlag::Deposits, &true);
+    assert_eq!(
+        dex.try_add_liquidity(&admin, &0_i128, &100_i128, &0_i128),
+        Err(Ok(DexError::OperationPaused.into()))
+    );
+    dex.remove_liquidity(&admin, &lp);
+}
+
+#[test]
+#[should_panic]
+fn test_mint_blocked_by_minting_flag() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.set_op_paused(&admin, &PauseFlag::Minting, &true);
+    dex.mint_usdc(&admin, &user, &100_i128);
+}
+
+// ─────────────────────────────────────────────
+//  14. EMERGENCY EXIT
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_emergency_mode_halts_trading_but_allows_exit() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 1_000, 10);
+
+    // Even with every other switch thrown, emergency mode lets LPs out
+    dex.set_paused(&admin, &true);
+    dex.set_op_paused(&admin, &PauseFlag::Withdrawals, &true);
+    dex.set_emergency(&admin, &true);
+    assert!(dex.is_emergency());
+
+    xlm_s.mint(&user, &619_000_000_i128);
+    assert!(dex.try_swap_xlm_for_usdc(&user, &619_000_000_i128, &0_i128, &None).is_err());
+    assert!(dex.try_add_liquidity(&user, &619_000_000_i128, &0_i128, &0_i128).is_err());
+
+    let lp = dex.lp_balance(&admin);
+    let (xlm_out, usdc_out, eth_out) = dex.remove_liquidity(&admin, &lp);
+    assert_eq!((xlm_out, usdc_out, eth_out), (10_000_000_000, 1_000, 10));
+}
+
+#[test]
+#[should_panic]
+fn test_emergency_mode_blocks_swaps_without_global_pause() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 1_000, 0);
+
+    dex.set_emergency(&admin, &true);
+    xlm_s.mint(&user, &619_000_000_i128);
+    dex.swap_xlm_for_usdc(&user, &619_000_000_i128, &0_i128, &None);
+}
+
+#[test]
+fn test_pause_cannot_block_withdrawals_past_max_duration() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    usdc_s.mint(&admin, &1_000_i128);
+    let lp = dex.add_liquidity(&admin, &0_i128, &1_000_i128, &0_i128);
+
+    env.ledger().set_timestamp(1_000);
+    dex.set_paused(&admin, &true);
+    dex.set_op_paused(&admin, &PauseFlag::Withdrawals, &true);
+    assert_eq!(dex.withdrawals_forced_open_at(), 1_000 + MAX_PAUSE_SECS);
+
+    env.ledger().set_timestamp(1_000 + MAX_PAUSE_SECS - 1);
+    assert_eq!(
+        dex.try_remove_liquidity(&admin, &lp),
+        Err(Ok(DexError::ContractPaused.into()))
+    );
+
+    env.ledger().set_timestamp(1_000 + MAX_PAUSE_SECS);
+    let (_, usdc_out, _) = dex.remove_liquidity(&admin, &lp);
+    assert_eq!(usdc_out, 1_000);
+    // Trading stays halted
+    assert!(dex.is_paused());
+}
+
+#[test]
+fn test_repausing_does_not_reset_withdrawal_clock() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    usdc_s.mint(&admin, &1_000_i128);
+    let lp = dex.add_liquidity(&admin, &0_i128, &1_000_i128, &0_i128);
+
+    env.ledger().set_timestamp(1_000);
+    dex.set_op_paused(&admin, &PauseFlag::Withdrawals, &true);
+
+    // Briefly reopen, then switch to the global pause instead
+    env.ledger().set_timestamp(1_000 + MAX_PAUSE_SECS - 10);
+    dex.set_op_paused(&admin, &PauseFlag::Withdrawals, &false);
+    dex.set_paused(&admin, &true);
+    assert_eq!(dex.withdrawals_forced_open_at(), 1_000 + MAX_PAUSE_SECS);
+
+    env.ledger().set_timestamp(1_000 + MAX_PAUSE_SECS);
+    dex.remove_liquidity(&admin, &lp);
+}
+
+#[test]
+fn test_withdrawal_clock_restarts_after_full_open_window() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    usdc_s.mint(&admin, &1_000_i128);
+    let lp = dex.add_liquidity(&admin, &0_i128, &1_000_i128, &0_i128);
+
+    env.ledger().set_timestamp(1_000);
+    dex.set_paused(&admin, &true);
+    env.ledger().set_timestamp(2_000);
+    dex.set_paused(&admin, &false);
+
+    let later = 2_000 + MAX_PAUSE_SECS;
+    env.ledger().set_timestamp(later);
+    dex.set_paused(&admin, &true);
+    assert_eq!(dex.withdrawals_forced_open_at(), later + MAX_PAUSE_SECS);
+    assert!(dex.try_remove_liquidity(&admin, &lp).is_err());
+}
+
+// ─────────────────────────────────────────────
+//  15. STORAGE TTL
+// ─────────────────────────────────────────────
+
+fn lp_ttl(env: &Env, dex: &StellarDexClient, user: &Address) -> u32 {
+    env.as_contract(&dex.address, || {
+        env.storage().persistent().get_ttl(&DataKey::LpBalance(user.clone()))
+    })
+}
+
+fn instance_ttl(env: &Env, dex: &StellarDexClient) -> u32 {
+    env.as_contract(&dex.address, || env.storage().instance().get_ttl())
+}
+
+#[test]
+fn test_liquidity_extends_instance_and_lp_ttl() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    usdc_s.mint(&admin, &100_i128);
+    dex.add_liquidity(&admin, &0_i128, &100_i128, &0_i128);
+
+    assert_eq!(lp_ttl(&env, &dex, &admin), DEFAULT_TTL_EXTEND_TO);
+    assert_eq!(instance_ttl(&env, &dex), DEFAULT_TTL_EXTEND_TO);
+}
+
+#[test]
+fn test_bump_keeps_lp_balance_alive_past_expiry() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    usdc_s.mint(&admin, &100_i128);
+    let lp = dex.add_liquidity(&admin, &0_i128, &100_i128, &0_i128);
+
+    // Shortly before the entries would archive, anyone bumps them
+    env.ledger().with_mut(|li| li.sequence_number += DEFAULT_TTL_EXTEND_TO - 10);
+    assert_eq!(lp_ttl(&env, &dex, &admin), 10);
+    dex.bump(&admin);
+    assert_eq!(lp_ttl(&env, &dex, &admin), DEFAULT_TTL_EXTEND_TO);
+    assert_eq!(instance_ttl(&env, &dex), DEFAULT_TTL_EXTEND_TO);
+
+    // Well past the original expiry, the entries are still live
+    env.ledger().with_mut(|li| li.sequence_number += DEFAULT_TTL_EXTEND_TO - 10);
+    assert_eq!(lp_ttl(&env, &dex, &admin), 10);
+    assert_eq!(dex.lp_balance(&admin), lp);
+}
+
+#[test]
+fn test_bump_without_lp_position_only_extends_instance() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin    = Address::generate(&env);
+    let stranger = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.bump(&stranger);
+    assert_eq!(instance_ttl(&env, &dex), DEFAULT_TTL_EXTEND_TO);
+    assert_eq!(dex.lp_balance(&stranger), 0);
+}
+
+#[test]
+fn test_custom_ttl_config() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let threshold = DEFAULT_TTL_EXTEND_TO;
+    let extend_to = DEFAULT_TTL_EXTEND_TO * 2;
+    dex.set_ttl_config(&threshold, &extend_to);
+    assert_eq!(dex.ttl_config(), TtlConfig { threshold, extend_to });
+
+    usdc_s.mint(&admin, &100_i128);
+    dex.add_liquidity(&admin, &0_i128, &100_i128, &0_i128);
+    assert_eq!(lp_ttl(&env, &dex, &admin), extend_to);
+
+    // Above the threshold nothing is re-extended
+    env.ledger().with_mut(|li| li.sequence_number += 500);
+    dex.bump(&admin);
+    assert_eq!(lp_ttl(&env, &dex, &admin), extend_to - 500);
+}
+
+#[test]
+fn test_bad_ttl_config_rejected() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    assert_eq!(
+        dex.try_set_ttl_config(&200, &100),
+        Err(Ok(DexError::BadTtl.into()))
+    );
+    assert_eq!(
+        dex.try_set_ttl_config(&100, &u32::MAX),
+        Err(Ok(DexError::BadTtl.into()))
+    );
+}
+
+// ─────────────────────────────────────────────
+//  16. SCHEMA MIGRATIONS
+// ─────────────────────────────────────────────
+
+/// Rewind storage to the pre-versioning (v0) layout of a paused pool.
+fn make_legacy_paused(env: &Env, dex: &StellarDexClient) {
+    env.as_contract(&dex.address, || {
+        env.storage().instance().remove(&DataKey::SchemaVersion);
+        env.storage().instance().remove(&DataKey::WithdrawBlockedAt);
+        env.storage().instance().set(&DataKey::Paused, &true);
+    });
+}
+
+#[test]
+fn test_initialize_writes_current_version() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    assert_eq!(dex.version(), SCHEMA_VERSION);
+    assert_eq!(dex.try_migrate(), Err(Ok(DexError::BadVersion.into())));
+}
+
+#[test]
+fn test_migrate_from_legacy_layout_runs_once() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    usdc_s.mint(&admin, &1_000_i128);
+    let lp = dex.add_liquidity(&admin, &0_i128, &1_000_i128, &0_i128);
+
+    env.ledger().set_timestamp(5_000);
+    make_legacy_paused(&env, &dex);
+    assert_eq!(dex.version(), 0);
+    assert_eq!(dex.withdrawals_forced_open_at(), MAX_PAUSE_SECS);
+
+    // Upgrade: the v0 → v1 step starts the withdrawal clock
+    dex.migrate();
+    assert_eq!(dex.version(), SCHEMA_VERSION);
+    assert_eq!(dex.withdrawals_forced_open_at(), 5_000 + MAX_PAUSE_SECS);
+
+    // Existing state survives the migration and it can't run twice
+    assert_eq!(dex.lp_balance(&admin), lp);
+    assert_eq!(dex.try_migrate(), Err(Ok(DexError::BadVersion.into())));
+}
+
+#[test]
+fn test_migrate_rejects_newer_layout() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    env.as_contract(&dex.address, || {
+        env.storage().instance().set(&DataKey::SchemaVersion, &(SCHEMA_VERSION + 1));
+    });
+    assert_eq!(dex.try_migrate(), Err(Ok(DexError::BadVersion.into())));
+}
+
+// ─────────────────────────────────────────────
+//  17. UPGRADE ALLOWLIST & ROLLBACK
+// ─────────────────────────────────────────────
+
+/// Smallest module the host accepts as contract code: no functions, just
+/// the `contractenvmetav0` section declaring the interface version.
+const STUB_WASM: [u8; 40] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x00, 0x1e, 0x11, 0x63, 0x6f, 0x6e,
+    0x74, 0x72, 0x61, 0x63, 0x74, 0x65, 0x6e, 0x76, 0x6d, 0x65, 0x74, 0x61, 0x76, 0x30,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x19, 0x00, 0x00, 0x00, 0x00,
+];
+
+fn upload_stub(env: &Env) -> BytesN<32> {
+    env.deployer().upload_contract_wasm(Bytes::from_array(env, &STUB_WASM))
+}
+
+#[test]
+fn test_upgrade_requires_allowlisted_hash() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let hash = upload_stub(&env);
+    assert_eq!(
+        dex.try_upgrade(&admin, &hash),
+        Err(Ok(DexError::WasmNotAllowed.into()))
+    );
+
+    dex.allow_wasm(&hash, &true);
+    assert!(dex.is_wasm_allowed(&hash));
+    dex.allow_wasm(&hash, &false);
+    assert!(!dex.is_wasm_allowed(&hash));
+}
+
+#[test]
+fn test_upgrade_records_history() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let original = BytesN::from_array(&env, &[7; 32]);
+    dex.record_current_wasm(&original);
+    assert_eq!(dex.wasm_history(), vec![&env, original.clone()]);
+
+    let hash = upload_stub(&env);
+    dex.allow_wasm(&hash, &true);
+    dex.upgrade(&admin, &hash);
+
+    // The stub now runs, so read the history straight from storage
+    let history: Vec<BytesN<32>> = env.as_contract(&dex.address, || {
+        env.storage().instance().get(&DataKey::WasmHistory).unwrap()
+    });
+    assert_eq!(history, vec![&env, original, hash]);
+}
+
+#[test]
+fn test_rollback_restores_previous_hash() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    assert_eq!(dex.try_rollback(&admin), Err(Ok(DexError::NoRollback.into())));
+
+    // Pretend a bad release went out on top of the stub
+    let good = upload_stub(&env);
+    let bad  = BytesN::from_array(&env, &[9; 32]);
+    dex.allow_wasm(&good, &true);
+    env.as_contract(&dex.address, || {
+        env.storage().instance().set(&DataKey::WasmHistory, &vec![&env, good.clone(), bad]);
+    });
+
+    dex.rollback(&admin);
+    let history: Vec<BytesN<32>> = env.as_contract(&dex.address, || {
+        env.storage().instance().get(&DataKey::WasmHistory).unwrap()
+    });
+    assert_eq!(history, vec![&env, good]);
+}
+
+#[test]
+fn test_rollback_refuses_delisted_hash() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let old = upload_stub(&env);
+    let new = BytesN::from_array(&env, &[9; 32]);
+    env.as_contract(&dex.address, || {
+        env.storage().instance().set(&DataKey::WasmHistory, &vec![&env, old, new]);
+    });
+    assert_eq!(dex.try_rollback(&admin), Err(Ok(DexError::WasmNotAllowed.into())));
+}
+
+#[test]
+#[should_panic]
+fn test_record_current_wasm_only_once() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.record_current_wasm(&BytesN::from_array(&env, &[1; 32]));
+    dex.record_current_wasm(&BytesN::from_array(&env, &[2; 32]));
+}
+
+// ─────────────────────────────────────────────
+//  18. RESERVE RECONCILIATION
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_solvency_reports_donation_surplus() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000, 100, 10);
+
+    // Direct transfer that bypasses add_liquidity
+    usdc_s.mint(&dex.address, &50_i128);
+
+    assert_eq!(dex.solvency(), Solvency {
+        xlm_reserve:  1_000,
+        xlm_balance:  1_000,
+        usdc_reserve: 100,
+        usdc_balance: 150,
+        eth_reserve:  10,
+        eth_balance:  10,
+    });
+}
+
+#[test]
+fn test_sync_credits_donation_to_lps() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 100, 0);
+
+    usdc_s.mint(&dex.address, &50_i128);
+    assert_eq!(dex.sync(), (0, 150, 0));
+    assert_eq!(dex.get_reserves(), (0, 150, 0));
+
+    let (_, usdc_out, _) = dex.remove_liquidity(&admin, &dex.lp_balance(&admin));
+    assert_eq!(usdc_out, 150);
+}
+
+#[test]
+fn test_skim_sends_excess_out() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin    = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000, 100, 10);
+
+    usdc_s.mint(&dex.address, &50_i128);
+    eth_s.mint(&dex.address,  &3_i128);
+
+    assert_eq!(dex.skim(&admin, &treasury), (0, 50, 3));
+    assert_eq!(usdc.balance(&treasury), 50);
+    assert_eq!(eth.balance(&treasury),  3);
+    assert_eq!(dex.get_reserves(), (1_000, 100, 10));
+    assert_eq!(dex.solvency().usdc_balance, 100);
+}
+
+#[test]
+#[should_panic]
+fn test_skim_requires_fee_manager() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin    = Address::generate(&env);
+    let stranger = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.skim(&stranger, &stranger);
+}
+
+// ─────────────────────────────────────────────
+//  19. FAUCET
+// ─────────────────────────────────────────────
+
+fn faucet_config() -> FaucetConfig {
+    FaucetConfig {
+        enabled:           true,
+        usdc_per_claim:    100,
+        eth_per_claim:     1,
+        cooldown_secs:     3_600,
+        daily_usdc_budget: 250,
+        daily_eth_budget:  10,
+    }
+}
+
+#[test]
+fn test_faucet_claim_mints_and_tracks_stats() {
+    let env = Env::default();
+    // The SAC admin authorises the nested `mint` call, not the root one
+    env.mock_all_auths_allowing_non_root_auth();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.set_faucet(&faucet_config());
+
+    assert_eq!(dex.claim_faucet(&user, &usdc.address), 100);
+    assert_eq!(dex.claim_faucet(&user, &eth.address), 1);
+
+    assert_eq!(usdc.balance(&user), 100);
+    assert_eq!(eth.balance(&user),  1);
+    assert_eq!(dex.mint_stats(), (100, 1));
+}
+
+#[test]
+fn test_faucet_cooldown_per_address() {
+    let env = Env::default();
+    env.mock_all_auths_allowing_non_root_auth();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let other = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.set_faucet(&FaucetConfig { daily_usdc_budget: 1_000, ..faucet_config() });
+
+    env.ledger().set_timestamp(10_000);
+    dex.claim_faucet(&user, &usdc.address);
+    assert_eq!(
+        dex.try_claim_faucet(&user, &usdc.address),
+        Err(Ok(DexError::FaucetCooldown.into()))
+    );
+    // Other addresses and other tokens are unaffected
+    dex.claim_faucet(&other, &usdc.address);
+    dex.claim_faucet(&user, &eth.address);
+
+    env.ledger().set_timestamp(10_000 + 3_600);
+    dex.claim_faucet(&user, &usdc.address);
+    assert_eq!(usdc.balance(&user), 200);
+}
+
+#[test]
+fn test_faucet_daily_budget() {
+    let env = Env::default();
+    env.mock_all_auths_allowing_non_root_auth();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.set_faucet(&faucet_config());
+
+    env.ledger().set_timestamp(86_400 * 3);
+    dex.claim_faucet(&Address::generate(&env), &usdc.address);
+    dex.claim_faucet(&Address::generate(&env), &usdc.address);
+    assert_eq!(
+        dex.try_claim_faucet(&Address::generate(&env), &usdc.address),
+        Err(Ok(DexError::FaucetExhausted.into()))
+    );
+
+    // Budget resets with the next day
+    env.ledger().set_timestamp(86_400 * 4);
+    dex.claim_faucet(&Address::generate(&env), &usdc.address);
+}
+
+#[test]
+fn test_faucet_disabled_and_bad_token() {
+    let env = Env::default();
+    env.mock_all_auths_allowing_non_root_auth();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let (other, _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    assert_eq!(
+        dex.try_claim_faucet(&user, &usdc.address),
+        Err(Ok(DexError::FaucetDisabled.into()))
+    );
+
+    dex.set_faucet(&FaucetConfig { enabled: false, ..faucet_config() });
+    assert_eq!(
+        dex.try_claim_faucet(&user, &usdc.address),
+        Err(Ok(DexError::FaucetDisabled.into()))
+    );
+
+    dex.set_faucet(&faucet_config());
+    assert_eq!(
+        dex.try_claim_faucet(&user, &other.address),
+        Err(Ok(DexError::BadToken.into()))
+    );
+}
+
+#[test]
+fn test_faucet_cooldown_outlives_fast_ledgers() {
+    let env = Env::default();
+    env.mock_all_auths_allowing_non_root_auth();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.set_faucet(&faucet_config());
+
+    env.ledger().set_timestamp(10_000);
+    dex.claim_faucet(&user, &usdc.address);
+
+    // Many more ledgers than 3_600s / 5s close, but only half the cooldown
+    env.ledger().set_sequence_number(env.ledger().sequence() + 10_000);
+    env.ledger().set_timestamp(10_000 + 1_800);
+    assert_eq!(
+        dex.try_claim_faucet(&user, &usdc.address),
+        Err(Ok(DexError::FaucetCooldown.into()))
+    );
+}
+
+#[test]
+fn test_faucet_config_is_validated() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    for bad in [
+        FaucetConfig { cooldown_secs: 0, ..faucet_config() },
+        FaucetConfig { cooldown_secs: MAX_FAUCET_COOLDOWN_SECS + 1, ..faucet_config() },
+        FaucetConfig { cooldown_secs: u64::MAX, ..faucet_config() },
+        FaucetConfig { usdc_per_claim: -1, ..faucet_config() },
+        FaucetConfig { daily_eth_budget: -1, ..faucet_config() },
+    ] {
+        assert_eq!(dex.try_set_faucet(&bad), Err(Ok(DexError::BadFaucet.into())));
+    }
+    dex.set_faucet(&FaucetConfig { cooldown_secs: MAX_FAUCET_COOLDOWN_SECS, ..faucet_config() });
+}
+
+// ─────────────────────────────────────────────
+//  20. SUPPLY CAPS, MINTER QUOTAS & BURNING
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_supply_cap_bounds_minting() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.set_supply_cap(&usdc.address, &Some(1_000_i128));
+    assert_eq!(dex.supply_cap(&usdc.address), Some(1_000));
+
+    dex.mint_usdc(&admin, &user, &600_i128);
+    assert_eq!(
+        dex.try_mint_usdc(&admin, &user, &401_i128),
+        Err(Ok(DexError::MintCapExceeded.into()))
+    );
+    dex.mint_usdc(&admin, &user, &400_i128);
+
+    // ETH is uncapped
+    dex.mint_eth(&admin, &user, &5_000_i128);
+
+    dex.set_supply_cap(&usdc.address, &None);
+    dex.mint_usdc(&admin, &user, &1_i128);
+}
+
+#[test]
+fn test_minter_quota_rolls_over() {
+    let env = Env::default();
+    env.mock_all_auths_allowing_non_root_auth();
+    let admin  = Address::generate(&env);
+    let minter = Address::generate(&env);
+    let user   = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.grant_role(&Role::Minter, &minter);
+    let quota = MinterQuota { limit: 500, window_secs: 86_400 };
+    dex.set_minter_quota(&minter, &usdc.address, &Some(quota.clone()));
+    assert_eq!(dex.minter_quota(&minter, &usdc.address), Some(quota));
+
+    env.ledger().set_timestamp(1_000);
+    dex.mint_usdc(&minter, &user, &300_i128);
+    env.ledger().set_timestamp(50_000);
+    dex.mint_usdc(&minter, &user, &200_i128);
+    assert_eq!(dex.minter_usage(&minter, &usdc.address), 500);
+    assert_eq!(
+        dex.try_mint_usdc(&minter, &user, &1_i128),
+        Err(Ok(DexError::MintCapExceeded.into()))
+    );
+
+    // The admin is not bound by a minter's quota
+    dex.mint_usdc(&admin, &user, &1_i128);
+
+    // Each mint keeps counting for a full window after it was made
+    env.ledger().set_timestamp(1_000 + 86_400 + 10_800);
+    assert_eq!(dex.minter_usage(&minter, &usdc.address), 200);
+    dex.mint_usdc(&minter, &user, &300_i128);
+    env.ledger().set_timestamp(50_000 + 86_400 + 10_800);
+    dex.mint_usdc(&minter, &user, &200_i128);
+    assert_eq!(usdc.balance(&user), 1_001);
+}
+
+#[test]
+fn test_minter_quota_slides_across_window_boundary() {
+    let env = Env::default();
+    env.mock_all_auths_allowing_non_root_auth();
+    let admin  = Address::generate(&env);
+    let minter = Address::generate(&env);
+    let user   = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.grant_role(&Role::Minter, &minter);
+    dex.set_minter_quota(&minter, &usdc.address, &Some(MinterQuota { limit: 500, window_secs: 86_400 }));
+
+    // Use the whole quota just before a day boundary...
+    env.ledger().set_timestamp(86_400 - 10);
+    dex.mint_usdc(&minter, &user, &500_i128);
+
+    // ...and it is still used up just after it
+    env.ledger().set_timestamp(86_400 + 10);
+    assert_eq!(
+        dex.try_mint_usdc(&minter, &user, &1_i128),
+        Err(Ok(DexError::MintCapExceeded.into()))
+    );
+    env.ledger().set_timestamp(2 * 86_400 - 11);
+    assert_eq!(
+        dex.try_mint_usdc(&minter, &user, &1_i128),
+        Err(Ok(DexError::MintCapExceeded.into()))
+    );
+
+    // Freed once the mint's bucket has left the window
+    env.ledger().set_timestamp(2 * 86_400 + 10_800);
+    dex.mint_usdc(&minter, &user, &500_i128);
+    assert_eq!(usdc.balance(&user), 1_000);
+}
+
+#[test]
+fn test_minter_quota_is_validated() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin  = Address::generate(&env);
+    let minter = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    for quota in [
+        MinterQuota { limit: -1,  window_secs: 86_400 },
+        MinterQuota { limit: 500, window_secs: 0 },
+        MinterQuota { limit: 500, window_secs: QUOTA_BUCKETS - 1 },
+    ] {
+        assert_eq!(
+            dex.try_set_minter_quota(&minter, &usdc.address, &Some(quota)),
+            Err(Ok(DexError::ZeroAmount.into()))
+        );
+    }
+    assert_eq!(dex.minter_quota(&minter, &usdc.address), None);
+
+    // The shortest window gives each bucket one second
+    let quota = MinterQuota { limit: 0, window_secs: QUOTA_BUCKETS };
+    dex.set_minter_quota(&minter, &usdc.address, &Some(quota.clone()));
+    assert_eq!(dex.minter_quota(&minter, &usdc.address), Some(quota));
+}
+
+#[test]
+fn test_burn_decrements_minted_totals() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.set_supply_cap(&usdc.address, &Some(1_000_i128));
+    dex.mint_usdc(&admin, &user, &1_000_i128);
+    dex.mint_eth(&admin, &user, &10_i128);
+
+    dex.burn_usdc(&user, &400_i128);
+    dex.burn_eth(&user, &4_i128);
+    assert_eq!(dex.mint_stats(), (600, 6));
+    assert_eq!(usdc.balance(&user), 600);
+    assert_eq!(eth.balance(&user),  6);
+
+    // Burned supply frees room under the cap
+    dex.mint_usdc(&admin, &user, &400_i128);
+}
+
+#[test]
+#[should_panic]
+fn test_burn_more_than_balance_panics() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let user  = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.mint_usdc(&admin, &user, &10_i128);
+    dex.burn_usdc(&user, &11_i128);
+}
+
+// ─────────────────────────────────────────────
+//  21. FLASH LOANS
+// ─────────────────────────────────────────────
+
+/// Borrower that returns the principal but keeps the fee.
+#[contract]
+struct StingyReceiver;
+
+#[contractimpl]
+impl StingyReceiver {
+    pub fn on_flash_loan(
+        env: Env,
+        lender: Address,
+        _initiator: Address,
+        token: Address,
+        amount: i128,
+        _fee: i128,
+        _data: Bytes,
+    ) {
+        token::Client::new(&env, &token).transfer(&env.current_contract_address(), &lender, &amount);
+    }
+}
+
+/// Register the sample receiver, owned by `owner` and bound to `dex`.
+fn create_receiver(env: &Env, owner: &Address, dex: &StellarDexClient) -> Address {
+    let receiver = env.register(flash_receiver::FlashReceiver, ());
+    flash_receiver::FlashReceiverClient::new(env, &receiver).initialize(owner, &dex.address);
+    receiver
+}
+
+#[test]
+fn test_flash_loan_fee_accrues_to_lps() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 100_000, 0);
+
+    let owner    = Address::generate(&env);
+    let receiver = create_receiver(&env, &owner, &dex);
+    usdc_s.mint(&receiver, &100_i128);
+
+    assert_eq!(dex.flash_fee_bps(), DEFAULT_FLASH_FEE_BPS);
+    // 50_000 * 9 / 10_000 = 45
+    let fee = dex.flash_loan(&owner, &receiver, &usdc.address, &50_000_i128, &Bytes::new(&env));
+    assert_eq!(fee, 45);
+    assert_eq!(usdc.balance(&receiver), 55);
+    assert_eq!(dex.get_reserves(), (0, 100_045, 0));
+
+    let (_, usdc_out, _) = dex.remove_liquidity(&admin, &dex.lp_balance(&admin));
+    assert_eq!(usdc_out, 100_045);
+}
+
+#[test]
+fn test_flash_loan_fee_rounds_up_and_is_configurable() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 0, 50);
+
+    let owner    = Address::generate(&env);
+    let receiver = create_receiver(&env, &owner, &dex);
+    eth_s.mint(&receiver, &10_i128);
+
+    // Small loans still pay at least one unit
+    assert_eq!(dex.flash_loan(&owner, &receiver, &eth.address, &10_i128, &Bytes::new(&env)), 1);
+
+    dex.set_flash_fee(&admin, &100);
+    assert_eq!(dex.flash_loan(&owner, &receiver, &eth.address, &50_i128, &Bytes::new(&env)), 1);
+    dex.set_flash_fee(&admin, &1_000);
+    assert_eq!(dex.flash_loan(&owner, &receiver, &eth.address, &50_i128, &Bytes::new(&env)), 5);
+    assert_eq!(dex.get_reserves(), (0, 0, 57));
+
+    assert_eq!(dex.try_set_flash_fee(&admin, &10_001), Err(Ok(DexError::BadFee.into())));
+}
+
+#[test]
+fn test_flash_loan_must_repay_fee() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 100_000, 0);
+
+    let receiver = env.register(StingyReceiver, ());
+    usdc_s.mint(&receiver, &100_i128);
+
+    assert_eq!(
+        dex.try_flash_loan(&admin, &receiver, &usdc.address, &50_000_i128, &Bytes::new(&env)),
+        Err(Ok(DexError::FlashNotRepaid.into()))
+    );
+    assert_eq!(usdc.balance(&dex.address), 100_000);
+}
+
+#[test]
+fn test_flash_loan_bounds() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let (other, _)     = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 1_000, 0);
+
+    let receiver = create_receiver(&env, &admin, &dex);
+    assert_eq!(
+        dex.try_flash_loan(&admin, &receiver, &usdc.address, &1_001_i128, &Bytes::new(&env)),
+        Err(Ok(DexError::InsufficientFunds.into()))
+    );
+    assert_eq!(
+        dex.try_flash_loan(&admin, &receiver, &other.address, &1_i128, &Bytes::new(&env)),
+        Err(Ok(DexError::BadToken.into()))
+    );
+}
+
+#[test]
+fn test_flash_receiver_only_repays_its_owners_loans() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 100_000, 0);
+
+    let owner    = Address::generate(&env);
+    let receiver = create_receiver(&env, &owner, &dex);
+    let client   = flash_receiver::FlashReceiverClient::new(&env, &receiver);
+    usdc_s.mint(&receiver, &100_i128);
+
+    // Calling the callback directly, or through a loan someone else
+    // started, moves nothing
+    let attacker = Address::generate(&env);
+    assert_eq!(
+        client.try_on_flash_loan(&attacker, &owner, &usdc.address, &0_i128, &100_i128, &Bytes::new(&env)),
+        Err(Ok(flash_receiver::ReceiverError::UnknownLender.into()))
+    );
+    assert_eq!(
+        dex.try_flash_loan(&attacker, &receiver, &usdc.address, &50_000_i128, &Bytes::new(&env)),
+        Err(Ok(flash_receiver::ReceiverError::NotOwner.into()))
+    );
+    assert_eq!(
+        client.try_initialize(&attacker, &attacker),
+        Err(Ok(flash_receiver::ReceiverError::AlreadyInit.into()))
+    );
+    assert_eq!(usdc.balance(&receiver), 100);
+
+    // The pool authenticates itself to the receiver as the direct caller,
+    // so only the owner's signature is needed
+    dex.flash_loan(&owner, &receiver, &usdc.address, &50_000_i128, &Bytes::new(&env));
+    let signers: std::vec::Vec<Address> = env.auths().into_iter().map(|(who, _)| who).collect();
+    assert_eq!(signers, std::vec![owner]);
+    assert_eq!(usdc.balance(&receiver), 55);
+}
+
+// ─────────────────────────────────────────────
+//  22. FLASH SWAPS
+// ─────────────────────────────────────────────
+
+/// Flash-swap recipient that pays one unit less than quoted.
+#[contract]
+struct ShortPayer;
+
+#[contractimpl]
+impl ShortPayer {
+    #[allow(clippy::too_many_arguments)]
+    pub fn on_flash_swap(
+        env: Env,
+        pool: Address,
+        _initiator: Address,
+        token_in: Address,
+        amount_in: i128,
+        _token_out: Address,
+        _amount_out: i128,
+        _data: Bytes,
+    ) {
+        token::Client::new(&env, &token_in)
+            .transfer(&env.current_contract_address(), &pool, &(amount_in - 1));
+    }
+}
+
+#[test]
+fn test_flash_swap_xlm_for_usdc_pays_minimal_quote() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 1_000, 0);
+
+    let owner    = Address::generate(&env);
+    let receiver = create_receiver(&env, &owner, &dex);
+    xlm_s.mint(&receiver, &10_000_000_000_i128);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+
+    let paid = dex.flash_swap(&owner, &receiver, &xlm_s.address, &usdc.address, &10_i128, &Bytes::new(&env));
+
+    assert_eq!(usdc.balance(&receiver), 10);
+    assert_eq!(xlm.balance(&receiver), 10_000_000_000 - paid);
+    // The charged input is the cheapest one the regular swap would accept
+    assert!(dex.quote_xlm_to_usdc(&paid) >= 10);
+    assert!(dex.quote_xlm_to_usdc(&(paid - 1)) < 10);
+    assert_eq!(dex.get_reserves(), (10_000_000_000 + paid, 990, 0));
+}
+
+#[test]
+fn test_flash_swap_cross_pair() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 0, 100);
+
+    let owner    = Address::generate(&env);
+    let receiver = create_receiver(&env, &owner, &dex);
+    usdc_s.mint(&receiver, &100_000_i128);
+
+    let paid = dex.flash_swap(&owner, &receiver, &usdc.address, &eth.address, &2_i128, &Bytes::new(&env));
+    assert_eq!(eth.balance(&receiver), 2);
+
+    // Same price as swap_usdc_for_eth
+    let user = Address::generate(&env);
+    usdc_s.mint(&user, &paid);
+    assert_eq!(dex.swap_usdc_for_eth(&user, &paid, &0_i128, &None), 2);
+    usdc_s.mint(&user, &(paid - 1));
+    assert!(dex.try_swap_usdc_for_eth(&user, &(paid - 1), &2_i128, &None).is_err());
+}
+
+#[test]
+fn test_flash_swap_rejects_underpayment() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000, 0, 10);
+
+    let receiver = env.register(ShortPayer, ());
+    eth_s.mint(&receiver, &10_i128);
+
+    assert_eq!(
+        dex.try_flash_swap(&admin, &receiver, &eth.address, &xlm_s.address, &ETH_RATE, &Bytes::new(&env)),
+        Err(Ok(DexError::FlashNotRepaid.into()))
+    );
+    assert_eq!(dex.get_reserves(), (1_000_000_000_000, 0, 10));
+}
+
+#[test]
+fn test_flash_swap_respects_pauses_and_reserves() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 100, 0);
+
+    let receiver = create_receiver(&env, &admin, &dex);
+    assert_eq!(
+        dex.try_flash_swap(&admin, &receiver, &xlm_s.address, &usdc.address, &101_i128, &Bytes::new(&env)),
+        Err(Ok(DexError::InsufficientFunds.into()))
+    );
+    assert_eq!(
+        dex.try_flash_swap(&admin, &receiver, &usdc.address, &usdc.address, &1_i128, &Bytes::new(&env)),
+        Err(Ok(DexError::BadToken.into()))
+    );
+
+    dex.set_op_paused(&admin, &PauseFlag::Swaps, &true);
+    assert_eq!(
+        dex.try_flash_swap(&admin, &receiver, &xlm_s.address, &usdc.address, &1_i128, &Bytes::new(&env)),
+        Err(Ok(DexError::OperationPaused.into()))
+    );
+}
+
+#[test]
+fn test_flash_receiver_only_pays_for_its_owners_swaps() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 0, 100);
+
+    let owner    = Address::generate(&env);
+    let receiver = create_receiver(&env, &owner, &dex);
+    let client   = flash_receiver::FlashReceiverClient::new(&env, &receiver);
+    usdc_s.mint(&receiver, &100_000_i128);
+
+    let attacker = Address::generate(&env);
+    assert_eq!(
+        client.try_on_flash_swap(
+            &attacker, &owner, &usdc.address, &100_000_i128, &eth.address, &0_i128, &Bytes::new(&env),
+        ),
+        Err(Ok(flash_receiver::ReceiverError::UnknownLender.into()))
+    );
+    assert_eq!(
+        dex.try_flash_swap(&attacker, &receiver, &usdc.address, &eth.address, &2_i128, &Bytes::new(&env)),
+        Err(Ok(flash_receiver::ReceiverError::NotOwner.into()))
+    );
+    assert_eq!(usdc.balance(&receiver), 100_000);
+
+    dex.flash_swap(&owner, &receiver, &usdc.address, &eth.address, &2_i128, &Bytes::new(&env));
+    let signers: std::vec::Vec<Address> = env.auths().into_iter().map(|(who, _)| who).collect();
+    assert_eq!(signers, std::vec![owner]);
+    assert_eq!(eth.balance(&receiver), 2);
+}
+
+// ─────────────────────────────────────────────
+//  23. GENERIC SWAP
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_generic_swap_matches_dedicated_swaps() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 1_000, 100);
+
+    let user = Address::generate(&env);
+    xlm_s.mint(&user, &1_000_000_000_i128);
+    usdc_s.mint(&user, &20_000_i128);
+
+    assert_eq!(dex.quote(&xlm_s.address, &usdc.address, &1_000_000_000_i128), dex.quote_xlm_to_usdc(&1_000_000_000_i128));
+    let out = dex.swap(&user, &xlm_s.address, &usdc.address, &1_000_000_000_i128, &0_i128, &None);
+    assert_eq!(out, dex.quote_xlm_to_usdc(&1_000_000_000_i128));
+
+    let via_generic = dex.swap(&user, &usdc.address, &eth.address, &10_000_i128, &0_i128, &None);
+    let via_named   = dex.swap_usdc_for_eth(&user, &10_000_i128, &0_i128, &None);
+    assert_eq!(via_generic, via_named);
+    assert_eq!(eth.balance(&user), via_generic + via_named);
+}
+
+#[test]
+fn test_generic_swap_guards() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 10, 0);
+
+    let user = Address::generate(&env);
+    xlm_s.mint(&user, &10_000_000_000_i128);
+    let stranger = Address::generate(&env);
+
+    assert_eq!(
+        dex.try_swap(&user, &xlm_s.address, &stranger, &1_000_i128, &0_i128, &None),
+        Err(Ok(DexError::BadToken.into()))
+    );
+    assert_eq!(
+        dex.try_swap(&user, &xlm_s.address, &usdc.address, &1_000_000_000_i128, &1_000_i128, &None),
+        Err(Ok(DexError::SlippageExceeded.into()))
+    );
+    assert_eq!(
+        dex.try_swap(&user, &xlm_s.address, &usdc.address, &10_000_000_000_i128, &0_i128, &None),
+        Err(Ok(DexError::InsufficientFunds.into()))
+    );
+}
+
+// ─────────────────────────────────────────────
+//  24. SWAP BATCH
+// ─────────────────────────────────────────────
+
+fn leg(token_in: &Address, token_out: &Address, amount_in: i128, min_out: i128) -> SwapLeg {
+    SwapLeg { token_in: token_in.clone(), token_out: token_out.clone(), amount_in, min_out }
+}
+
+#[test]
+fn test_swap_batch_executes_legs_under_one_auth() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 1_000, 100);
+
+    let treasury = Address::generate(&env);
+    xlm_s.mint(&treasury, &2_000_000_000_000_i128);
+    let to_usdc = dex.quote_xlm_to_usdc(&1_000_000_000_i128);
+    let to_eth  = dex.quote_xlm_to_eth(&1_000_000_000_000_i128);
+
+    let outs = dex.swap_batch(&treasury, &vec![
+        &env,
+        leg(&xlm_s.address, &usdc.address, 1_000_000_000, to_usdc),
+        leg(&xlm_s.address, &eth.address,  1_000_000_000_000, to_eth),
+    ], &None);
+
+    // The trader signed once for the whole batch
+    assert_eq!(env.auths().len(), 1);
+    assert_eq!(outs, vec![&env, to_usdc, to_eth]);
+    assert_eq!(usdc.balance(&treasury), to_usdc);
+    assert_eq!(eth.balance(&treasury), to_eth);
+    assert_eq!(dex.get_reserves(), (1_001_000_000_000, 1_000 - to_usdc, 100 - to_eth));
+}
+
+#[test]
+fn test_swap_batch_reverts_if_any_leg_fails() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 1_000, 100);
+
+    let treasury = Address::generate(&env);
+    xlm_s.mint(&treasury, &2_000_000_000_000_i128);
+    let to_eth = dex.quote_xlm_to_eth(&1_000_000_000_000_i128);
+
+    assert_eq!(
+        dex.try_swap_batch(&treasury, &vec![
+            &env,
+            leg(&xlm_s.address, &usdc.address, 1_000_000_000, 0),
+            leg(&xlm_s.address, &eth.address,  1_000_000_000_000, to_eth + 1),
+        ], &None),
+        Err(Ok(DexError::SlippageExceeded.into()))
+    );
+    assert_eq!(usdc.balance(&treasury), 0);
+    assert_eq!(dex.get_reserves(), (0, 1_000, 100));
+
+    assert_eq!(
+        dex.try_swap_batch(&treasury, &Vec::new(&env), &None),
+        Err(Ok(DexError::EmptyBatch.into()))
+    );
+}
+
+// ─────────────────────────────────────────────
+//  25. LIMIT ORDERS
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_place_limit_order_escrows_outside_reserves() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 100_000_000_000, 0, 0);
+
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &1_000_i128);
+    let id = dex.place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &(700_000 * PRICE_SCALE), &1_000_u64);
+
+    assert_eq!(usdc.balance(&trader), 0);
+    assert_eq!(dex.escrowed(&usdc.address), 1_000);
+    assert_eq!(dex.orders_of(&trader), vec![&env, id]);
+    assert_eq!(dex.get_order(&id), LimitOrder {
+        owner: trader.clone(), sell: usdc.address.clone(), buy: xlm_s.address.clone(),
+        amount: 1_000, limit_price: 700_000 * PRICE_SCALE, expiry: 1_000,
+    });
+
+    // Escrow is neither a reserve nor skimmable surplus
+    assert_eq!(dex.sync(), (100_000_000_000, 0, 0));
+    assert_eq!(dex.solvency().usdc_balance, 0);
+    assert_eq!(dex.skim(&admin, &admin), (0, 0, 0));
+}
+
+#[test]
+fn test_fill_orders_once_price_crosses() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 100_000_000_000, 0, 0);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+
+    // At USDC_RATE, 1_000 USDC fetches ~61.7 XLM per unit; ask for 70
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &1_000_i128);
+    let id = dex.place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &(70_000_000 * PRICE_SCALE), &1_000_u64);
+
+    assert_eq!(dex.fill_orders(&vec![&env, id]), Vec::<u64>::new(&env));
+    assert_eq!(dex.escrowed(&usdc.address), 1_000);
+
+    dex.set_usdc_rate(&admin, &71_000_000_i128);
+    let expected = dex.quote_usdc_to_xlm(&1_000_i128);
+    assert_eq!(dex.fill_orders(&vec![&env, id]), vec![&env, id]);
+
+    assert_eq!(xlm.balance(&trader), expected);
+    assert_eq!(dex.escrowed(&usdc.address), 0);
+    assert_eq!(dex.get_reserves(), (100_000_000_000 - expected, 1_000, 0));
+    assert_eq!(dex.orders_of(&trader).len(), 0);
+    assert_eq!(dex.try_get_order(&id), Err(Ok(DexError::NoOrder.into())));
+
+    // A second fill of the same id is a no-op
+    assert_eq!(dex.fill_orders(&vec![&env, id]), Vec::<u64>::new(&env));
+}
+
+#[test]
+fn test_fill_orders_skips_expired_and_cancel_refunds() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 100_000_000_000, 0, 0);
+
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &2_000_i128);
+    let a = dex.place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &PRICE_SCALE, &100_u64);
+    let b = dex.place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &PRICE_SCALE, &100_u64);
+    assert_eq!(dex.orders_of(&trader), vec![&env, a, b]);
+
+    env.ledger().set_timestamp(101);
+    assert_eq!(dex.fill_orders(&vec![&env, a, b]), Vec::<u64>::new(&env));
+
+    // Cancelling still works after expiry and while swaps are paused
+    dex.set_paused(&admin, &true);
+    assert_eq!(dex.cancel_order(&a), 1_000);
+    assert_eq!(usdc.balance(&trader), 1_000);
+    assert_eq!(dex.orders_of(&trader), vec![&env, b]);
+    assert_eq!(dex.escrowed(&usdc.address), 1_000);
+    assert_eq!(dex.try_cancel_order(&a), Err(Ok(DexError::NoOrder.into())));
+}
+
+#[test]
+fn test_fill_orders_skips_paused_pairs() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000, 0, 0);
+
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &1_000_i128);
+    eth_s.mint(&trader, &2_i128);
+    let a = dex.place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &1_i128, &100_u64);
+    let b = dex.place_limit_order(&trader, &eth.address,  &xlm_s.address, &2_i128,     &1_i128, &100_u64);
+
+    // One paused pair does not block the rest of the batch
+    dex.set_pair_paused(&admin, &usdc.address, &xlm_s.address, &true);
+    assert_eq!(dex.fill_orders(&vec![&env, a, b]), vec![&env, b]);
+    assert_eq!(dex.orders_of(&trader), vec![&env, a]);
+
+    dex.set_pair_paused(&admin, &usdc.address, &xlm_s.address, &false);
+    dex.set_asset_paused(&admin, &usdc.address, &true);
+    assert_eq!(dex.fill_orders(&vec![&env, a]), Vec::<u64>::new(&env));
+    dex.set_asset_paused(&admin, &usdc.address, &false);
+    assert_eq!(dex.fill_orders(&vec![&env, a]), vec![&env, a]);
+
+    // A global pause still fails the call outright
+    dex.set_op_paused(&admin, &PauseFlag::Swaps, &true);
+    assert_eq!(dex.try_fill_orders(&vec![&env, a]), Err(Ok(DexError::OperationPaused.into())));
+}
+
+#[test]
+fn test_fill_orders_skips_unreachable_prices() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000, 0, 0);
+
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &2_000_i128);
+    let huge = dex.place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &i128::MAX, &100_u64);
+    let fair = dex.place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &1_i128,     &100_u64);
+
+    // A price too large to compare is skipped instead of aborting the batch
+    assert_eq!(dex.fill_orders(&vec![&env, huge, fair]), vec![&env, fair]);
+    assert_eq!(dex.orders_of(&trader), vec![&env, huge]);
+    assert_eq!(dex.cancel_order(&huge), 1_000);
+}
+
+#[test]
+fn test_cancel_keeps_order_index_alive() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &2_000_i128);
+    let a = dex.place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &PRICE_SCALE, &u64::MAX);
+    let b = dex.place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &PRICE_SCALE, &u64::MAX);
+
+    let index_ttl = || env.as_contract(&dex.address, || {
+        env.storage().persistent().get_ttl(&DataKey::OwnerOrders(trader.clone()))
+    });
+    env.ledger().with_mut(|li| li.sequence_number += DEFAULT_TTL_EXTEND_TO - 10);
+    assert_eq!(index_ttl(), 10);
+
+    // Shrinking the index bumps it just like growing it does
+    dex.cancel_order(&a);
+    assert_eq!(index_ttl(), DEFAULT_TTL_EXTEND_TO);
+    assert_eq!(dex.orders_of(&trader), vec![&env, b]);
+}
+
+#[test]
+fn test_place_limit_order_validation() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    env.ledger().set_timestamp(500);
+
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &1_000_i128);
+    assert_eq!(
+        dex.try_place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &PRICE_SCALE, &500_u64),
+        Err(Ok(DexError::OrderExpired.into()))
+    );
+    assert_eq!(
+        dex.try_place_limit_order(&trader, &usdc.address, &xlm_s.address, &1_000_i128, &0_i128, &600_u64),
+        Err(Ok(DexError::BadPrice.into()))
+    );
+    assert_eq!(
+        dex.try_place_limit_order(&trader, &usdc.address, &usdc.address, &1_000_i128, &PRICE_SCALE, &600_u64),
+        Err(Ok(DexError::BadToken.into()))
+    );
+    assert_eq!(
+        dex.try_place_limit_order(&trader, &usdc.address, &xlm_s.address, &0_i128, &PRICE_SCALE, &600_u64),
+        Err(Ok(DexError::ZeroAmount.into()))
+    );
+}
+
+// ─────────────────────────────────────────────
+//  26. DCA
+// ─────────────────────────────────────────────
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+const HUNDRED_XLM: i128 = 1_000_000_000;
+
+#[test]
+fn test_dca_executes_each_due_period_with_keeper_tip() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 1_000, 0);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+    env.ledger().set_timestamp(1_000);
+
+    let owner = Address::generate(&env);
+    xlm_s.mint(&owner, &(3 * HUNDRED_XLM));
+    let id = dex.create_dca(&owner, &xlm_s.address, &usdc.address, &(3 * HUNDRED_XLM), &HUNDRED_XLM, &DAY_SECS, &0_i128);
+    assert_eq!(dex.dca_plans_of(&owner), vec![&env, id]);
+    assert_eq!(dex.escrowed(&xlm_s.address), 3 * HUNDRED_XLM);
+
+    let tip = HUNDRED_XLM * DEFAULT_DCA_TIP_BPS as i128 / 10_000;
+    let per_buy = dex.quote_xlm_to_usdc(&(HUNDRED_XLM - tip));
+
+    // First period is due immediately
+    let keeper = Address::generate(&env);
+    assert_eq!(dex.execute_dca(&keeper, &id), per_buy);
+    assert_eq!(usdc.balance(&owner), per_buy);
+    assert_eq!(xlm.balance(&keeper), tip);
+    assert_eq!(dex.get_reserves(), (HUNDRED_XLM - tip, 1_000 - per_buy, 0));
+    assert_eq!(dex.escrowed(&xlm_s.address), 2 * HUNDRED_XLM);
+
+    assert_eq!(dex.try_execute_dca(&keeper, &id), Err(Ok(DexError::NotDue.into())));
+    env.ledger().set_timestamp(1_000 + DAY_SECS);
+    dex.execute_dca(&keeper, &id);
+    assert_eq!(dex.get_dca(&id).next_at, 1_000 + 2 * DAY_SECS);
+    assert_eq!(dex.get_dca(&id).remaining, HUNDRED_XLM);
+    assert_eq!(usdc.balance(&owner), 2 * per_buy);
+}
+
+#[test]
+fn test_dca_final_short_period_closes_plan() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 1_000, 0);
+    dex.set_dca_tip(&admin, &0_u32);
+
+    let owner = Address::generate(&env);
+    xlm_s.mint(&owner, &(HUNDRED_XLM + HUNDRED_XLM / 2));
+    let id = dex.create_dca(&owner, &xlm_s.address, &usdc.address, &(HUNDRED_XLM + HUNDRED_XLM / 2), &HUNDRED_XLM, &DAY_SECS, &0_i128);
+
+    let keeper = Address::generate(&env);
+    dex.execute_dca(&keeper, &id);
+    // Several missed periods can be caught up back to back
+    env.ledger().set_timestamp(5 * DAY_SECS);
+    let last = dex.execute_dca(&keeper, &id);
+    assert_eq!(last, dex.quote_xlm_to_usdc(&(HUNDRED_XLM / 2)));
+
+    assert_eq!(dex.try_get_dca(&id), Err(Ok(DexError::NoDca.into())));
+    assert_eq!(dex.dca_plans_of(&owner).len(), 0);
+    assert_eq!(dex.escrowed(&xlm_s.address), 0);
+}
+
+#[test]
+fn test_dca_withdraw_remainder_any_time() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 1_000, 0);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+
+    let owner = Address::generate(&env);
+    xlm_s.mint(&owner, &(3 * HUNDRED_XLM));
+    let id = dex.create_dca(&owner, &xlm_s.address, &usdc.address, &(3 * HUNDRED_XLM), &HUNDRED_XLM, &DAY_SECS, &0_i128);
+    dex.execute_dca(&Address::generate(&env), &id);
+
+    dex.set_paused(&admin, &true);
+    assert_eq!(dex.withdraw_dca(&id), 2 * HUNDRED_XLM);
+    assert_eq!(xlm.balance(&owner), 2 * HUNDRED_XLM);
+    assert_eq!(dex.escrowed(&xlm_s.address), 0);
+    assert_eq!(dex.dca_plans_of(&owner).len(), 0);
+}
+
+#[test]
+fn test_dca_min_out_and_tip_bounds() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 1_000, 0);
+
+    let owner = Address::generate(&env);
+    xlm_s.mint(&owner, &(2 * HUNDRED_XLM));
+    let tip = HUNDRED_XLM * DEFAULT_DCA_TIP_BPS as i128 / 10_000;
+    let min_out = dex.quote_xlm_to_usdc(&(HUNDRED_XLM - tip));
+    let id = dex.create_dca(&owner, &xlm_s.address, &usdc.address, &(2 * HUNDRED_XLM), &HUNDRED_XLM, &DAY_SECS, &min_out);
+
+    // USDC gets pricier: a period now buys less than the owner accepts
+    dex.set_usdc_rate(&admin, &(USDC_RATE * 2));
+    let keeper = Address::generate(&env);
+    assert_eq!(dex.try_execute_dca(&keeper, &id), Err(Ok(DexError::SlippageExceeded.into())));
+    assert_eq!(dex.get_dca(&id), DcaPlan {
+        owner: owner.clone(), sell: xlm_s.address.clone(), buy: usdc.address.clone(),
+        per_period: HUNDRED_XLM, min_out, interval_secs: DAY_SECS, next_at: 0, remaining: 2 * HUNDRED_XLM,
+    });
+
+    assert_eq!(dex.try_set_dca_tip(&admin, &101_u32), Err(Ok(DexError::BadFee.into())));
+    assert_eq!(
+        dex.try_create_dca(&owner, &xlm_s.address, &usdc.address, &HUNDRED_XLM, &HUNDRED_XLM, &0_u64, &0_i128),
+        Err(Ok(DexError::ZeroAmount.into()))
+    );
+}
+
+// ─────────────────────────────────────────────
+//  27. TRIGGER ORDERS
+// ─────────────────────────────────────────────
+
+/// Pool price (XLM per `PRICE_SCALE` ETH, after the fee) of a
+/// 1_000-unit ETH sale at `xlm_per_eth`.
+fn eth_price(xlm_per_eth: i128) -> i128 {
+    997 * xlm_per_eth * PRICE_SCALE / 1_000
+}
+
+#[test]
+fn test_stop_loss_fires_when_rate_falls() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000_000, 0, 0);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+
+    let owner = Address::generate(&env);
+    eth_s.mint(&owner, &1_000_i128);
+    let id = dex.place_trigger_order(
+        &owner, &eth.address, &xlm_s.address, &1_000_i128,
+        &TriggerKind::StopLoss, &eth_price(ETH_RATE * 9 / 10), &eth_price(ETH_RATE * 8 / 10),
+    );
+    assert_eq!(dex.triggers_of(&owner), vec![&env, id]);
+    assert_eq!(dex.escrowed(&eth.address), 1_000);
+
+    assert_eq!(dex.fill_triggers(&vec![&env, id]), Vec::<u64>::new(&env));
+
+    dex.set_eth_rate(&admin, &(ETH_RATE * 85 / 100));
+    let expected = dex.quote_eth_to_xlm(&1_000_i128);
+    assert_eq!(dex.fill_triggers(&vec![&env, id]), vec![&env, id]);
+    assert_eq!(xlm.balance(&owner), expected);
+    assert_eq!(dex.get_reserves(), (1_000_000_000_000_000 - expected, 0, 1_000));
+    assert_eq!(dex.escrowed(&eth.address), 0);
+    assert_eq!(dex.triggers_of(&owner).len(), 0);
+    assert_eq!(dex.try_get_trigger(&id), Err(Ok(DexError::NoTrigger.into())));
+}
+
+#[test]
+fn test_fill_triggers_skips_paused_pairs() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000_000, 0, 0);
+
+    // Both take-profits are already in the money
+    let owner = Address::generate(&env);
+    eth_s.mint(&owner, &1_000_i128);
+    usdc_s.mint(&owner, &1_000_i128);
+    let a = dex.place_trigger_order(
+        &owner, &eth.address, &xlm_s.address, &1_000_i128,
+        &TriggerKind::TakeProfit, &eth_price(ETH_RATE * 9 / 10), &eth_price(ETH_RATE * 9 / 10),
+    );
+    let b = dex.place_trigger_order(
+        &owner, &usdc.address, &xlm_s.address, &1_000_i128,
+        &TriggerKind::TakeProfit, &eth_price(USDC_RATE * 9 / 10), &eth_price(USDC_RATE * 9 / 10),
+    );
+
+    dex.set_pair_paused(&admin, &eth.address, &xlm_s.address, &true);
+    assert_eq!(dex.fill_triggers(&vec![&env, a, b]), vec![&env, b]);
+    assert_eq!(dex.triggers_of(&owner), vec![&env, a]);
+
+    dex.set_pair_paused(&admin, &eth.address, &xlm_s.address, &false);
+    dex.set_op_paused(&admin, &PauseFlag::Swaps, &true);
+    assert_eq!(dex.try_fill_triggers(&vec![&env, a]), Err(Ok(DexError::OperationPaused.into())));
+    dex.set_op_paused(&admin, &PauseFlag::Swaps, &false);
+    assert_eq!(dex.fill_triggers(&vec![&env, a]), vec![&env, a]);
+}
+
+#[test]
+fn test_fill_triggers_skips_unreachable_prices() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000_000, 0, 0);
+
+    let owner = Address::generate(&env);
+    eth_s.mint(&owner, &2_000_i128);
+    let huge = dex.place_trigger_order(
+        &owner, &eth.address, &xlm_s.address, &1_000_i128,
+        &TriggerKind::StopLoss, &i128::MAX, &i128::MAX,
+    );
+    let fair = dex.place_trigger_order(
+        &owner, &eth.address, &xlm_s.address, &1_000_i128,
+        &TriggerKind::TakeProfit, &eth_price(ETH_RATE * 9 / 10), &eth_price(ETH_RATE * 9 / 10),
+    );
+
+    // A price too large to compare is skipped instead of aborting the batch
+    assert_eq!(dex.fill_triggers(&vec![&env, huge, fair]), vec![&env, fair]);
+    assert_eq!(dex.triggers_of(&owner), vec![&env, huge]);
+    assert_eq!(dex.cancel_trigger(&huge), 1_000);
+}
+
+#[test]
+fn test_stop_loss_gap_below_limit_waits() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000_000, 0, 0);
+
+    let owner = Address::generate(&env);
+    eth_s.mint(&owner, &1_000_i128);
+    let id = dex.place_trigger_order(
+        &owner, &eth.address, &xlm_s.address, &1_000_i128,
+        &TriggerKind::StopLoss, &eth_price(ETH_RATE * 9 / 10), &eth_price(ETH_RATE * 8 / 10),
+    );
+
+    // The rate gaps straight past the execution bound: the order stays
+    dex.set_eth_rate(&admin, &(ETH_RATE * 7 / 10));
+    assert_eq!(dex.fill_triggers(&vec![&env, id]), Vec::<u64>::new(&env));
+
+    // ...and fires if it recovers into the band
+    dex.set_eth_rate(&admin, &(ETH_RATE * 85 / 100));
+    assert_eq!(dex.fill_triggers(&vec![&env, id]), vec![&env, id]);
+}
+
+#[test]
+fn test_take_profit_fires_when_rate_rises() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000_000_000, 0, 0);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+
+    let owner = Address::generate(&env);
+    eth_s.mint(&owner, &2_000_i128);
+    let take = dex.place_trigger_order(
+        &owner, &eth.address, &xlm_s.address, &1_000_i128,
+        &TriggerKind::TakeProfit, &eth_price(ETH_RATE * 11 / 10), &eth_price(ETH_RATE),
+    );
+    let stop = dex.place_trigger_order(
+        &owner, &eth.address, &xlm_s.address, &1_000_i128,
+        &TriggerKind::StopLoss, &eth_price(ETH_RATE * 9 / 10), &eth_price(ETH_RATE * 8 / 10),
+    );
+
+    dex.set_eth_rate(&admin, &(ETH_RATE * 12 / 10));
+    assert_eq!(dex.fill_triggers(&vec![&env, take, stop]), vec![&env, take]);
+    assert_eq!(xlm.balance(&owner), dex.quote_eth_to_xlm(&1_000_i128));
+    assert_eq!(dex.triggers_of(&owner), vec![&env, stop]);
+}
+
+#[test]
+fn test_trigger_validation_and_cancel() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _)     = create_token(&env, &admin);
+    let (eth,  eth_s) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let owner = Address::generate(&env);
+    eth_s.mint(&owner, &1_000_i128);
+    assert_eq!(
+        dex.try_place_trigger_order(&owner, &eth.address, &xlm_s.address, &1_000_i128, &TriggerKind::StopLoss, &100_i128, &101_i128),
+        Err(Ok(DexError::BadPrice.into()))
+    );
+
+    let id = dex.place_trigger_order(&owner, &eth.address, &xlm_s.address, &1_000_i128, &TriggerKind::StopLoss, &100_i128, &90_i128);
+    dex.set_paused(&admin, &true);
+    assert_eq!(dex.cancel_trigger(&id), 1_000);
+    assert_eq!(eth.balance(&owner), 1_000);
+    assert_eq!(dex.escrowed(&eth.address), 0);
+    assert_eq!(dex.try_cancel_trigger(&id), Err(Ok(DexError::NoTrigger.into())));
+}
+
+// ─────────────────────────────────────────────
+//  28. TWAP ORDERS
+// ─────────────────────────────────────────────
+
+const XLM_CHUNK: i128 = 1_000_000_000_000;
+
+#[test]
+fn test_twap_executes_chunks_at_ledger_intervals() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 0, 20);
+    env.ledger().set_sequence_number(100);
+
+    // Too big for the ETH reserve in one go
+    let whale = Address::generate(&env);
+    xlm_s.mint(&whale, &(3 * XLM_CHUNK + 2));
+    assert_eq!(
+        dex.try_swap_xlm_for_eth(&whale, &(3 * XLM_CHUNK + 2), &0_i128, &None),
+        Err(Ok(DexError::InsufficientFunds.into()))
+    );
+
+    let id = dex.place_twap(&whale, &xlm_s.address, &eth.address, &(3 * XLM_CHUNK + 2), &3_u32, &10_u32, &0_i128);
+    let per_chunk = dex.quote_xlm_to_eth(&XLM_CHUNK);
+
+    assert_eq!(dex.execute_twap(&id), per_chunk);
+    assert_eq!(dex.try_execute_twap(&id), Err(Ok(DexError::NotDue.into())));
+
+    // LPs top up the ETH side between slices
+    env.ledger().set_sequence_number(110);
+    eth_s.mint(&admin, &10_i128);
+    dex.add_liquidity(&admin, &0_i128, &0_i128, &10_i128);
+    assert_eq!(dex.execute_twap(&id), per_chunk);
+    assert_eq!(dex.get_twap(&id).chunks_left, 1);
+
+    // The last slice also sells the rounding remainder
+    env.ledger().set_sequence_number(120);
+    dex.execute_twap(&id);
+    assert_eq!(eth.balance(&whale), 3 * per_chunk);
+    assert_eq!(xlm_s.balance(&whale), 0);
+    assert_eq!(dex.escrowed(&xlm_s.address), 0);
+    assert_eq!(dex.twaps_of(&whale).len(), 0);
+    assert_eq!(dex.try_get_twap(&id), Err(Ok(DexError::NoTwap.into())));
+}
+
+#[test]
+fn test_twap_chunk_slippage_and_cancel() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 0, 100);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+
+    let whale = Address::generate(&env);
+    xlm_s.mint(&whale, &(4 * XLM_CHUNK));
+    let min_out = dex.quote_xlm_to_eth(&XLM_CHUNK);
+    let id = dex.place_twap(&whale, &xlm_s.address, &eth.address, &(4 * XLM_CHUNK), &4_u32, &1_u32, &min_out);
+    dex.execute_twap(&id);
+    env.ledger().set_sequence_number(env.ledger().sequence() + 1);
+
+    // ETH gets pricier: the next slice would buy too little
+    dex.set_eth_rate(&admin, &(ETH_RATE * 2));
+    assert_eq!(dex.try_execute_twap(&id), Err(Ok(DexError::SlippageExceeded.into())));
+    assert_eq!(dex.get_twap(&id), TwapOrder {
+        owner: whale.clone(), sell: xlm_s.address.clone(), buy: eth.address.clone(),
+        chunk_size: XLM_CHUNK, min_out_per_chunk: min_out, chunks_left: 3,
+        interval_ledgers: 1, next_ledger: env.ledger().sequence(), remaining: 3 * XLM_CHUNK,
+    });
+
+    assert_eq!(dex.cancel_twap(&id), 3 * XLM_CHUNK);
+    assert_eq!(xlm.balance(&whale), 3 * XLM_CHUNK);
+    assert_eq!(dex.escrowed(&xlm_s.address), 0);
+
+    assert_eq!(
+        dex.try_place_twap(&whale, &xlm_s.address, &eth.address, &XLM_CHUNK, &0_u32, &1_u32, &0_i128),
+        Err(Ok(DexError::ZeroAmount.into()))
+    );
+}
+
+#[test]
+fn test_twap_needs_an_interval_and_a_floor() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 0, 100);
+
+    let whale = Address::generate(&env);
+    xlm_s.mint(&whale, &(2 * XLM_CHUNK));
+
+    // Every slice in one ledger would not spread the trade at all
+    assert_eq!(
+        dex.try_place_twap(&whale, &xlm_s.address, &eth.address, &(2 * XLM_CHUNK), &2_u32, &0_u32, &0_i128),
+        Err(Ok(DexError::ZeroAmount.into()))
+    );
+    assert_eq!(
+        dex.try_place_twap(&whale, &xlm_s.address, &eth.address, &(2 * XLM_CHUNK), &2_u32, &1_u32, &-1_i128),
+        Err(Ok(DexError::BadPrice.into()))
+    );
+
+    // The shortest interval still holds the next slice for a ledger
+    let id = dex.place_twap(&whale, &xlm_s.address, &eth.address, &(2 * XLM_CHUNK), &2_u32, &1_u32, &0_i128);
+    dex.execute_twap(&id);
+    assert_eq!(dex.try_execute_twap(&id), Err(Ok(DexError::NotDue.into())));
+    env.ledger().set_sequence_number(env.ledger().sequence() + 1);
+    dex.execute_twap(&id);
+    assert_eq!(dex.escrowed(&xlm_s.address), 0);
+}
+
+// ─────────────────────────────────────────────
+//  29. COMMIT-REVEAL SWAPS
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_commit_reveal_swap_executes_at_reveal_rate() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 0, 0);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+    env.ledger().set_sequence_number(100);
+
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &150);
+    let salt = BytesN::from_array(&env, &[7; 32]);
+    let hash = dex.commitment_hash(&trader, &usdc.address, &xlm_s.address, &100_i128, &0_i128, &salt);
+    let id = dex.commit_swap(&trader, &hash, &usdc.address, &150_i128);
+    assert_eq!(usdc.balance(&trader), 0);
+    assert_eq!(dex.escrowed(&usdc.address), 150);
+
+    // Same ledger as the commit: too early
+    assert_eq!(
+        dex.try_reveal_swap(&id, &xlm_s.address, &100_i128, &0_i128, &salt),
+        Err(Ok(DexError::NotDue.into()))
+    );
+
+    // The rate moves between commit and reveal; the reveal rate applies
+    env.ledger().set_sequence_number(101);
+    dex.set_usdc_rate(&admin, &(USDC_RATE / 2));
+    let expected = dex.quote(&usdc.address, &xlm_s.address, &100_i128);
+    assert_eq!(dex.reveal_swap(&id, &xlm_s.address, &100_i128, &0_i128, &salt), expected);
+    assert_eq!(xlm.balance(&trader), expected);
+    assert_eq!(usdc.balance(&trader), 50);
+    assert_eq!(dex.escrowed(&usdc.address), 0);
+    assert_eq!(dex.try_get_commit(&id), Err(Ok(DexError::NoCommit.into())));
+}
+
+#[test]
+fn test_reveal_must_match_commitment() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 0, 0);
+
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &100);
+    let salt = BytesN::from_array(&env, &[1; 32]);
+    let big  = dex.commitment_hash(&trader, &usdc.address, &xlm_s.address, &200_i128, &0_i128, &salt);
+    let id = dex.commit_swap(&trader, &big, &usdc.address, &100_i128);
+    env.ledger().set_sequence_number(env.ledger().sequence() + 1);
+
+    // Wrong salt, wrong output token, and more than the escrow
+    let other = BytesN::from_array(&env, &[2; 32]);
+    assert_eq!(
+        dex.try_reveal_swap(&id, &xlm_s.address, &200_i128, &0_i128, &other),
+        Err(Ok(DexError::BadReveal.into()))
+    );
+    assert_eq!(
+        dex.try_reveal_swap(&id, &eth.address, &200_i128, &0_i128, &salt),
+        Err(Ok(DexError::BadReveal.into()))
+    );
+    assert_eq!(
+        dex.try_reveal_swap(&id, &xlm_s.address, &200_i128, &0_i128, &salt),
+        Err(Ok(DexError::BadReveal.into()))
+    );
+
+    // A revealed min_out is still enforced
+    let greedy = dex.commitment_hash(&trader, &usdc.address, &xlm_s.address, &100_i128, &i128::MAX, &salt);
+    usdc_s.mint(&trader, &100);
+    let id2 = dex.commit_swap(&trader, &greedy, &usdc.address, &100_i128);
+    env.ledger().set_sequence_number(env.ledger().sequence() + 1);
+    assert_eq!(
+        dex.try_reveal_swap(&id2, &xlm_s.address, &100_i128, &i128::MAX, &salt),
+        Err(Ok(DexError::SlippageExceeded.into()))
+    );
+}
+
+#[test]
+fn test_expired_commit_is_reclaimed() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 0, 0);
+    env.ledger().set_sequence_number(100);
+    assert_eq!(dex.commit_config(), CommitConfig { reveal_delay: 1, reveal_window: DEFAULT_REVEAL_WINDOW });
+    dex.set_commit_config(&3_u32, &10_u32);
+
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &100);
+    let salt = BytesN::from_array(&env, &[3; 32]);
+    let hash = dex.commitment_hash(&trader, &usdc.address, &xlm_s.address, &100_i128, &0_i128, &salt);
+    let id = dex.commit_swap(&trader, &hash, &usdc.address, &100_i128);
+    let commit = dex.get_commit(&id);
+    assert_eq!((commit.committed_at, commit.opens_at, commit.expires_at), (100, 103, 113));
+
+    // Still revealable at the end of the window, so not yet reclaimable
+    env.ledger().set_sequence_number(113);
+    assert_eq!(dex.try_reclaim_commit(&id), Err(Ok(DexError::NotDue.into())));
+
+    env.ledger().set_sequence_number(114);
+    assert_eq!(
+        dex.try_reveal_swap(&id, &xlm_s.address, &100_i128, &0_i128, &salt),
+        Err(Ok(DexError::CommitExpired.into()))
+    );
+    assert_eq!(dex.reclaim_commit(&id), 100);
+    assert_eq!(usdc.balance(&trader), 100);
+    assert_eq!(dex.escrowed(&usdc.address), 0);
+    assert_eq!(dex.try_reclaim_commit(&id), Err(Ok(DexError::NoCommit.into())));
+}
+
+#[test]
+fn test_commit_config_changes_spare_pending_commits() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 10_000_000_000, 0, 0);
+    env.ledger().set_sequence_number(100);
+    dex.set_commit_config(&3_u32, &10_u32);
+
+    let trader = Address::generate(&env);
+    usdc_s.mint(&trader, &200);
+    let salt = BytesN::from_array(&env, &[5; 32]);
+    let hash = dex.commitment_hash(&trader, &usdc.address, &xlm_s.address, &100_i128, &0_i128, &salt);
+    let early = dex.commit_swap(&trader, &hash, &usdc.address, &100_i128);
+    let late  = dex.commit_swap(&trader, &hash, &usdc.address, &100_i128);
+
+    // Shortening the window does not expire the pending commitments...
+    dex.set_commit_config(&0_u32, &1_u32);
+    env.ledger().set_sequence_number(113);
+    dex.reveal_swap(&early, &xlm_s.address, &100_i128, &0_i128, &salt);
+
+    // ...and lengthening it does not hold their escrow longer
+    dex.set_commit_config(&MAX_REVEAL_LEDGERS, &MAX_REVEAL_LEDGERS);
+    env.ledger().set_sequence_number(114);
+    assert_eq!(dex.reclaim_commit(&late), 100);
+
+    assert_eq!(
+        dex.try_set_commit_config(&(MAX_REVEAL_LEDGERS + 1), &10_u32),
+        Err(Ok(DexError::BadCommitConfig.into()))
+    );
+    assert_eq!(
+        dex.try_set_commit_config(&3_u32, &(MAX_REVEAL_LEDGERS + 1)),
+        Err(Ok(DexError::BadCommitConfig.into()))
+    );
+}
+
+// ─────────────────────────────────────────────
+//  30. BATCH AUCTIONS
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_batch_nets_opposite_flows_at_one_price() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000, 100, 0);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+    env.ledger().set_sequence_number(12);
+
+    // Alice alone would drain more XLM than the pool holds
+    let alice = Address::generate(&env);
+    usdc_s.mint(&alice, &100);
+    assert_eq!(
+        dex.try_swap_usdc_for_xlm(&alice, &100_i128, &0_i128, &None),
+        Err(Ok(DexError::InsufficientFunds.into()))
+    );
+    let a = dex.submit_intent(&alice, &usdc.address, &xlm_s.address, &100_i128, &0_i128);
+
+    // Bob sells XLM the other way in the same batch
+    let bob = Address::generate(&env);
+    xlm_s.mint(&bob, &10_000_000_000);
+    let b = dex.submit_intent(&bob, &xlm_s.address, &usdc.address, &10_000_000_000_i128, &0_i128);
+    assert_eq!(dex.get_intent(&b).batch, 15);
+    assert_eq!(dex.batch_intents(&15), vec![&env, a, b]);
+    assert_eq!(dex.escrowed(&usdc.address), 100);
+
+    assert_eq!(dex.try_clear_batch(&15), Err(Ok(DexError::NotDue.into())));
+
+    // Both sides get the rate in effect at clearing
+    env.ledger().set_sequence_number(15);
+    dex.set_usdc_rate(&admin, &(USDC_RATE * 11 / 10));
+    let alice_out = dex.quote(&usdc.address, &xlm_s.address, &100_i128);
+    let bob_out   = dex.quote(&xlm_s.address, &usdc.address, &10_000_000_000_i128);
+    assert_eq!(dex.clear_batch(&15), vec![&env, a, b]);
+    assert_eq!(xlm.balance(&alice), alice_out);
+    assert_eq!(usdc.balance(&bob), bob_out);
+
+    // Only the residual moved the reserves
+    assert_eq!(
+        dex.get_reserves(),
+        (1_000_000_000 + 10_000_000_000 - alice_out, 100 + 100 - bob_out, 0)
+    );
+    assert_eq!(dex.escrowed(&usdc.address), 0);
+    assert_eq!(dex.escrowed(&xlm_s.address), 0);
+    assert_eq!(dex.batch_intents(&15).len(), 0);
+    assert_eq!(dex.try_get_intent(&a), Err(Ok(DexError::NoIntent.into())));
+    assert_eq!(dex.try_clear_batch(&15), Err(Ok(DexError::EmptyBatch.into())));
+}
+
+#[test]
+fn test_batch_refunds_intents_the_reserves_cannot_cover() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 1_000_000_000, 0, 0);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+
+    // Alice wants more XLM than the pool holds; Carol's swap fits
+    let alice = Address::generate(&env);
+    let carol = Address::generate(&env);
+    usdc_s.mint(&alice, &100);
+    usdc_s.mint(&carol, &10);
+    let big   = dex.submit_intent(&alice, &usdc.address, &xlm_s.address, &100_i128, &0_i128);
+    let small = dex.submit_intent(&carol, &usdc.address, &xlm_s.address, &10_i128,  &0_i128);
+    let batch = dex.get_intent(&big).batch;
+    assert!(dex.quote(&usdc.address, &xlm_s.address, &100_i128) > 1_000_000_000);
+
+    // The oversized intent is refunded instead of reverting the batch
+    env.ledger().set_sequence_number(batch);
+    let carol_out = dex.quote(&usdc.address, &xlm_s.address, &10_i128);
+    assert_eq!(dex.clear_batch(&batch), vec![&env, small]);
+    assert_eq!(usdc.balance(&alice), 100);
+    assert_eq!(xlm.balance(&carol), carol_out);
+    assert_eq!(dex.get_reserves(), (1_000_000_000 - carol_out, 10, 0));
+    assert_eq!(dex.escrowed(&usdc.address), 0);
+    assert_eq!(dex.batch_intents(&batch).len(), 0);
+}
+
+#[test]
+fn test_batch_refunds_unmet_min_out_and_cancels() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 100_000, 0);
+    dex.set_auction_ledgers(&10_u32);
+    assert_eq!(dex.auction_ledgers(), 10);
+
+    let trader = Address::generate(&env);
+    xlm_s.mint(&trader, &(3 * XLM_CHUNK));
+    let min_out = dex.quote(&xlm_s.address, &usdc.address, &XLM_CHUNK);
+    let keep   = dex.submit_intent(&trader, &xlm_s.address, &usdc.address, &XLM_CHUNK, &min_out);
+    let greedy = dex.submit_intent(&trader, &xlm_s.address, &usdc.address, &XLM_CHUNK, &(min_out + 1));
+    let pulled = dex.submit_intent(&trader, &xlm_s.address, &usdc.address, &XLM_CHUNK, &0_i128);
+    assert_eq!(dex.get_intent(&pulled), SwapIntent {
+        trader: trader.clone(), token_in: xlm_s.address.clone(), token_out: usdc.address.clone(),
+        amount_in: XLM_CHUNK, min_out: 0, batch: 10,
+    });
+
+    assert_eq!(dex.cancel_intent(&pulled), XLM_CHUNK);
+    assert_eq!(dex.batch_intents(&10), vec![&env, keep, greedy]);
+
+    env.ledger().set_sequence_number(10);
+    assert_eq!(dex.clear_batch(&10), vec![&env, keep]);
+    assert_eq!(usdc.balance(&trader), min_out);
+    assert_eq!(xlm_s.balance(&trader), 2 * XLM_CHUNK);
+    assert_eq!(dex.get_reserves(), (XLM_CHUNK, 100_000 - min_out, 0));
+    assert_eq!(dex.escrowed(&xlm_s.address), 0);
+}
+
+#[test]
+fn test_batch_size_is_capped() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 1_000, 0);
+
+    let trader = Address::generate(&env);
+    xlm_s.mint(&trader, &((MAX_BATCH_INTENTS as i128 + 1) * DEFAULT_MIN_INTENT));
+    for _ in 0..MAX_BATCH_INTENTS {
+        dex.submit_intent(&trader, &xlm_s.address, &usdc.address, &DEFAULT_MIN_INTENT, &0_i128);
+    }
+    assert_eq!(
+        dex.try_submit_intent(&trader, &xlm_s.address, &usdc.address, &DEFAULT_MIN_INTENT, &0_i128),
+        Err(Ok(DexError::BatchFull.into()))
+    );
+
+    // The next batch takes new intents
+    env.ledger().set_sequence_number(env.ledger().sequence() + dex.auction_ledgers());
+    dex.submit_intent(&trader, &xlm_s.address, &usdc.address, &DEFAULT_MIN_INTENT, &0_i128);
+}
+
+#[test]
+fn test_dust_intents_are_rejected() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 1_000, 0);
+    assert_eq!(dex.min_intent(), DEFAULT_MIN_INTENT);
+
+    let trader = Address::generate(&env);
+    xlm_s.mint(&trader, &DEFAULT_MIN_INTENT);
+    usdc_s.mint(&trader, &2_i128);
+    assert_eq!(
+        dex.try_submit_intent(&trader, &xlm_s.address, &usdc.address, &(DEFAULT_MIN_INTENT - 1), &0_i128),
+        Err(Ok(DexError::IntentTooSmall.into()))
+    );
+    // Non-XLM inputs are valued at the current rate: 1 USDC is ~6 XLM
+    assert_eq!(
+        dex.try_submit_intent(&trader, &usdc.address, &xlm_s.address, &1_i128, &0_i128),
+        Err(Ok(DexError::IntentTooSmall.into()))
+    );
+    dex.submit_intent(&trader, &usdc.address, &xlm_s.address, &2_i128, &0_i128);
+
+    dex.set_min_intent(&1_i128);
+    dex.submit_intent(&trader, &xlm_s.address, &usdc.address, &1_i128, &0_i128);
+    assert_eq!(dex.try_set_min_intent(&0_i128), Err(Ok(DexError::ZeroAmount.into())));
+}
+
+#[test]
+fn test_batch_refunds_intents_on_paused_pairs() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 100_000, 100);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+
+    let trader = Address::generate(&env);
+    xlm_s.mint(&trader, &(2 * XLM_CHUNK));
+    let to_usdc = dex.submit_intent(&trader, &xlm_s.address, &usdc.address, &XLM_CHUNK, &0_i128);
+    let to_eth  = dex.submit_intent(&trader, &xlm_s.address, &eth.address,  &XLM_CHUNK, &0_i128);
+    let batch = dex.get_intent(&to_usdc).batch;
+
+    // A pause on one pair refunds its intents without blocking the rest
+    dex.set_pair_paused(&admin, &xlm_s.address, &eth.address, &true);
+    env.ledger().set_sequence_number(batch);
+    let usdc_out = dex.quote(&xlm_s.address, &usdc.address, &XLM_CHUNK);
+    assert_eq!(dex.clear_batch(&batch), vec![&env, to_usdc]);
+    assert_eq!(usdc.balance(&trader), usdc_out);
+    assert_eq!(xlm.balance(&trader), XLM_CHUNK);
+    assert_eq!(dex.get_reserves(), (XLM_CHUNK, 100_000 - usdc_out, 100));
+    assert_eq!(dex.escrowed(&xlm_s.address), 0);
+    assert_eq!(dex.try_get_intent(&to_eth), Err(Ok(DexError::NoIntent.into())));
+}
+
+// ─────────────────────────────────────────────
+//  31. LP STAKING
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_stakers_share_emissions_pro_rata() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let (reward, reward_s) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    env.ledger().set_timestamp(1_000);
+
+    dex.set_reward_token(&reward.address);
+    reward_s.mint(&admin, &10_000);
+    dex.fund_rewards(&10_000_i128);
+    dex.set_reward_rate(&10_i128);
+    assert_eq!(dex.escrowed(&reward.address), 10_000);
+
+    let alice = Address::generate(&env);
+    let bob   = Address::generate(&env);
+    xlm_s.mint(&alice, &100);
+    xlm_s.mint(&bob, &300);
+    dex.add_liquidity(&alice, &100_i128, &0_i128, &0_i128);
+    dex.add_liquidity(&bob, &300_i128, &0_i128, &0_i128);
+
+    // Nothing is emitted before anyone stakes
+    env.ledger().set_timestamp(1_050);
+    dex.stake(&alice, &100_i128);
+    assert_eq!(dex.lp_balance(&alice), 0);
+    assert_eq!(dex.reward_pool().budget, 10_000);
+
+    // Alice earns alone for 10s, then shares 1:3 with Bob for 10s
+    env.ledger().set_timestamp(1_060);
+    dex.stake(&bob, &300_i128);
+    env.ledger().set_timestamp(1_070);
+    assert_eq!(dex.pending_rewards(&alice), 100 + 25);
+    assert_eq!(dex.pending_rewards(&bob), 75);
+
+    assert_eq!(dex.claim_rewards(&alice), 125);
+    assert_eq!(reward.balance(&alice), 125);
+    assert_eq!(dex.pending_rewards(&alice), 0);
+    assert_eq!(dex.stake_of(&bob), Stake { shares: 300, reward_debt: 300, unclaimed: 0 });
+    assert_eq!(dex.reward_pool().budget, 10_000 - 200);
+    assert_eq!(dex.escrowed(&reward.address), 10_000 - 125);
+}
+
+#[test]
+fn test_unstake_and_budget_limits() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let (reward, reward_s) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    let lp = Address::generate(&env);
+    xlm_s.mint(&lp, &1_000);
+    dex.add_liquidity(&lp, &1_000_i128, &0_i128, &0_i128);
+    assert_eq!(dex.try_fund_rewards(&50_i128), Err(Ok(DexError::NoRewardToken.into())));
+
+    dex.set_reward_token(&reward.address);
+    assert_eq!(dex.try_set_reward_token(&usdc.address), Err(Ok(DexError::AlreadyInit.into())));
+    reward_s.mint(&admin, &50);
+    dex.fund_rewards(&50_i128);
+    dex.set_reward_rate(&1_i128);
+    dex.stake(&lp, &500_i128);
+
+    // Staked shares cannot be withdrawn as liquidity
+    assert_eq!(
+        dex.try_remove_liquidity(&lp, &501_i128),
+        Err(Ok(DexError::InsufficientLp.into()))
+    );
+    assert_eq!(
+        dex.try_stake(&lp, &501_i128),
+        Err(Ok(DexError::InsufficientLp.into()))
+    );
+
+    // Emissions stop once the funded budget runs out
+    env.ledger().set_timestamp(env.ledger().timestamp() + 500);
+    assert_eq!(dex.pending_rewards(&lp), 50);
+
+    assert_eq!(
+        dex.try_unstake(&lp, &501_i128),
+        Err(Ok(DexError::InsufficientStake.into()))
+    );
+    dex.unstake(&lp, &500_i128);
+    assert_eq!(dex.lp_balance(&lp), 1_000);
+    assert_eq!(dex.reward_pool().total_weight, 0);
+    assert_eq!(dex.claim_rewards(&lp), 50);
+    assert_eq!(reward.balance(&lp), 50);
+    assert_eq!(dex.stake_of(&lp), Stake { shares: 0, reward_debt: 0, unclaimed: 0 });
+    assert_eq!(dex.escrowed(&reward.address), 0);
+}
+
+#[test]
+fn test_large_stakes_still_earn_rewards() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  _)      = create_token(&env, &admin);
+    let (reward, reward_s) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+
+    dex.set_reward_token(&reward.address);
+    reward_s.mint(&admin, &1_000_000);
+    dex.fund_rewards(&1_000_000_i128);
+    dex.set_reward_rate(&10_i128);
+
+    // 1,000 USDC of liquidity is about 6.19e17 shares of weight
+    let lp = Address::generate(&env);
+    usdc_s.mint(&lp, &10_000_000_000);
+    dex.add_liquidity(&lp, &0_i128, &10_000_000_000_i128, &0_i128);
+    let shares = dex.lp_balance(&lp);
+    assert!(shares > 100_000_000_000_000_000);
+    dex.stake(&lp, &shares);
+
+    // Accruing every second still pays out, and the rounding dust stays
+    // in the budget instead of vanishing
+    let mut claimed = 0;
+    for _ in 0..10 {
+        env.ledger().set_timestamp(env.ledger().timestamp() + 1);
+        claimed += dex.claim_rewards(&lp);
+    }
+    let credited = (10 * REWARD_SCALE / shares * shares + REWARD_SCALE - 1) / REWARD_SCALE;
+    assert_eq!(claimed, 99);
+    assert_eq!(reward.balance(&lp), claimed);
+    assert_eq!(dex.reward_pool().budget, 1_000_000 - 10 * credited);
+    assert_eq!(dex.escrowed(&reward.address), 1_000_000 - claimed);
+    assert!(dex.reward_pool().budget <= dex.escrowed(&reward.address));
+}
+
+// ─────────────────────────────────────────────
+//  32. VOTE-ESCROWED LOCKS
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_max_lock_earns_boosted_rewards_and_decaying_votes() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let (reward, reward_s) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    dex.set_reward_token(&reward.address);
+    reward_s.mint(&admin, &1_000);
+    dex.fund_rewards(&1_000_i128);
+    dex.set_reward_rate(&10_i128);
+    env.ledger().set_timestamp(1_000);
+
+    let alice = Address::generate(&env);
+    let bob   = Address::generate(&env);
+    xlm_s.mint(&alice, &100);
+    xlm_s.mint(&bob, &250);
+    dex.add_liquidity(&alice, &100_i128, &0_i128, &0_i128);
+    dex.add_liquidity(&bob, &250_i128, &0_i128, &0_i128);
+
+    // A maximum lock weighs 2.5x: 100 locked shares match 250 staked ones
+    let unlock_at = 1_000 + MAX_LOCK_SECS;
+    dex.lock(&alice, &100_i128, &unlock_at);
+    dex.stake(&bob, &250_i128);
+    assert_eq!(dex.lock_of(&alice), Some(VeLock { shares: 100, weight: 250, unlock_at }));
+    assert_eq!(dex.lp_balance(&alice), 0);
+    assert_eq!(dex.reward_pool().total_weight, 500);
+
+    env.ledger().set_timestamp(1_010);
+    assert_eq!(dex.pending_rewards(&alice), 50);
+    assert_eq!(dex.pending_rewards(&bob), 50);
+    assert_eq!(dex.claim_rewards(&alice), 50);
+
+    // Voting power falls linearly to zero at unlock
+    env.ledger().set_timestamp(1_000);
+    assert_eq!(dex.voting_power(&alice), 100);
+    env.ledger().set_timestamp(1_000 + MAX_LOCK_SECS / 2);
+    assert_eq!(dex.voting_power(&alice), 50);
+    env.ledger().set_timestamp(unlock_at);
+    assert_eq!(dex.voting_power(&alice), 0);
+    assert_eq!(dex.voting_power(&bob), 0);
+}
+
+#[test]
+fn test_lock_cannot_be_shortened_or_released_early() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, _) = create_token(&env, &admin);
+    let (eth,  _) = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    env.ledger().set_timestamp(1_000);
+
+    let lp = Address::generate(&env);
+    xlm_s.mint(&lp, &1_000);
+    dex.add_liquidity(&lp, &1_000_i128, &0_i128, &0_i128);
+    assert_eq!(
+        dex.try_lock(&lp, &100_i128, &(1_000 + MAX_LOCK_SECS + 1)),
+        Err(Ok(DexError::BadLockTime.into()))
+    );
+    assert_eq!(dex.try_lock(&lp, &100_i128, &1_000), Err(Ok(DexError::BadLockTime.into())));
+    assert_eq!(dex.try_lock(&lp, &0_i128, &2_000), Err(Ok(DexError::ZeroAmount.into())));
+
+    dex.lock(&lp, &600_i128, &2_000);
+    assert_eq!(
+        dex.try_remove_liquidity(&lp, &401_i128),
+        Err(Ok(DexError::InsufficientLp.into()))
+    );
+    assert_eq!(dex.try_lock(&lp, &0_i128, &1_999), Err(Ok(DexError::BadLockTime.into())));
+    assert_eq!(dex.try_unlock(&lp), Err(Ok(DexError::LockActive.into())));
+
+    // Extending keeps the shares and moves the unlock time out
+    dex.lock(&lp, &0_i128, &3_000);
+    assert_eq!(dex.lock_of(&lp).unwrap().shares, 600);
+    env.ledger().set_timestamp(2_999);
+    assert_eq!(dex.try_unlock(&lp), Err(Ok(DexError::LockActive.into())));
+
+    // Once expired, anyone can release it back to the LP balance
+    env.ledger().set_timestamp(3_000);
+    assert_eq!(dex.unlock(&lp), 600);
+    assert!(env.auths().is_empty());
+    assert_eq!(dex.lp_balance(&lp), 1_000);
+    assert_eq!(dex.lock_of(&lp), None);
+    assert_eq!(dex.reward_pool().total_weight, 0);
+    assert_eq!(dex.try_unlock(&lp), Err(Ok(DexError::NoLock.into())));
+}
+
+// ─────────────────────────────────────────────
+//  33. REFERRALS
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_referrer_earns_fee_share_on_swaps() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 0, 1_000, 0);
+    let xlm = token::Client::new(&env, &xlm_s.address);
+
+    let user    = Address::generate(&env);
+    let partner = Address::generate(&env);
+    xlm_s.mint(&user, &3_000_000_000);
+
+    // Referrers the admin hasn't registered earn nothing by default
+    assert_eq!(DEFAULT_REFERRAL_BPS, 0);
+    dex.swap_xlm_for_usdc(&user, &1_000_000_000_i128, &0_i128, &Some(partner.clone()));
+    assert_eq!(dex.referral_balance(&partner, &xlm_s.address), 0);
+    assert_eq!(dex.get_reserves().0, 1_000_000_000);
+
+    // 0.3% of 1000 XLM is 3 XLM of fee; the partner's share is 20% of that
+    dex.set_referrer_bps(&partner, &Some(2_000_u32));
+    let out = dex.swap_xlm_for_usdc(&user, &1_000_000_000_i128, &0_i128, &Some(partner.clone()));
+    assert_eq!(out, dex.quote_xlm_to_usdc(&1_000_000_000_i128));
+    let cut = 600_000;
+    assert_eq!(dex.referral_balance(&partner, &xlm_s.address), cut);
+    assert_eq!(dex.get_reserves().0, 2_000_000_000 - cut);
+    assert_eq!(dex.escrowed(&xlm_s.address), cut);
+
+    // The generic entrypoint pays the same share
+    dex.swap(&user, &xlm_s.address, &usdc.address, &1_000_000_000_i128, &0_i128, &Some(partner.clone()));
+    assert_eq!(dex.referral_balance(&partner, &xlm_s.address), 2 * cut);
+    assert_eq!(dex.sync(), (3_000_000_000 - 2 * cut, dex.get_reserves().1, 0));
+
+    assert_eq!(dex.claim_referral(&partner, &xlm_s.address), 2 * cut);
+    assert_eq!(xlm.balance(&partner), 2 * cut);
+    assert_eq!(dex.referral_balance(&partner, &xlm_s.address), 0);
+    assert_eq!(dex.escrowed(&xlm_s.address), 0);
+    assert_eq!(dex.claim_referral(&partner, &xlm_s.address), 0);
+}
+
+#[test]
+fn test_admin_sets_referral_shares_within_cap() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 3 * XLM_CHUNK, 0, 0);
+
+    let user    = Address::generate(&env);
+    let partner = Address::generate(&env);
+    let other   = Address::generate(&env);
+    assert_eq!(dex.try_set_referral_bps(&5_001_u32), Err(Ok(DexError::BadFee.into())));
+    assert_eq!(
+        dex.try_set_referrer_bps(&partner, &Some(5_001_u32)),
+        Err(Ok(DexError::BadFee.into()))
+    );
+
+    dex.set_referral_bps(&1_000_u32);
+    dex.set_referrer_bps(&partner, &Some(5_000_u32));
+    assert_eq!(dex.referral_bps(&partner), 5_000);
+    assert_eq!(dex.referral_bps(&other), 1_000);
+
+    // 0.3% of 10_000 USDC is 30 USDC of fee
+    usdc_s.mint(&user, &20_000);
+    dex.swap_usdc_for_xlm(&user, &10_000_i128, &0_i128, &Some(partner.clone()));
+    dex.swap_usdc_for_xlm(&user, &10_000_i128, &0_i128, &Some(other.clone()));
+    assert_eq!(dex.referral_balance(&partner, &usdc.address), 15);
+    assert_eq!(dex.referral_balance(&other, &usdc.address), 3);
+
+    // Without a referrer the whole input stays in the pool
+    usdc_s.mint(&user, &10_000);
+    dex.swap_usdc_for_xlm(&user, &10_000_i128, &0_i128, &None);
+    assert_eq!(dex.get_reserves().1, 30_000 - 18);
+
+    dex.set_referrer_bps(&partner, &None);
+    assert_eq!(dex.referral_bps(&partner), 1_000);
+}
+
+#[test]
+fn test_traders_cannot_refer_themselves() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let (eth,  eth_s)  = create_token(&env, &admin);
+    let dex = create_dex(&env);
+    let xlm_s = init_dex(&env, &dex, &admin, &usdc.address, &eth.address);
+    seed(&dex, &admin, &xlm_s, &usdc_s, &eth_s, 3 * XLM_CHUNK, 0, 0);
+
+    let partner = Address::generate(&env);
+    dex.set_referrer_bps(&partner, &Some(5_000_u32));
+    usdc_s.mint(&partner, &20_000);
+
+    // A registered referrer trading on its own code gets no rebate
+    dex.swap_usdc_for_xlm(&partner, &10_000_i128, &0_i128, &Some(partner.clone()));
+    dex.swap(&partner, &usdc.address, &xlm_s.address, &10_000_i128, &0_i128, &Some(partner.clone()));
+    assert_eq!(dex.referral_balance(&partner, &usdc.address), 0);
+    assert_eq!(dex.get_reserves().1, 20_000);
+    assert_eq!(dex.escrowed(&usdc.address), 0);
+}
@@ -0,0 +1,616 @@
+#![no_std]
+
+use soroban_sdk::{
+    contract, contracterror, contractevent, contractimpl, contracttype, panic_with_error,
+    symbol_short, token, Address, Env, Symbol, Vec,
+};
+
+// ─────────────────────────────────────────────
+//  Central limit order book for XLM/USDC
+// ─────────────────────────────────────────────
+//  Runs next to StellarDex as its own contract. Orders rest at a price
+//  (quote per `PRICE_SCALE` base units) and match in price-time priority:
+//  best price first, then oldest order at that price. Trades settle at
+//  the resting (maker) order's price. Fees are taken from what each side
+//  receives and accrue to the book until the admin withdraws them.
+//
+//  Asks escrow base; bids escrow quote for the full amount at their limit
+//  and are refunded any price improvement.
+//
+//  The book is bounded so that no call has to walk an unbounded list:
+//  orders below the minimum size are rejected, a taker stops after
+//  `MAX_FILLS` maker orders, and each side holds at most
+//  `MAX_PRICE_LEVELS` prices of at most `MAX_LEVEL_ORDERS` orders each.
+//  A full book still takes better orders: a new price beyond the worst
+//  one evicts the worst level, and a larger order at a full price evicts
+//  the smallest one there. Evicted orders are refunded in full.
+
+// ─────────────────────────────────────────────
+//  Storage Keys
+// ─────────────────────────────────────────────
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    Admin,
+    BaseToken,
+    QuoteToken,
+    MakerFeeBps,
+    TakerFeeBps,
+    MinOrder,
+    NextOrderId,
+    Fees(Address),
+    Prices(Side),
+    Level(Side, i128),
+    Order(u64),
+    OwnerOrders(Address),
+}
+
+// ─────────────────────────────────────────────
+//  Types
+// ─────────────────────────────────────────────
+#[contracttype]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+/// A resting order. `remaining` is in base units; `escrow` is the quote
+/// still held for a bid (always 0 for asks, whose escrow is `remaining`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Order {
+    pub owner:     Address,
+    pub side:      Side,
+    pub price:     i128,
+    pub remaining: i128,
+    pub escrow:    i128,
+}
+
+/// Orders at one price, oldest first, and their combined base amount.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Level {
+    pub orders: Vec<u64>,
+    pub total:  i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DepthLevel {
+    pub price:  i128,
+    pub amount: i128,
+}
+
+/// Outcome of `place_order`, in base units. `remaining` rests in the
+/// book; `cancelled` neither filled nor rested and was refunded.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlaceResult {
+    pub order_id:  u64,
+    pub filled:    i128,
+    pub remaining: i128,
+    pub cancelled: i128,
+}
+
+/// Fixed-point scale of order prices: quote units per `PRICE_SCALE` base units.
+pub const PRICE_SCALE: i128 = 10_000_000;
+
+/// Highest maker or taker fee the admin can set (10%).
+pub const MAX_FEE_BPS: u32 = 1_000;
+
+/// Smallest order, in base units, until the admin sets another
+/// (one token at 7 decimals).
+pub const DEFAULT_MIN_ORDER: i128 = 10_000_000;
+
+/// Most maker orders a single taker fills. Whatever would still cross
+/// after that is refunded instead of resting in a crossed book. Each fill
+/// writes the maker's order, order list and balance, so this keeps a
+/// taker within a transaction's write limit.
+pub const MAX_FILLS: u32 = 10;
+
+/// Most distinct prices resting on each side of the book. Once full, a
+/// new price must beat the worst one, whose orders are then evicted.
+pub const MAX_PRICE_LEVELS: u32 = 100;
+
+/// Most orders resting at a single price. Once full, a new order must be
+/// larger than the smallest one, which is then evicted. Kept small enough
+/// that refunding a whole level fits in one transaction's footprint.
+pub const MAX_LEVEL_ORDERS: u32 = 10;
+
+// ─────────────────────────────────────────────
+//  Storage TTL  ← ledgers; ~17_280 per day at 5s close time
+// ─────────────────────────────────────────────
+const DAY_IN_LEDGERS: u32 = 17_280;
+const TTL_THRESHOLD: u32 = 7 * DAY_IN_LEDGERS;
+const TTL_EXTEND_TO: u32 = 30 * DAY_IN_LEDGERS;
+
+// ─────────────────────────────────────────────
+//  Errors
+// ─────────────────────────────────────────────
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum BookError {
+    NotInitialized = 1,
+    AlreadyInit    = 2,
+    ZeroAmount     = 3,
+    BadPrice       = 4,
+    BadFee         = 5,
+    NoOrder        = 6,
+    BelowMinimum   = 7,
+    BookFull       = 8,
+}
+
+// ─────────────────────────────────────────────
+//  Events
+// ─────────────────────────────────────────────
+#[contractevent]
+pub struct OrderEvent {
+    #[topic]
+    pub action:   Symbol,
+    #[topic]
+    pub order_id: u64,
+    pub side:     Side,
+    pub price:    i128,
+    pub amount:   i128,
+}
+
+#[contractevent]
+pub struct TradeEvent {
+    #[topic]
+    pub maker_order: u64,
+    #[topic]
+    pub taker_order: u64,
+    pub price:       i128,
+    pub amount:      i128,
+}
+
+// ─────────────────────────────────────────────
+//  Contract
+// ─────────────────────────────────────────────
+#[contract]
+pub struct OrderBook;
+
+#[contractimpl]
+impl OrderBook {
+
+    // ═══════════════════════════════════════
+    //  INITIALISATION
+    // ═══════════════════════════════════════
+    pub fn initialize(
+        env: Env,
+        admin: Address,
+        base_token: Address,
+        quote_token: Address,
+        maker_fee_bps: u32,
+        taker_fee_bps: u32,
+    ) {
+        if env.storage().instance().has(&DataKey::Admin) {
+            panic_with_error!(&env, BookError::AlreadyInit);
+        }
+        admin.require_auth();
+        Self::check_fees(&env, maker_fee_bps, taker_fee_bps);
+
+        env.storage().instance().set(&DataKey::Admin,       &admin);
+        env.storage().instance().set(&DataKey::BaseToken,   &base_token);
+        env.storage().instance().set(&DataKey::QuoteToken,  &quote_token);
+        env.storage().instance().set(&DataKey::MakerFeeBps, &maker_fee_bps);
+        env.storage().instance().set(&DataKey::TakerFeeBps, &taker_fee_bps);
+        env.storage().instance().set(&DataKey::NextOrderId, &0_u64);
+    }
+
+    // ═══════════════════════════════════════
+    //  ADMIN
+    // ═══════════════════════════════════════
+    pub fn set_fees(env: Env, maker_fee_bps: u32, taker_fee_bps: u32) {
+        Self::require_admin(&env);
+        Self::check_fees(&env, maker_fee_bps, taker_fee_bps);
+        env.storage().instance().set(&DataKey::MakerFeeBps, &maker_fee_bps);
+        env.storage().instance().set(&DataKey::TakerFeeBps, &taker_fee_bps);
+    }
+
+    /// Smallest order `place_order` accepts, in base units.
+    pub fn set_min_order(env: Env, min_amount: i128) {
+        Self::require_admin(&env);
+        if min_amount <= 0 { panic_with_error!(&env, BookError::ZeroAmount); }
+        env.storage().instance().set(&DataKey::MinOrder, &min_amount);
+    }
+
+    /// Send accrued fees to `to`; returns (base, quote) withdrawn.
+    pub fn withdraw_fees(env: Env, to: Address) -> (i128, i128) {
+        Self::require_admin(&env);
+        let b = Self::withdraw_fee_token(&env, DataKey::BaseToken,  &to);
+        let q = Self::withdraw_fee_token(&env, DataKey::QuoteToken, &to);
+        (b, q)
+    }
+
+    // ═══════════════════════════════════════
+    //  TRADING
+    // ═══════════════════════════════════════
+
+    /// Buy (`Bid`) or sell (`Ask`) `amount` base at `price` or better.
+    /// Whatever crosses the opposite side fills immediately as taker; the
+    /// rest rests in the book as maker under the returned order id. A
+    /// rest below the minimum order size, or one that still crosses once
+    /// `MAX_FILLS` makers were filled, is refunded instead.
+    pub fn place_order(env: Env, owner: Address, side: Side, price: i128, amount: i128) -> PlaceResult {
+        owner.require_auth();
+        if amount <= 0 { panic_with_error!(&env, BookError::ZeroAmount); }
+        if price <= 0 { panic_with_error!(&env, BookError::BadPrice); }
+        let min_order = Self::min_order(env.clone());
+        if amount < min_order { panic_with_error!(&env, BookError::BelowMinimum); }
+        env.storage().instance().extend_ttl(TTL_THRESHOLD, TTL_EXTEND_TO);
+
+        let order_id: u64 = env.storage().instance().get(&DataKey::NextOrderId)
+            .unwrap_or_else(|| panic_with_error!(&env, BookError::NotInitialized));
+        env.storage().instance().set(&DataKey::NextOrderId, &(order_id + 1));
+
+        let (pay_key, escrow) = match side {
+            Side::Ask => (DataKey::BaseToken,  amount),
+            Side::Bid => (DataKey::QuoteToken, Self::cost_ceil(amount, price)),
+        };
+        Self::token(&env, pay_key).transfer(&owner, env.current_contract_address(), &escrow);
+
+        let (filled, quote_traded, still_crosses) = Self::match_order(&env, order_id, &owner, side, price, amount);
+        let unfilled  = amount - filled;
+        let remaining = if still_crosses || unfilled < min_order { 0 } else { unfilled };
+        let cancelled = unfilled - remaining;
+
+        let mut resting_escrow = 0;
+        let (refund_key, refund) = match side {
+            Side::Ask => (DataKey::BaseToken, cancelled),
+            Side::Bid => {
+                resting_escrow = Self::cost_ceil(remaining, price);
+                (DataKey::QuoteToken, escrow - quote_traded - resting_escrow)
+            }
+        };
+        if refund > 0 {
+            Self::token(&env, refund_key).transfer(&env.current_contract_address(), &owner, &refund);
+        }
+        if remaining > 0 {
+            let order = Order { owner, side, price, remaining, escrow: resting_escrow };
+            Self::rest(&env, order_id, &order);
+        }
+
+        OrderEvent { action: symbol_short!("placed"), order_id, side, price, amount }.publish(&env);
+        PlaceResult { order_id, filled, remaining, cancelled }
+    }
+
+    /// Remove a resting order and refund its escrow to the owner. Returns
+    /// the refund (base for asks, quote for bids).
+    pub fn cancel_order(env: Env, order_id: u64) -> i128 {
+        let order = Self::load_order(&env, order_id);
+        order.owner.require_auth();
+
+        let level_key = DataKey::Level(order.side, order.price);
+        let mut level: Level = env.storage().persistent().get(&level_key).unwrap();
+        if let Some(i) = level.orders.first_index_of(order_id) {
+            level.orders.remove(i);
+        }
+        level.total -= order.remaining;
+        if level.orders.is_empty() {
+            Self::drop_level(&env, order.side, order.price);
+        } else {
+            Self::save(&env, &level_key, &level);
+        }
+        Self::refund(&env, order_id, &order, symbol_short!("cancel"))
+    }
+
+    // ═══════════════════════════════════════
+    //  VIEW
+    // ═══════════════════════════════════════
+
+    /// Up to `levels` aggregated price levels per side: bids from the
+    /// highest price down, asks from the lowest price up.
+    pub fn depth(env: Env, levels: u32) -> (Vec<DepthLevel>, Vec<DepthLevel>) {
+        (Self::side_depth(&env, Side::Bid, levels), Self::side_depth(&env, Side::Ask, levels))
+    }
+
+    pub fn get_order(env: Env, order_id: u64) -> Order {
+        Self::load_order(&env, order_id)
+    }
+
+    /// Ids of `owner`'s resting orders, oldest first.
+    pub fn orders_of(env: Env, owner: Address) -> Vec<u64> {
+        env.storage().persistent()
+            .get(&DataKey::OwnerOrders(owner))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// (maker, taker) fee in basis points
+    pub fn fee_bps(env: Env) -> (u32, u32) {
+        let m: u32 = env.storage().instance().get(&DataKey::MakerFeeBps).unwrap_or(0);
+        let t: u32 = env.storage().instance().get(&DataKey::TakerFeeBps).unwrap_or(0);
+        (m, t)
+    }
+
+    pub fn min_order(env: Env) -> i128 {
+        env.storage().instance().get(&DataKey::MinOrder).unwrap_or(DEFAULT_MIN_ORDER)
+    }
+
+    /// Accrued, unwithdrawn fees as (base, quote)
+    pub fn fees(env: Env) -> (i128, i128) {
+        (Self::accrued(&env, DataKey::BaseToken), Self::accrued(&env, DataKey::QuoteToken))
+    }
+
+    // ═══════════════════════════════════════
+    //  INTERNAL HELPERS
+    // ═══════════════════════════════════════
+    fn require_admin(env: &Env) {
+        let admin: Address = env.storage().instance().get(&DataKey::Admin)
+            .unwrap_or_else(|| panic_with_error!(env, BookError::NotInitialized));
+        admin.require_auth();
+    }
+
+    fn check_fees(env: &Env, maker_fee_bps: u32, taker_fee_bps: u32) {
+        if maker_fee_bps > MAX_FEE_BPS || taker_fee_bps > MAX_FEE_BPS {
+            panic_with_error!(env, BookError::BadFee);
+        }
+    }
+
+    fn token(env: &Env, token_key: DataKey) -> token::Client<'_> {
+        let addr: Address = env.storage().instance().get(&token_key).unwrap();
+        token::Client::new(env, &addr)
+    }
+
+    fn accrued(env: &Env, token_key: DataKey) -> i128 {
+        let addr: Address = env.storage().instance().get(&token_key).unwrap();
+        env.storage().instance().get(&DataKey::Fees(addr)).unwrap_or(0)
+    }
+
+    fn withdraw_fee_token(env: &Env, token_key: DataKey, to: &Address) -> i128 {
+        let addr: Address = env.storage().instance().get(&token_key).unwrap();
+        let amount: i128 = env.storage().instance().get(&DataKey::Fees(addr.clone())).unwrap_or(0);
+        if amount > 0 {
+            env.storage().instance().set(&DataKey::Fees(addr.clone()), &0_i128);
+            token::Client::new(env, &addr).transfer(&env.current_contract_address(), to, &amount);
+        }
+        amount
+    }
+
+    /// Quote owed for `amount` base at `price`, rounded down (buyer keeps dust)
+    fn cost(amount: i128, price: i128) -> i128 {
+        amount * price / PRICE_SCALE
+    }
+
+    /// Quote a bid escrows for `amount` base at `price`, rounded up
+    fn cost_ceil(amount: i128, price: i128) -> i128 {
+        (amount * price + PRICE_SCALE - 1) / PRICE_SCALE
+    }
+
+    /// Send `gross` of the token under `token_key` to `to`, keeping `fee_bps` of it.
+    fn pay(env: &Env, token_key: DataKey, to: &Address, gross: i128, fee_key: DataKey) {
+        if gross <= 0 { return; }
+        let fee_bps: u32 = env.storage().instance().get(&fee_key).unwrap_or(0);
+        let fee = gross * fee_bps as i128 / 10_000;
+        let addr: Address = env.storage().instance().get(&token_key).unwrap();
+        if fee > 0 {
+            let fees_key = DataKey::Fees(addr.clone());
+            let held: i128 = env.storage().instance().get(&fees_key).unwrap_or(0);
+            env.storage().instance().set(&fees_key, &(held + fee));
+        }
+        token::Client::new(env, &addr).transfer(&env.current_contract_address(), to, &(gross - fee));
+    }
+
+    /// Fill up to `amount` of a new order against the opposite side, best
+    /// price first and oldest first within a price, stopping after
+    /// `MAX_FILLS` maker orders. Returns the base filled, the quote it
+    /// traded for, and whether the best opposite price still crosses.
+    fn match_order(env: &Env, taker_id: u64, taker: &Address, side: Side, limit: i128, amount: i128) -> (i128, i128, bool) {
+        let maker_side = match side { Side::Bid => Side::Ask, Side::Ask => Side::Bid };
+        let prices_key = DataKey::Prices(maker_side);
+        let mut prices: Vec<i128> = env.storage().persistent().get(&prices_key).unwrap_or(Vec::new(env));
+        let crosses = |level_price: i128| match side {
+            Side::Bid => level_price <= limit,
+            Side::Ask => level_price >= limit,
+        };
+        let mut remaining = amount;
+        let mut quote_traded = 0;
+        let mut fills = 0;
+
+        while remaining > 0 && fills < MAX_FILLS {
+            let Some(level_price) = prices.first() else { break };
+            if !crosses(level_price) { break; }
+
+            let level_key = DataKey::Level(maker_side, level_price);
+            let mut level: Level = env.storage().persistent().get(&level_key).unwrap();
+            while remaining > 0 && fills < MAX_FILLS && !level.orders.is_empty() {
+                let maker_id = level.orders.first_unchecked();
+                let mut maker = Self::load_order(env, maker_id);
+                let fill = remaining.min(maker.remaining);
+                let quote = Self::cost(fill, level_price);
+
+                remaining       -= fill;
+                maker.remaining -= fill;
+                level.total     -= fill;
+                quote_traded    += quote;
+                fills           += 1;
+                match side {
+                    Side::Bid => Self::pay(env, DataKey::QuoteToken, &maker.owner, quote, DataKey::MakerFeeBps),
+                    Side::Ask => {
+                        maker.escrow -= quote;
+                        Self::pay(env, DataKey::BaseToken, &maker.owner, fill, DataKey::MakerFeeBps);
+                    }
+                }
+                TradeEvent { maker_order: maker_id, taker_order: taker_id, price: level_price, amount: fill }.publish(env);
+
+                if maker.remaining == 0 {
+                    if maker.escrow > 0 {
+                        Self::token(env, DataKey::QuoteToken)
+                            .transfer(&env.current_contract_address(), &maker.owner, &maker.escrow);
+                    }
+                    level.orders.pop_front();
+                    Self::close_order(env, maker_id, &maker.owner);
+                } else {
+                    Self::save(env, &DataKey::Order(maker_id), &maker);
+                }
+            }
+
+            if level.orders.is_empty() {
+                env.storage().persistent().remove(&level_key);
+                prices.pop_front();
+            } else {
+                Self::save(env, &level_key, &level);
+            }
+        }
+
+        if prices.is_empty() {
+            env.storage().persistent().remove(&prices_key);
+        } else {
+            Self::save(env, &prices_key, &prices);
+        }
+
+        let filled = amount - remaining;
+        match side {
+            Side::Bid => Self::pay(env, DataKey::BaseToken,  taker, filled,       DataKey::TakerFeeBps),
+            Side::Ask => Self::pay(env, DataKey::QuoteToken, taker, quote_traded, DataKey::TakerFeeBps),
+        }
+        let still_crosses = remaining > 0 && prices.first().is_some_and(crosses);
+        (filled, quote_traded, still_crosses)
+    }
+
+    /// Add an order to the back of its price level, creating the level
+    /// in price order if needed. A full level makes room by evicting its
+    /// smallest order, if the new one is larger.
+    fn rest(env: &Env, order_id: u64, order: &Order) {
+        let level_key = DataKey::Level(order.side, order.price);
+        let mut level: Level = match env.storage().persistent().get::<_, Level>(&level_key) {
+            Some(mut l) => {
+                if l.orders.len() >= MAX_LEVEL_ORDERS {
+                    Self::evict_smallest(env, &mut l, order.remaining);
+                }
+                l
+            }
+            None => {
+                Self::insert_price(env, order.side, order.price);
+                Level { orders: Vec::new(env), total: 0 }
+            }
+        };
+        level.orders.push_back(order_id);
+        level.total += order.remaining;
+        Self::save(env, &level_key, &level);
+        Self::save(env, &DataKey::Order(order_id), order);
+
+        let owner_key = DataKey::OwnerOrders(order.owner.clone());
+        let mut ids: Vec<u64> = env.storage().persistent().get(&owner_key).unwrap_or(Vec::new(env));
+        ids.push_back(order_id);
+        Self::save(env, &owner_key, &ids);
+    }
+
+    /// Keep bid prices descending and ask prices ascending (best first).
+    /// A full side makes room by evicting its worst level, if `price`
+    /// beats it.
+    fn insert_price(env: &Env, side: Side, price: i128) {
+        let prices_key = DataKey::Prices(side);
+        let mut prices: Vec<i128> = env.storage().persistent().get(&prices_key).unwrap_or(Vec::new(env));
+        let beats = |p: i128| match side {
+            Side::Bid => p < price,
+            Side::Ask => p > price,
+        };
+        if prices.len() >= MAX_PRICE_LEVELS {
+            let worst = prices.last_unchecked();
+            if !beats(worst) { panic_with_error!(env, BookError::BookFull); }
+            Self::evict_level(env, side, worst);
+            prices.pop_back();
+        }
+        let mut at = prices.len();
+        for (i, p) in prices.iter().enumerate() {
+            if beats(p) { at = i as u32; break; }
+        }
+        prices.insert(at, price);
+        Self::save(env, &prices_key, &prices);
+    }
+
+    /// Refund every order at `price` and remove the level. The caller
+    /// updates the side's price list.
+    fn evict_level(env: &Env, side: Side, price: i128) {
+        let level_key = DataKey::Level(side, price);
+        let level: Level = env.storage().persistent().get(&level_key).unwrap();
+        for order_id in level.orders.iter() {
+            Self::refund(env, order_id, &Self::load_order(env, order_id), symbol_short!("evicted"));
+        }
+        env.storage().persistent().remove(&level_key);
+    }
+
+    /// Refund the smallest order in a full level, the newest among equals,
+    /// to make room for an order of `amount`. The caller saves the level.
+    fn evict_smallest(env: &Env, level: &mut Level, amount: i128) {
+        let mut smallest: Option<(u32, u64, Order)> = None;
+        for (i, order_id) in level.orders.iter().enumerate() {
+            let order = Self::load_order(env, order_id);
+            if smallest.as_ref().is_none_or(|(_, _, s)| order.remaining <= s.remaining) {
+                smallest = Some((i as u32, order_id, order));
+            }
+        }
+        let (i, order_id, order) = smallest.unwrap();
+        if order.remaining >= amount { panic_with_error!(env, BookError::BookFull); }
+        level.orders.remove(i);
+        level.total -= order.remaining;
+        Self::refund(env, order_id, &order, symbol_short!("evicted"));
+    }
+
+    /// Close an order that has left its level and return its escrow to
+    /// the owner (base for asks, quote for bids).
+    fn refund(env: &Env, order_id: u64, order: &Order, action: Symbol) -> i128 {
+        Self::close_order(env, order_id, &order.owner);
+        let (refund_key, refund) = match order.side {
+            Side::Ask => (DataKey::BaseToken,  order.remaining),
+            Side::Bid => (DataKey::QuoteToken, order.escrow),
+        };
+        Self::token(env, refund_key).transfer(&env.current_contract_address(), &order.owner, &refund);
+        OrderEvent { action, order_id, side: order.side, price: order.price, amount: order.remaining }.publish(env);
+        refund
+    }
+
+    fn drop_level(env: &Env, side: Side, price: i128) {
+        env.storage().persistent().remove(&DataKey::Level(side, price));
+        let prices_key = DataKey::Prices(side);
+        let mut prices: Vec<i128> = env.storage().persistent().get(&prices_key).unwrap_or(Vec::new(env));
+        if let Some(i) = prices.first_index_of(price) {
+            prices.remove(i);
+        }
+        if prices.is_empty() {
+            env.storage().persistent().remove(&prices_key);
+        } else {
+            Self::save(env, &prices_key, &prices);
+        }
+    }
+
+    fn close_order(env: &Env, order_id: u64, owner: &Address) {
+        env.storage().persistent().remove(&DataKey::Order(order_id));
+        let owner_key = DataKey::OwnerOrders(owner.clone());
+        let mut ids: Vec<u64> = env.storage().persistent().get(&owner_key).unwrap_or(Vec::new(env));
+        if let Some(i) = ids.first_index_of(order_id) {
+            ids.remove(i);
+        }
+        if ids.is_empty() {
+            env.storage().persistent().remove(&owner_key);
+        } else {
+            Self::save(env, &owner_key, &ids);
+        }
+    }
+
+    fn load_order(env: &Env, order_id: u64) -> Order {
+        env.storage().persistent()
+            .get(&DataKey::Order(order_id))
+            .unwrap_or_else(|| panic_with_error!(env, BookError::NoOrder))
+    }
+
+    fn side_depth(env: &Env, side: Side, levels: u32) -> Vec<DepthLevel> {
+        let prices: Vec<i128> = env.storage().persistent().get(&DataKey::Prices(side)).unwrap_or(Vec::new(env));
+        let mut out = Vec::new(env);
+        for price in prices.iter().take(levels as usize) {
+            let level: Level = env.storage().persistent().get(&DataKey::Level(side, price)).unwrap();
+            out.push_back(DepthLevel { price, amount: level.total });
+        }
+        out
+    }
+
+    fn save<V: soroban_sdk::IntoVal<Env, soroban_sdk::Val>>(env: &Env, key: &DataKey, value: &V) {
+        env.storage().persistent().set(key, value);
+        env.storage().persistent().extend_ttl(key, TTL_THRESHOLD, TTL_EXTEND_TO);
+    }
+}
+
+mod test;
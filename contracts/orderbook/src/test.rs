@@ -0,0 +1,424 @@
+#![cfg(test)]
+
+extern crate std;
+
+use soroban_sdk::{testutils::Address as _, token, vec, Address, Env};
+
+use crate::{
+    BookError, DepthLevel, OrderBook, OrderBookClient, PlaceResult, Side, DEFAULT_MIN_ORDER, MAX_FILLS,
+    MAX_LEVEL_ORDERS, MAX_PRICE_LEVELS, PRICE_SCALE,
+};
+
+// ─────────────────────────────────────────────
+//  Helpers
+// ─────────────────────────────────────────────
+
+fn create_token<'a>(
+    env: &'a Env,
+    admin: &Address,
+) -> (token::Client<'a>, token::StellarAssetClient<'a>) {
+    let addr = env.register_stellar_asset_contract_v2(admin.clone()).address();
+    (
+        token::Client::new(env, &addr),
+        token::StellarAssetClient::new(env, &addr),
+    )
+}
+
+struct Book<'a> {
+    book:    OrderBookClient<'a>,
+    admin:   Address,
+    xlm:     token::Client<'a>,
+    xlm_s:   token::StellarAssetClient<'a>,
+    usdc:    token::Client<'a>,
+    usdc_s:  token::StellarAssetClient<'a>,
+}
+
+/// XLM/USDC book with the given maker and taker fees. The minimum order
+/// size is lowered to one unit so tests can trade small amounts.
+fn create_book(env: &Env, maker_fee_bps: u32, taker_fee_bps: u32) -> Book<'_> {
+    let admin = Address::generate(env);
+    let (xlm,  xlm_s)  = create_token(env, &admin);
+    let (usdc, usdc_s) = create_token(env, &admin);
+    let book = OrderBookClient::new(env, &env.register(OrderBook, ()));
+    book.initialize(&admin, &xlm.address, &usdc.address, &maker_fee_bps, &taker_fee_bps);
+    book.set_min_order(&1_i128);
+    Book { book, admin, xlm, xlm_s, usdc, usdc_s }
+}
+
+/// 0.125 USDC per XLM, and one tick either side
+const MID:  i128 = PRICE_SCALE / 8;
+const TICK: i128 = PRICE_SCALE / 1_000;
+
+fn level(price: i128, amount: i128) -> DepthLevel {
+    DepthLevel { price, amount }
+}
+
+// ─────────────────────────────────────────────
+//  1. RESTING ORDERS & DEPTH
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_depth_aggregates_and_sorts_levels() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let maker = Address::generate(&env);
+    b.xlm_s.mint(&maker, &10_000_i128);
+    b.usdc_s.mint(&maker, &10_000_i128);
+
+    b.book.place_order(&maker, &Side::Ask, &(MID + TICK), &1_000_i128);
+    b.book.place_order(&maker, &Side::Ask, &(MID + 2 * TICK), &500_i128);
+    b.book.place_order(&maker, &Side::Ask, &(MID + TICK), &300_i128);
+    b.book.place_order(&maker, &Side::Bid, &(MID - 2 * TICK), &2_000_i128);
+    b.book.place_order(&maker, &Side::Bid, &(MID - TICK), &400_i128);
+
+    let (bids, asks) = b.book.depth(&10_u32);
+    assert_eq!(bids, vec![&env, level(MID - TICK, 400), level(MID - 2 * TICK, 2_000)]);
+    assert_eq!(asks, vec![&env, level(MID + TICK, 1_300), level(MID + 2 * TICK, 500)]);
+
+    let (bids, asks) = b.book.depth(&1_u32);
+    assert_eq!(bids.len(), 1);
+    assert_eq!(asks, vec![&env, level(MID + TICK, 1_300)]);
+
+    // Asks escrow base; bids escrow quote at their limit, rounded up
+    // (2_000 × 0.123 = 246, 400 × 0.124 = 49.6 → 50)
+    assert_eq!(b.xlm.balance(&maker), 10_000 - 1_800);
+    assert_eq!(b.usdc.balance(&maker), 10_000 - 246 - 50);
+    assert_eq!(b.book.orders_of(&maker).len(), 5);
+}
+
+// ─────────────────────────────────────────────
+//  2. MATCHING
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_taker_bid_fills_in_price_time_priority() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let (first, second, cheap) = (Address::generate(&env), Address::generate(&env), Address::generate(&env));
+    for m in [&first, &second, &cheap] { b.xlm_s.mint(m, &1_000_i128); }
+
+    let first_id  = b.book.place_order(&first,  &Side::Ask, &(MID + TICK), &1_000_i128).order_id;
+    let second_id = b.book.place_order(&second, &Side::Ask, &(MID + TICK), &1_000_i128).order_id;
+    b.book.place_order(&cheap, &Side::Ask, &MID, &1_000_i128);
+
+    // Cheapest level first, then the older of the two at MID + TICK
+    let taker = Address::generate(&env);
+    b.usdc_s.mint(&taker, &1_000_i128);
+    let res = b.book.place_order(&taker, &Side::Bid, &(MID + TICK), &1_500_i128);
+    assert_eq!(res.filled, 1_500);
+    assert_eq!(res.remaining, 0);
+
+    assert_eq!(b.xlm.balance(&taker), 1_500);
+    assert_eq!(b.usdc.balance(&cheap), 1_000 * MID / PRICE_SCALE);
+    assert_eq!(b.usdc.balance(&first), 500 * (MID + TICK) / PRICE_SCALE);
+    assert_eq!(b.usdc.balance(&second), 0);
+    // The taker paid maker prices and got the rest of the escrow back
+    assert_eq!(b.usdc.balance(&taker), 1_000 - 1_000 * MID / PRICE_SCALE - 500 * (MID + TICK) / PRICE_SCALE);
+
+    assert_eq!(b.book.get_order(&first_id).remaining, 500);
+    assert_eq!(b.book.get_order(&second_id).remaining, 1_000);
+    assert_eq!(b.book.depth(&10_u32).1, vec![&env, level(MID + TICK, 1_500)]);
+    assert_eq!(b.book.orders_of(&cheap).len(), 0);
+}
+
+#[test]
+fn test_partial_fill_rests_remainder() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let maker = Address::generate(&env);
+    b.xlm_s.mint(&maker, &400_i128);
+    b.book.place_order(&maker, &Side::Ask, &MID, &400_i128);
+
+    let taker = Address::generate(&env);
+    b.usdc_s.mint(&taker, &1_000_i128);
+    let res = b.book.place_order(&taker, &Side::Bid, &MID, &1_000_i128);
+    assert_eq!(res, PlaceResult { order_id: 1, filled: 400, remaining: 600, cancelled: 0 });
+
+    let resting = b.book.get_order(&1_u64);
+    assert_eq!(resting.side, Side::Bid);
+    assert_eq!(resting.remaining, 600);
+    assert_eq!(resting.escrow, 600 * MID / PRICE_SCALE);
+    assert_eq!(b.book.depth(&10_u32), (vec![&env, level(MID, 600)], vec![&env]));
+}
+
+#[test]
+fn test_taker_ask_hits_bids_and_refunds_maker_escrow() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let maker = Address::generate(&env);
+    b.usdc_s.mint(&maker, &1_000_i128);
+    // 3 base at 1/3 quote each escrows ceil(1) = 1 quote
+    let price = PRICE_SCALE / 3;
+    let bid = b.book.place_order(&maker, &Side::Bid, &price, &3_i128).order_id;
+    assert_eq!(b.usdc.balance(&maker), 999);
+
+    let taker = Address::generate(&env);
+    b.xlm_s.mint(&taker, &3_i128);
+    b.book.place_order(&taker, &Side::Ask, &price, &1_i128);
+    b.book.place_order(&taker, &Side::Ask, &price, &2_i128);
+
+    // Each fill rounds down in the buyer's favour, and the unused escrow
+    // goes back to the maker once the bid is done
+    assert_eq!(b.xlm.balance(&maker), 3);
+    assert_eq!(b.usdc.balance(&taker), 0);
+    assert_eq!(b.usdc.balance(&maker), 1_000);
+    assert_eq!(b.book.try_get_order(&bid), Err(Ok(BookError::NoOrder.into())));
+}
+
+#[test]
+fn test_non_crossing_orders_rest() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let maker = Address::generate(&env);
+    b.xlm_s.mint(&maker, &1_000_i128);
+    b.book.place_order(&maker, &Side::Ask, &(MID + TICK), &1_000_i128);
+
+    let taker = Address::generate(&env);
+    b.usdc_s.mint(&taker, &1_000_i128);
+    let res = b.book.place_order(&taker, &Side::Bid, &MID, &1_000_i128);
+    assert_eq!(res.filled, 0);
+    assert_eq!(b.book.depth(&10_u32), (vec![&env, level(MID, 1_000)], vec![&env, level(MID + TICK, 1_000)]));
+}
+
+// ─────────────────────────────────────────────
+//  3. FEES
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_maker_and_taker_fees() {
+    let env = Env::default();
+    env.mock_all_auths();
+    // 0.1% maker, 0.3% taker
+    let b = create_book(&env, 10, 30);
+    let maker = Address::generate(&env);
+    b.xlm_s.mint(&maker, &100_000_i128);
+    b.book.place_order(&maker, &Side::Ask, &PRICE_SCALE, &100_000_i128);
+
+    let taker = Address::generate(&env);
+    b.usdc_s.mint(&taker, &100_000_i128);
+    b.book.place_order(&taker, &Side::Bid, &PRICE_SCALE, &100_000_i128);
+
+    assert_eq!(b.usdc.balance(&maker), 100_000 - 100);
+    assert_eq!(b.xlm.balance(&taker), 100_000 - 300);
+    assert_eq!(b.book.fees(), (300, 100));
+
+    let treasury = Address::generate(&env);
+    assert_eq!(b.book.withdraw_fees(&treasury), (300, 100));
+    assert_eq!(b.xlm.balance(&treasury), 300);
+    assert_eq!(b.usdc.balance(&treasury), 100);
+    assert_eq!(b.book.fees(), (0, 0));
+}
+
+#[test]
+fn test_fee_bounds() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    assert_eq!(b.book.try_set_fees(&0_u32, &1_001_u32), Err(Ok(BookError::BadFee.into())));
+    b.book.set_fees(&5_u32, &20_u32);
+    assert_eq!(b.book.fee_bps(), (5, 20));
+}
+
+// ─────────────────────────────────────────────
+//  4. CANCEL & VALIDATION
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_cancel_refunds_escrow() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let maker = Address::generate(&env);
+    b.xlm_s.mint(&maker, &1_000_i128);
+    b.usdc_s.mint(&maker, &1_000_i128);
+
+    let ask = b.book.place_order(&maker, &Side::Ask, &MID, &1_000_i128).order_id;
+    let bid = b.book.place_order(&maker, &Side::Bid, &(MID - TICK), &1_000_i128).order_id;
+
+    assert_eq!(b.book.cancel_order(&ask), 1_000);
+    assert_eq!(b.book.cancel_order(&bid), 1_000 * (MID - TICK) / PRICE_SCALE);
+    assert_eq!(b.xlm.balance(&maker), 1_000);
+    assert_eq!(b.usdc.balance(&maker), 1_000);
+    assert_eq!(b.book.depth(&10_u32), (vec![&env], vec![&env]));
+    assert_eq!(b.book.orders_of(&maker).len(), 0);
+    assert_eq!(b.book.try_cancel_order(&ask), Err(Ok(BookError::NoOrder.into())));
+}
+
+#[test]
+#[should_panic]
+fn test_cancel_requires_owner_auth() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let maker = Address::generate(&env);
+    b.xlm_s.mint(&maker, &1_000_i128);
+    let ask = b.book.place_order(&maker, &Side::Ask, &MID, &1_000_i128).order_id;
+
+    env.set_auths(&[]);
+    b.book.cancel_order(&ask);
+}
+
+#[test]
+fn test_place_order_validation() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let trader = Address::generate(&env);
+    assert_eq!(b.book.try_place_order(&trader, &Side::Bid, &MID, &0_i128), Err(Ok(BookError::ZeroAmount.into())));
+    assert_eq!(b.book.try_place_order(&trader, &Side::Ask, &0_i128, &10_i128), Err(Ok(BookError::BadPrice.into())));
+    assert_eq!(
+        b.book.try_initialize(&b.admin, &b.xlm.address, &b.usdc.address, &0_u32, &0_u32),
+        Err(Ok(BookError::AlreadyInit.into()))
+    );
+}
+
+// ─────────────────────────────────────────────
+//  5. BOOK LIMITS
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_minimum_order_size() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (xlm,  xlm_s)  = create_token(&env, &admin);
+    let (usdc, usdc_s) = create_token(&env, &admin);
+    let book = OrderBookClient::new(&env, &env.register(OrderBook, ()));
+    book.initialize(&admin, &xlm.address, &usdc.address, &0_u32, &0_u32);
+    assert_eq!(book.min_order(), DEFAULT_MIN_ORDER);
+
+    let trader = Address::generate(&env);
+    xlm_s.mint(&trader, &(10 * DEFAULT_MIN_ORDER));
+    assert_eq!(
+        book.try_place_order(&trader, &Side::Ask, &MID, &(DEFAULT_MIN_ORDER - 1)),
+        Err(Ok(BookError::BelowMinimum.into()))
+    );
+    let ask = book.place_order(&trader, &Side::Ask, &MID, &DEFAULT_MIN_ORDER);
+    assert_eq!(ask.remaining, DEFAULT_MIN_ORDER);
+
+    // A taker remainder too small to rest is refunded instead
+    let taker = Address::generate(&env);
+    let amount = DEFAULT_MIN_ORDER + DEFAULT_MIN_ORDER / 2;
+    usdc_s.mint(&taker, &(amount * MID / PRICE_SCALE));
+    let res = book.place_order(&taker, &Side::Bid, &MID, &amount);
+    assert_eq!(res, PlaceResult { order_id: 1, filled: DEFAULT_MIN_ORDER, remaining: 0, cancelled: DEFAULT_MIN_ORDER / 2 });
+    assert_eq!(usdc.balance(&taker), DEFAULT_MIN_ORDER / 2 * MID / PRICE_SCALE);
+    assert_eq!(book.orders_of(&taker).len(), 0);
+
+    assert_eq!(book.try_set_min_order(&0_i128), Err(Ok(BookError::ZeroAmount.into())));
+    book.set_min_order(&5_i128);
+    assert_eq!(book.min_order(), 5);
+}
+
+#[test]
+fn test_taker_stops_after_max_fills() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let makers = MAX_FILLS as i128 + 5;
+    for i in 0..makers {
+        let maker = Address::generate(&env);
+        b.xlm_s.mint(&maker, &10_i128);
+        let price = if i < MAX_FILLS as i128 { PRICE_SCALE - TICK } else { PRICE_SCALE };
+        b.book.place_order(&maker, &Side::Ask, &price, &10_i128);
+    }
+
+    // The part that would still cross is refunded rather than left
+    // resting in a crossed book; the untouched asks stay put
+    let taker = Address::generate(&env);
+    b.usdc_s.mint(&taker, &(makers * 10));
+    let res = b.book.place_order(&taker, &Side::Bid, &PRICE_SCALE, &(makers * 10));
+    assert_eq!(res.filled, MAX_FILLS as i128 * 10);
+    assert_eq!((res.remaining, res.cancelled), (0, 50));
+    assert_eq!(b.xlm.balance(&taker), MAX_FILLS as i128 * 10);
+    assert_eq!(b.usdc.balance(&taker), makers * 10 - MAX_FILLS as i128 * 9);
+    assert_eq!(b.book.depth(&5), (vec![&env], vec![&env, level(PRICE_SCALE, 50)]));
+
+    // A remainder that no longer crosses rests as usual
+    let res = b.book.place_order(&taker, &Side::Bid, &(PRICE_SCALE - TICK), &10_i128);
+    assert_eq!((res.filled, res.remaining, res.cancelled), (0, 10, 0));
+}
+
+#[test]
+fn test_price_levels_and_level_orders_are_capped() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let maker = Address::generate(&env);
+    b.xlm_s.mint(&maker, &1_000_000_i128);
+
+    for i in 0..MAX_PRICE_LEVELS as i128 {
+        b.book.place_order(&maker, &Side::Ask, &(MID + i * TICK), &1_i128);
+    }
+    let past_last = MID + MAX_PRICE_LEVELS as i128 * TICK;
+    assert_eq!(
+        b.book.try_place_order(&maker, &Side::Ask, &past_last, &1_i128),
+        Err(Ok(BookError::BookFull.into()))
+    );
+
+    // Existing prices still take orders, up to the per-level cap
+    for _ in 1..MAX_LEVEL_ORDERS {
+        b.book.place_order(&maker, &Side::Ask, &MID, &1_i128);
+    }
+    assert_eq!(
+        b.book.try_place_order(&maker, &Side::Ask, &MID, &1_i128),
+        Err(Ok(BookError::BookFull.into()))
+    );
+    assert_eq!(b.book.depth(&1).1, vec![&env, level(MID, MAX_LEVEL_ORDERS as i128)]);
+}
+
+#[test]
+fn test_full_book_still_takes_better_orders() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let b = create_book(&env, 0, 0);
+    let spammer = Address::generate(&env);
+    let maker   = Address::generate(&env);
+    b.usdc_s.mint(&spammer, &1_000_000_i128);
+    b.usdc_s.mint(&maker,   &1_000_000_i128);
+
+    // Fill every bid level with dust far below the market, the worst
+    // level with as many owners as it holds
+    for i in 0..MAX_PRICE_LEVELS as i128 {
+        b.book.place_order(&spammer, &Side::Bid, &(TICK + i * TICK), &1_i128);
+    }
+    let mut worst = vec![&env, b.book.orders_of(&spammer).first_unchecked()];
+    for _ in 1..MAX_LEVEL_ORDERS {
+        let owner = Address::generate(&env);
+        b.usdc_s.mint(&owner, &1_i128);
+        worst.push_back(b.book.place_order(&owner, &Side::Bid, &TICK, &1_i128).order_id);
+    }
+    assert_eq!(
+        b.book.try_place_order(&maker, &Side::Bid, &(TICK / 2), &1_000_i128),
+        Err(Ok(BookError::BookFull.into()))
+    );
+
+    // A better price still rests and pushes out the worst level
+    let quote = b.book.place_order(&maker, &Side::Bid, &MID, &1_000_i128);
+    assert_eq!(quote.remaining, 1_000);
+    let (bids, _) = b.book.depth(&1);
+    assert_eq!(bids, vec![&env, level(MID, 1_000)]);
+    for order_id in worst.iter() {
+        assert_eq!(b.book.try_get_order(&order_id), Err(Ok(BookError::NoOrder.into())));
+    }
+    assert_eq!(b.book.orders_of(&spammer).len(), MAX_PRICE_LEVELS - 1);
+    assert_eq!(b.usdc.balance(&spammer), 1_000_000 - (MAX_PRICE_LEVELS as i128 - 1));
+
+    // A full level takes a larger order in place of its smallest one
+    for _ in 1..MAX_LEVEL_ORDERS {
+        b.book.place_order(&spammer, &Side::Bid, &MID, &1_i128);
+    }
+    let newest = b.book.orders_of(&spammer).last_unchecked();
+    assert_eq!(
+        b.book.try_place_order(&maker, &Side::Bid, &MID, &1_i128),
+        Err(Ok(BookError::BookFull.into()))
+    );
+    b.book.place_order(&maker, &Side::Bid, &MID, &2_i128);
+    assert_eq!(b.book.try_get_order(&newest), Err(Ok(BookError::NoOrder.into())));
+    assert_eq!(b.book.depth(&1).0, vec![&env, level(MID, 1_000 + (MAX_LEVEL_ORDERS as i128 - 2) + 2)]);
+}
@@ -0,0 +1,128 @@
+#![no_std]
+
+use soroban_sdk::{
+    contract, contractclient, contracterror, contractimpl, contracttype, panic_with_error,
+    Address, Env, Vec,
+};
+
+// ─────────────────────────────────────────────
+//  Multi-hop router for StellarDex pools
+// ─────────────────────────────────────────────
+//  Chains exact-input swaps across any number of StellarDex deployments.
+//  Each hop names the pool and the token pair to trade there; the output
+//  of one hop is the input of the next. Every hop trades on behalf of the
+//  trader, so the router never holds funds, and a single `min_out` and
+//  deadline guard the whole path. A referrer, if given, is passed to
+//  every hop and earns its fee share in each pool.
+
+// ─────────────────────────────────────────────
+//  POOL INTERFACE
+// ─────────────────────────────────────────────
+#[contractclient(name = "PoolClient")]
+pub trait Pool {
+    fn swap(
+        env: Env,
+        trader: Address,
+        token_in: Address,
+        token_out: Address,
+        amount_in: i128,
+        min_out: i128,
+        referrer: Option<Address>,
+    ) -> i128;
+    fn quote(env: Env, token_in: Address, token_out: Address, amount_in: i128) -> i128;
+}
+
+// ─────────────────────────────────────────────
+//  TYPES
+// ─────────────────────────────────────────────
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hop {
+    pub pool:      Address,
+    pub token_in:  Address,
+    pub token_out: Address,
+}
+
+// ─────────────────────────────────────────────
+//  ERRORS
+// ─────────────────────────────────────────────
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum RouterError {
+    EmptyPath        = 1,
+    BrokenPath       = 2,
+    ZeroAmount       = 3,
+    DeadlinePassed   = 4,
+    SlippageExceeded = 5,
+}
+
+// ─────────────────────────────────────────────
+//  CONTRACT
+// ─────────────────────────────────────────────
+#[contract]
+pub struct Router;
+
+#[contractimpl]
+impl Router {
+
+    // ═══════════════════════════════════════
+    //  ROUTING
+    // ═══════════════════════════════════════
+
+    /// Swap `amount_in` along `path`, failing the whole route if the final
+    /// output is below `min_out` or the ledger timestamp is past `deadline`.
+    pub fn swap_path(
+        env: Env,
+        trader: Address,
+        path: Vec<Hop>,
+        amount_in: i128,
+        min_out: i128,
+        deadline: u64,
+        referrer: Option<Address>,
+    ) -> i128 {
+        trader.require_auth();
+        if env.ledger().timestamp() > deadline {
+            panic_with_error!(&env, RouterError::DeadlinePassed);
+        }
+        Self::check_path(&env, &path, amount_in);
+
+        let mut amount = amount_in;
+        for hop in path.iter() {
+            amount = PoolClient::new(&env, &hop.pool)
+                .swap(&trader, &hop.token_in, &hop.token_out, &amount, &0_i128, &referrer);
+        }
+
+        if amount < min_out { panic_with_error!(&env, RouterError::SlippageExceeded); }
+        amount
+    }
+
+    /// Expected output of `swap_path` at current pool rates.
+    pub fn quote_path(env: Env, path: Vec<Hop>, amount_in: i128) -> i128 {
+        Self::check_path(&env, &path, amount_in);
+
+        let mut amount = amount_in;
+        for hop in path.iter() {
+            amount = PoolClient::new(&env, &hop.pool)
+                .quote(&hop.token_in, &hop.token_out, &amount);
+        }
+        amount
+    }
+
+    // ═══════════════════════════════════════
+    //  INTERNAL HELPERS
+    // ═══════════════════════════════════════
+
+    /// Non-empty path whose hops are linked token to token.
+    fn check_path(env: &Env, path: &Vec<Hop>, amount_in: i128) {
+        if amount_in <= 0 { panic_with_error!(env, RouterError::ZeroAmount); }
+        if path.is_empty() { panic_with_error!(env, RouterError::EmptyPath); }
+        for i in 1..path.len() {
+            if path.get_unchecked(i - 1).token_out != path.get_unchecked(i).token_in {
+                panic_with_error!(env, RouterError::BrokenPath);
+            }
+        }
+    }
+}
+
+mod test;
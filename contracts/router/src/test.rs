@@ -0,0 +1,228 @@
+#![cfg(test)]
+
+extern crate std;
+
+use hello_world::{StellarDex, StellarDexClient};
+use soroban_sdk::{
+    testutils::{Address as _, Ledger},
+    token, vec, Address, Env, Vec,
+};
+
+use crate::{Hop, Router, RouterClient, RouterError};
+
+// ─────────────────────────────────────────────
+//  Helpers
+// ─────────────────────────────────────────────
+
+fn create_token<'a>(
+    env: &'a Env,
+    admin: &Address,
+) -> (token::Client<'a>, token::StellarAssetClient<'a>) {
+    let addr = env.register_stellar_asset_contract_v2(admin.clone()).address();
+    (
+        token::Client::new(env, &addr),
+        token::StellarAssetClient::new(env, &addr),
+    )
+}
+
+const USDC_RATE: i128 = 61_900_000;
+const ETH_RATE:  i128 = 121_200_000_000;
+
+/// Deploy a StellarDex over `xlm` plus a fresh USDC/ETH pair and seed
+/// it with `xlm`, `usdc` and `eth` reserves.
+fn create_pool<'a>(
+    env:   &'a Env,
+    admin: &Address,
+    xlm:   &token::StellarAssetClient,
+    seed:  (i128, i128, i128),
+) -> (StellarDexClient<'a>, token::StellarAssetClient<'a>, token::StellarAssetClient<'a>) {
+    let (_, usdc_s) = create_token(env, admin);
+    let (_, eth_s)  = create_token(env, admin);
+    let dex = StellarDexClient::new(env, &env.register(StellarDex, ()));
+    dex.initialize(admin, &xlm.address, &usdc_s.address, &eth_s.address, &USDC_RATE, &ETH_RATE);
+
+    let (x, u, e) = seed;
+    if x > 0 { xlm.mint(admin, &x); }
+    if u > 0 { usdc_s.mint(admin, &u); }
+    if e > 0 { eth_s.mint(admin, &e); }
+    dex.add_liquidity(admin, &x, &u, &e);
+    (dex, usdc_s, eth_s)
+}
+
+fn create_router(env: &Env) -> RouterClient<'_> {
+    RouterClient::new(env, &env.register(Router, ()))
+}
+
+fn hop(pool: &Address, token_in: &Address, token_out: &Address) -> Hop {
+    Hop { pool: pool.clone(), token_in: token_in.clone(), token_out: token_out.clone() }
+}
+
+// ─────────────────────────────────────────────
+//  1. ROUTING
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_swap_path_across_two_pools() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (xlm, xlm_s) = create_token(&env, &admin);
+    let (pool_a, usdc_a, _) = create_pool(&env, &admin, &xlm_s, (10_000_000_000, 0, 0));
+    let (pool_b, usdc_b, _) = create_pool(&env, &admin, &xlm_s, (0, 1_000, 0));
+    let router = create_router(&env);
+
+    // USDC(a) → XLM in pool A, then XLM → USDC(b) in pool B
+    let path = vec![
+        &env,
+        hop(&pool_a.address, &usdc_a.address, &xlm.address),
+        hop(&pool_b.address, &xlm.address, &usdc_b.address),
+    ];
+    let quoted = router.quote_path(&path, &100_i128);
+    let mid    = pool_a.quote_usdc_to_xlm(&100_i128);
+    assert_eq!(quoted, pool_b.quote_xlm_to_usdc(&mid));
+
+    let trader = Address::generate(&env);
+    usdc_a.mint(&trader, &100_i128);
+    let out = router.swap_path(&trader, &path, &100_i128, &quoted, &env.ledger().timestamp(), &None);
+
+    assert_eq!(out, quoted);
+    assert_eq!(token::Client::new(&env, &usdc_a.address).balance(&trader), 0);
+    assert_eq!(token::Client::new(&env, &usdc_b.address).balance(&trader), out);
+    // The intermediate XLM passed straight through
+    assert_eq!(xlm.balance(&trader), 0);
+    assert_eq!(pool_a.get_reserves(), (10_000_000_000 - mid, 100, 0));
+    assert_eq!(pool_b.get_reserves(), (mid, 1_000 - out, 0));
+}
+
+#[test]
+fn test_single_hop_matches_pool_swap() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (_, xlm_s) = create_token(&env, &admin);
+    let (pool, usdc, eth) = create_pool(&env, &admin, &xlm_s, (0, 0, 100));
+    let router = create_router(&env);
+
+    let path = vec![&env, hop(&pool.address, &usdc.address, &eth.address)];
+    let trader = Address::generate(&env);
+    usdc.mint(&trader, &10_000_i128);
+
+    let out = router.swap_path(&trader, &path, &10_000_i128, &0_i128, &u64::MAX, &None);
+    assert_eq!(out, pool.quote(&usdc.address, &eth.address, &10_000_i128));
+    assert_eq!(token::Client::new(&env, &eth.address).balance(&trader), out);
+}
+
+#[test]
+fn test_referrer_is_credited_in_every_hop() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (_, xlm_s) = create_token(&env, &admin);
+    let (pool_a, usdc_a, _) = create_pool(&env, &admin, &xlm_s, (200_000_000_000, 0, 0));
+    let (pool_b, usdc_b, _) = create_pool(&env, &admin, &xlm_s, (0, 10_000, 0));
+    let router = create_router(&env);
+
+    let path = vec![
+        &env,
+        hop(&pool_a.address, &usdc_a.address, &xlm_s.address),
+        hop(&pool_b.address, &xlm_s.address, &usdc_b.address),
+    ];
+    let trader  = Address::generate(&env);
+    let partner = Address::generate(&env);
+    usdc_a.mint(&trader, &2_000_i128);
+    pool_a.set_referrer_bps(&partner, &Some(2_000_u32));
+    pool_b.set_referrer_bps(&partner, &Some(2_000_u32));
+
+    let xlm_mid = pool_a.quote(&usdc_a.address, &xlm_s.address, &2_000_i128);
+    router.swap_path(&trader, &path, &2_000_i128, &0_i128, &u64::MAX, &Some(partner.clone()));
+    assert_eq!(pool_a.referral_balance(&partner, &usdc_a.address), 1);
+    assert_eq!(
+        pool_b.referral_balance(&partner, &xlm_s.address),
+        xlm_mid * 3 / 1000 * pool_b.referral_bps(&partner) as i128 / 10_000
+    );
+}
+
+// ─────────────────────────────────────────────
+//  2. GUARDS
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_min_out_reverts_whole_path() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (xlm, xlm_s) = create_token(&env, &admin);
+    let (pool_a, usdc_a, _) = create_pool(&env, &admin, &xlm_s, (10_000_000_000, 0, 0));
+    let (pool_b, usdc_b, _) = create_pool(&env, &admin, &xlm_s, (0, 1_000, 0));
+    let router = create_router(&env);
+
+    let path = vec![
+        &env,
+        hop(&pool_a.address, &usdc_a.address, &xlm.address),
+        hop(&pool_b.address, &xlm.address, &usdc_b.address),
+    ];
+    let quoted = router.quote_path(&path, &100_i128);
+    let trader = Address::generate(&env);
+    usdc_a.mint(&trader, &100_i128);
+
+    assert_eq!(
+        router.try_swap_path(&trader, &path, &100_i128, &(quoted + 1), &u64::MAX, &None),
+        Err(Ok(RouterError::SlippageExceeded.into()))
+    );
+    assert_eq!(token::Client::new(&env, &usdc_a.address).balance(&trader), 100);
+    assert_eq!(pool_a.get_reserves(), (10_000_000_000, 0, 0));
+    assert_eq!(pool_b.get_reserves(), (0, 1_000, 0));
+}
+
+#[test]
+fn test_deadline_passed() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (xlm, xlm_s) = create_token(&env, &admin);
+    let (pool, usdc, _) = create_pool(&env, &admin, &xlm_s, (0, 1_000, 0));
+    let router = create_router(&env);
+    env.ledger().set_timestamp(1_000);
+
+    let path = vec![&env, hop(&pool.address, &usdc.address, &xlm.address)];
+    let trader = Address::generate(&env);
+    usdc.mint(&trader, &100_i128);
+
+    assert_eq!(
+        router.try_swap_path(&trader, &path, &100_i128, &0_i128, &999_u64, &None),
+        Err(Ok(RouterError::DeadlinePassed.into()))
+    );
+}
+
+#[test]
+fn test_invalid_paths() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let admin = Address::generate(&env);
+    let (xlm, xlm_s) = create_token(&env, &admin);
+    let (pool, usdc, eth) = create_pool(&env, &admin, &xlm_s, (0, 1_000, 0));
+    let router = create_router(&env);
+
+    let empty: Vec<Hop> = Vec::new(&env);
+    assert_eq!(
+        router.try_quote_path(&empty, &100_i128),
+        Err(Ok(RouterError::EmptyPath.into()))
+    );
+
+    // USDC → XLM followed by ETH → USDC does not connect
+    let broken = vec![
+        &env,
+        hop(&pool.address, &usdc.address, &xlm.address),
+        hop(&pool.address, &eth.address, &usdc.address),
+    ];
+    assert_eq!(
+        router.try_quote_path(&broken, &100_i128),
+        Err(Ok(RouterError::BrokenPath.into()))
+    );
+
+    let path = vec![&env, hop(&pool.address, &usdc.address, &xlm.address)];
+    assert_eq!(
+        router.try_quote_path(&path, &0_i128),
+        Err(Ok(RouterError::ZeroAmount.into()))
+    );
+}